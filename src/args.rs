@@ -0,0 +1,499 @@
+use std::path::PathBuf;
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
+
+/// Arguments of the whole program. Without a subcommand passman starts the REPL.
+#[derive(Parser)]
+#[command(name = "passman", version, about = "Prosty menedżer haseł obsługiwany z konsoli.")]
+pub struct Args {
+    #[arg(long, value_name = "FD", conflicts_with = "password_env",
+        help = "Odczytaj główne hasło z podanego deskryptora pliku")]
+    pub password_fd: Option<i32>,
+
+    #[arg(long, value_name = "ZMIENNA",
+        help = "Odczytaj główne hasło z podanej zmiennej środowiskowej")]
+    pub password_env: Option<String>,
+
+    #[arg(long, value_name = "ŚCIEŻKA", conflicts_with = "profile",
+        help = "Użyj sejfu z podanego pliku (zamiast zmiennej PASSMAN_VAULT i pliku konfiguracyjnego)")]
+    pub vault: Option<PathBuf>,
+
+    #[arg(long, value_name = "NAZWA",
+        help = "Użyj sejfu z profilu o podanej nazwie z pliku konfiguracyjnego")]
+    pub profile: Option<String>,
+
+    #[arg(long, help = "Otwórz sejf tylko do odczytu, bez zapisywania żadnych zmian")]
+    pub read_only: bool,
+
+    // Used internally by the process which clears the clipboard later
+    #[arg(long, value_name = "SEKUNDY", hide = true)]
+    pub clear_clipboard_after: Option<u64>,
+
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+/// Subcommands - all commands plus the ones that only make sense without an unlocked vault
+#[derive(Subcommand)]
+pub enum CliCommand {
+    #[command(about = "Utwórz pusty sejf z głównym hasłem z terminala, --password-fd albo --password-env")]
+    Init {
+        #[arg(long, help = "Użyj głównego hasła, nawet jeśli jest słabsze niż minimum z pliku konfiguracyjnego")]
+        allow_weak: bool,
+    },
+    #[command(flatten)]
+    Command(Box<Command>),
+}
+
+/// Commands available both as subcommands and inside the REPL
+#[derive(Subcommand)]
+pub enum Command {
+    #[command(about = "Wygeneruj losowe hasło z podaną etykietą")]
+    New {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+        #[arg(value_name = "DŁUGOŚĆ", value_parser = parse_length)]
+        length: Option<usize>,
+        #[command(flatten)]
+        generator: GeneratorArgs,
+        #[command(flatten)]
+        passphrase: PassphraseArgs,
+        #[command(flatten)]
+        details: EntryDetails,
+    },
+
+    #[command(about = "Wypisz losowe hasło albo frazę bez zapisywania jej w sejfie")]
+    Generate {
+        #[arg(value_name = "DŁUGOŚĆ", value_parser = parse_length)]
+        length: Option<usize>,
+        #[command(flatten)]
+        generator: GeneratorArgs,
+        #[command(flatten)]
+        passphrase: PassphraseArgs,
+    },
+
+    #[command(about = "Dodaj nowe hasło z podaną etykietą")]
+    Add {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+        #[arg(long, help = "Odczytaj hasło jako jedną linię ze standardowego wejścia")]
+        stdin: bool,
+        #[arg(long, help = "Zapisz hasło, nawet jeśli jest słabsze niż minimum z pliku konfiguracyjnego")]
+        allow_weak: bool,
+        #[command(flatten)]
+        details: EntryDetails,
+    },
+
+    #[command(about = "Przenieś hasło z podaną etykietą do kosza")]
+    Remove {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+        #[arg(short, long, help = "Nie pytaj o potwierdzenie")]
+        yes: bool,
+    },
+
+    #[command(about = "Przenieś hasło albo cały folder, np. mv praca/aws chmura/ (z / na końcu - do środka folderu)")]
+    Mv {
+        #[arg(value_name = "NAZWA_LUB_FOLDER")]
+        source: String,
+        #[arg(value_name = "CEL")]
+        destination: String,
+    },
+
+    #[command(about = "Wypisz drzewo etykiet folderu albo całego sejfu")]
+    Ls {
+        #[arg(value_name = "FOLDER")]
+        folder: Option<String>,
+    },
+
+    #[command(about = "Zmień etykietę hasła, bez zmiany jego danych")]
+    Rename {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+        #[arg(value_name = "NOWA_NAZWA")]
+        new_label: String,
+    },
+
+    #[command(about = "Ustaw nowe hasło z podaną etykietą, poprzednie zostaje w historii")]
+    Edit {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+        #[arg(long, help = "Odczytaj hasło jako jedną linię ze standardowego wejścia")]
+        stdin: bool,
+        #[arg(long, help = "Zapisz hasło, nawet jeśli jest słabsze niż minimum z pliku konfiguracyjnego")]
+        allow_weak: bool,
+    },
+
+    #[command(about = "Wygeneruj nowe hasło z podaną etykietą według tych samych reguł, poprzednie zostaje w historii")]
+    Rotate {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+        #[arg(value_name = "DŁUGOŚĆ", value_parser = parse_length)]
+        length: Option<usize>,
+        #[command(flatten)]
+        generator: GeneratorArgs,
+        #[command(flatten)]
+        passphrase: PassphraseArgs,
+    },
+
+    #[command(about = "Wypisz, kiedy zmieniono poprzednie hasła z podaną etykietą (bez samych haseł)")]
+    History {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+    },
+
+    #[command(about = "Przywróć poprzednie hasło z historii, obecne zostaje zapisane w historii")]
+    Restore {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+        #[arg(value_name = "NUMER", value_parser = clap::value_parser!(usize), help = "Numer hasła z listy komendy history, 1 to najnowsze")]
+        number: usize,
+        #[arg(short, long, help = "Nie pytaj o potwierdzenie")]
+        yes: bool,
+    },
+
+    #[command(about = "Pokaż usunięte hasła, przywróć je albo opróżnij kosz")]
+    Trash {
+        #[command(subcommand)]
+        action: Option<TrashAction>,
+    },
+
+    #[command(about = "Znajdź hasła po etykiecie, nazwie użytkownika, adresie albo tagu i wybierz jedno do skopiowania")]
+    Find {
+        #[arg(value_name = "SZUKANE", required = true, help = "Słowa, z których każde musi pasować do jednego z pól. Litery nie muszą być obok siebie, np. gthb pasuje do github")]
+        query: Vec<String>,
+        #[arg(long, help = "Tylko wypisz wyniki, bez wybierania hasła")]
+        list: bool,
+    },
+
+    #[command(about = "Skopiuj do schowka hasło z podaną etykietą")]
+    Get {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+        #[arg(long, help = "Wypisz hasło na standardowe wyjście zamiast kopiować je do schowka")]
+        stdout: bool,
+    },
+
+    #[command(about = "Skopiuj do schowka obecny kod TOTP hasła z podaną etykietą")]
+    Totp {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+        #[arg(long, help = "Wypisz kod na standardowe wyjście zamiast kopiować go do schowka")]
+        stdout: bool,
+    },
+
+    #[command(about = "Pokaż hasło z podaną etykietą na chwilę, na osobnym ekranie terminala")]
+    Show {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+        #[arg(long, value_enum, default_value_t = Secret::Password, help = "Co pokazać")]
+        field: Secret,
+        #[arg(long, help = "Pokaż tylko kilka znaków z początku i końca")]
+        mask: bool,
+        #[arg(long, value_name = "SEKUNDY", help = "Ukryj po tym czasie zamiast po czasie z pliku konfiguracyjnego, 0 - dopiero po naciśnięciu klawisza")]
+        timeout: Option<u64>,
+    },
+
+    #[command(about = "Pokaż dane hasła z podaną etykietą (bez samego hasła)")]
+    Info {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+    },
+
+    #[command(about = "Ustaw pole hasła z podaną etykietą")]
+    Set {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+        #[arg(value_name = "POLE")]
+        field: Field,
+        #[arg(value_name = "WARTOŚĆ", help = "Nowa wartość, dla url i tags można podać kilka. Notatka i sekret TOTP są zawsze czytane z wejścia")]
+        values: Vec<String>,
+        #[arg(long, help = "Dopisz adresy albo tagi do obecnych zamiast je zastąpić")]
+        append: bool,
+    },
+
+    #[command(about = "Wyczyść pole hasła z podaną etykietą")]
+    Unset {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+        #[arg(value_name = "POLE")]
+        field: Field,
+    },
+
+    #[command(about = "Zmień główne hasło")]
+    ChangePassword {
+        #[arg(short, long, help = "Nie pytaj o potwierdzenie")]
+        yes: bool,
+        #[arg(long, help = "Odczytaj nowe hasło jako jedną linię ze standardowego wejścia")]
+        stdin: bool,
+        #[arg(long, help = "Ustaw hasło, nawet jeśli jest słabsze niż minimum z pliku konfiguracyjnego")]
+        allow_weak: bool,
+    },
+
+    #[command(about = "Wypisz kopie zapasowe sejfu albo przywróć kopię o podanym numerze")]
+    RestoreBackup {
+        #[arg(value_name = "NUMER", value_parser = clap::value_parser!(usize), help = "Numer kopii z listy, 1 to najnowsza")]
+        number: Option<usize>,
+        #[arg(short, long, help = "Nie pytaj o potwierdzenie")]
+        yes: bool,
+        #[arg(long, help = "Odczytaj hasło kopii jako jedną linię ze standardowego wejścia, jeśli jest inne niż obecne")]
+        stdin: bool,
+    },
+
+    #[command(about = "Sprawdź wszystkie hasła: powtórzone, słabe, stare i wpisy bez danych")]
+    Audit {
+        #[arg(long, help = "Wypisz wynik w formacie JSON")]
+        json: bool,
+        #[arg(long, value_name = "DNI", help = "Wiek, po którym hasło jest uznawane za stare (0 wyłącza sprawdzanie)")]
+        max_age: Option<u64>,
+        #[arg(long, value_name = "ŚCIEŻKA", help = "Sprawdź hasła w lokalnej kopii Pwned Passwords (folder plików zakresów albo posortowany plik skrótów SHA-1)")]
+        breached: Option<PathBuf>,
+    },
+
+    #[command(about = "Zaimportuj hasła z eksportu Bitwardena, KeePassa, 1Password albo pliku CSV (np. z przeglądarki)")]
+    Import {
+        #[arg(value_name = "PLIK")]
+        path: PathBuf,
+        #[arg(long, value_enum, help = "Format pliku, domyślnie rozpoznawany po rozszerzeniu (.json, .xml, .1pux, .csv)")]
+        format: Option<ImportFormat>,
+        #[arg(long, value_name = "POLE=KOLUMNA", value_delimiter = ',',
+            help = "Kolumny CSV z polami label, password, username, url, notes, tags i totp, gdy mają nietypowe nazwy")]
+        columns: Vec<String>,
+        #[arg(long, value_enum, default_value_t = OnConflict::Ask, help = "Co zrobić, gdy hasło z taką etykietą już istnieje")]
+        on_conflict: OnConflict,
+        #[arg(long, help = "Tylko pokaż, co zostałoby zaimportowane, bez zapisywania")]
+        dry_run: bool,
+    },
+
+    #[command(about = "Wyeksportuj wszystkie hasła do pliku zaszyfrowanego osobnym hasłem albo, po potwierdzeniu, do CSV lub JSON")]
+    Export {
+        #[arg(value_name = "PLIK")]
+        path: PathBuf,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Encrypted, help = "Format pliku")]
+        format: ExportFormat,
+        #[arg(short, long, help = "Nie pytaj o potwierdzenie zapisu haseł bez szyfrowania")]
+        yes: bool,
+        #[arg(long, help = "Odczytaj hasło eksportu (albo główne hasło przy eksporcie bez szyfrowania) jako jedną linię ze standardowego wejścia")]
+        stdin: bool,
+        #[arg(long, help = "Użyj hasła eksportu, nawet jeśli jest słabsze niż minimum z pliku konfiguracyjnego")]
+        allow_weak: bool,
+    },
+
+    #[command(about = "Pokaż albo zmień parametry tworzenia klucza z głównego hasła (Argon2)")]
+    Kdf {
+        #[command(subcommand)]
+        action: Option<KdfAction>,
+    },
+}
+
+/// Metadata which can be given when creating an entry
+#[derive(ClapArgs)]
+pub struct EntryDetails {
+    #[arg(long, value_name = "NAZWA_UŻYTKOWNIKA", help = "Nazwa użytkownika")]
+    pub username: Option<String>,
+    #[arg(long = "url", value_name = "URL", help = "Adres strony, można podać kilka razy")]
+    pub urls: Vec<String>,
+    #[arg(long, help = "Zapytaj o notatkę (zapisywaną w postaci zaszyfrowanej), bez --stdin czytaną z terminala")]
+    pub notes: bool,
+    #[arg(long = "tag", value_name = "TAG", help = "Tag, można podać kilka razy")]
+    pub tags: Vec<String>,
+    #[arg(long, help = "Zapytaj o sekret TOTP - adres otpauth://totp/... albo sekret w base32, czytany bez wyświetlania")]
+    pub totp: bool,
+}
+
+/// Rules for the generated password, override the ones of the chosen preset
+#[derive(ClapArgs)]
+pub struct GeneratorArgs {
+    #[arg(long, value_name = "NAZWA", help = "Użyj zestawu reguł generatora z pliku konfiguracyjnego")]
+    pub preset: Option<String>,
+    #[arg(long, value_name = "KLASY", value_delimiter = ',', help = "Klasy znaków hasła, oddzielone przecinkami")]
+    pub chars: Vec<CharClass>,
+    #[arg(long, value_name = "ZNAKI", help = "Znaki, których nie może być w haśle")]
+    pub exclude: Option<String>,
+    #[arg(long, help = "Pomiń podobne do siebie znaki, np. 1, l i I")]
+    pub no_similar: bool,
+    #[arg(long, value_name = "N", help = "Minimalna liczba małych liter")]
+    pub min_lower: Option<usize>,
+    #[arg(long, value_name = "N", help = "Minimalna liczba wielkich liter")]
+    pub min_upper: Option<usize>,
+    #[arg(long, value_name = "N", help = "Minimalna liczba cyfr")]
+    pub min_digits: Option<usize>,
+    #[arg(long, value_name = "N", help = "Minimalna liczba symboli")]
+    pub min_symbols: Option<usize>,
+}
+
+impl GeneratorArgs {
+    /// True if any rule was given
+    pub fn is_set(&self) -> bool {
+        self.preset.is_some() || !self.chars.is_empty() || self.exclude.is_some() || self.no_similar
+            || self.min_lower.is_some() || self.min_upper.is_some() || self.min_digits.is_some() || self.min_symbols.is_some()
+    }
+}
+
+/// Diceware-style passphrase instead of a random password
+#[derive(ClapArgs)]
+pub struct PassphraseArgs {
+    #[arg(long, value_name = "N", value_parser = parse_length,
+        conflicts_with_all = ["length", "preset", "chars", "exclude", "no_similar", "min_lower", "min_upper", "min_digits", "min_symbols"],
+        help = "Wygeneruj frazę z podanej liczby losowych słów zamiast hasła")]
+    pub words: Option<usize>,
+    #[arg(long, value_name = "ZNAKI", default_value = "-", requires = "words", help = "Separator słów frazy")]
+    pub separator: String,
+    #[arg(long, value_name = "STYL", value_enum, default_value_t = Case::Lower, requires = "words", help = "Wielkość liter słów frazy")]
+    pub case: Case,
+    #[arg(long, value_name = "N", default_value_t = 0, requires = "words",
+        help = "Liczba losowych cyfr dopisanych do losowych słów frazy")]
+    pub digits: usize,
+}
+
+/// Capitalization of passphrase words
+#[derive(ValueEnum, Clone, Copy)]
+pub enum Case {
+    #[value(help = "same małe litery")]
+    Lower,
+    #[value(help = "każde słowo wielką literą")]
+    Title,
+    #[value(help = "losowe słowa wielką literą")]
+    Random,
+}
+
+/// Character classes of generated passwords
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum CharClass {
+    Lower,
+    Upper,
+    Digits,
+    Symbols,
+}
+
+/// Formats of exports `import` reads
+#[derive(ValueEnum, Clone, Copy)]
+pub enum ImportFormat {
+    #[value(help = "niezaszyfrowany eksport JSON")]
+    Bitwarden,
+    #[value(help = "eksport XML KeePassa 2 albo KeePassXC")]
+    Keepass,
+    #[value(name = "1password", help = "archiwum 1PUX")]
+    OnePassword,
+    #[value(help = "CSV z nagłówkiem, np. z Chrome albo Firefoksa")]
+    Csv,
+}
+
+/// Formats of files written by `export`
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    #[value(help = "kopia sejfu zaszyfrowana osobnym hasłem")]
+    Encrypted,
+    #[value(help = "CSV bez szyfrowania")]
+    Csv,
+    #[value(help = "JSON bez szyfrowania")]
+    Json,
+}
+
+/// What `import` does with entries whose label is already taken
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum OnConflict {
+    #[value(help = "pytaj o każdy wpis")]
+    Ask,
+    #[value(help = "dodaj numer do etykiety")]
+    Rename,
+    #[value(help = "pomiń wpis")]
+    Skip,
+}
+
+/// Editable metadata fields of an entry
+#[derive(ValueEnum, Clone, Copy)]
+pub enum Field {
+    Username,
+    Url,
+    Notes,
+    Tags,
+    Totp,
+}
+
+/// Encrypted fields of an entry `show` can reveal
+#[derive(ValueEnum, Clone, Copy)]
+pub enum Secret {
+    Password,
+    Notes,
+    #[value(help = "sekret TOTP, nie kod")]
+    Totp,
+}
+
+#[derive(Subcommand)]
+pub enum KdfAction {
+    #[command(about = "Pokaż obecne parametry i czas odblokowania")]
+    Show,
+
+    #[command(about = "Ustaw podane parametry i zaszyfruj sejf na nowo")]
+    Set {
+        #[arg(long, value_name = "KiB", help = "Ilość pamięci w KiB")]
+        memory: Option<u32>,
+        #[arg(long, help = "Liczba przebiegów")]
+        iterations: Option<u32>,
+        #[arg(long, help = "Liczba wątków")]
+        parallelism: Option<u32>,
+        #[arg(short, long, help = "Nie pytaj o potwierdzenie słabszych parametrów")]
+        yes: bool,
+        #[arg(long, help = "Odczytaj główne hasło jako jedną linię ze standardowego wejścia")]
+        stdin: bool,
+    },
+
+    #[command(about = "Dobierz parametry do tego komputera, tak by odblokowanie trwało podany czas")]
+    Calibrate {
+        #[arg(value_name = "MS", default_value_t = 500, help = "Docelowy czas odblokowania w milisekundach")]
+        target_ms: u64,
+        #[arg(long, default_value_t = 1, help = "Liczba wątków")]
+        parallelism: u32,
+        #[arg(short, long, help = "Nie pytaj o potwierdzenie słabszych parametrów")]
+        yes: bool,
+        #[arg(long, help = "Odczytaj główne hasło jako jedną linię ze standardowego wejścia")]
+        stdin: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TrashAction {
+    #[command(about = "Wypisz usunięte hasła, ostatnio usunięte najpierw")]
+    List,
+
+    #[command(about = "Przywróć ostatnio usunięte hasło z podaną etykietą")]
+    Restore {
+        #[arg(value_name = "NAZWA")]
+        label: String,
+    },
+
+    #[command(about = "Usuń na dobre wszystkie hasła z kosza")]
+    Purge {
+        #[arg(short, long, help = "Nie pytaj o potwierdzenie")]
+        yes: bool,
+    },
+}
+
+/// A single line typed into the REPL
+#[derive(Parser)]
+#[command(no_binary_name = true, disable_help_flag = true, disable_help_subcommand = true)]
+pub struct ReplLine {
+    #[command(subcommand)]
+    pub command: ReplCommand,
+}
+
+/// Commands of the REPL - all subcommands plus the ones that only make sense in a session
+#[derive(Subcommand)]
+pub enum ReplCommand {
+    #[command(flatten)]
+    Command(Box<Command>),
+    #[command(about = "Przełącz na inny sejf, odblokowując go w razie potrzeby")]
+    Use {
+        #[arg(value_name = "PROFIL_LUB_ŚCIEŻKA")]
+        name: Option<String>,
+    },
+    Help,
+    Exit,
+}
+
+fn parse_length(input: &str) -> Result<usize, String> {
+    match input.parse::<usize>() {
+        Ok(len) if len > 0 => Ok(len),
+        _ => Err(String::from("Długość hasła musi być dodatnią liczbą całkowitą!")),
+    }
+}
@@ -0,0 +1,82 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Replaces contents of the file so that after a crash it holds either
+/// the old or the new data, never a mix. Data goes to a temporary file
+/// in the same directory, which is flushed to disk and renamed over the target.
+/// The file is readable only by its owner (0600 on Unix), or keeps its mode if it is stricter.
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    // Parent of a bare file name is empty, not the current directory
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let tmp_path = temp_path(path);
+    // Left over after a crash, it could have a different mode
+    let _ = fs::remove_file(&tmp_path);
+    let result = (|| {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, private_mode(path));
+
+        let mut file = options.open(&tmp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        // Don't leave half written temporary file behind, the original is untouched
+        let _ = fs::remove_file(&tmp_path);
+        return result;
+    }
+
+    sync_dir(dir)
+}
+
+/// Writes data to a new file readable only by its owner (0600 on Unix).
+/// Fails if the file already exists, so nothing gets overwritten.
+pub fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    let result = file.write_all(data).and_then(|_| file.sync_all());
+    if result.is_err() {
+        // Don't leave a partial export behind
+        let _ = fs::remove_file(path);
+    }
+    result
+}
+
+/// Owner read and write, without the permissions the existing file doesn't have
+#[cfg(unix)]
+fn private_mode(path: &Path) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.permissions().mode() & 0o600,
+        Err(_) => 0o600,
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Makes the rename itself durable
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+// Directories can't be opened as files on Windows, renames are journaled by NTFS
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
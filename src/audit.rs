@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+use crate::breach::BreachedPasswords;
+use crate::error::Result;
+use crate::password_storage::now;
+use crate::strength::estimate_strength;
+use crate::vault::Vault;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// What `audit` reports as a problem
+#[derive(Clone, Debug)]
+pub struct AuditOptions {
+    /// Passwords with lower strength score are weak, see `estimate_strength`
+    pub min_score: u8,
+    /// Passwords older than this many days should be changed, 0 turns the check off
+    pub max_age_days: u64,
+    /// Local copy of Pwned Passwords to look passwords up in, see `BreachedPasswords`
+    pub breached: Option<PathBuf>,
+}
+
+/// Problems found in the vault, labels are sorted
+#[derive(Serialize, Debug, Default)]
+pub struct Audit {
+    /// Number of checked entries
+    pub entries: usize,
+    /// Groups of labels with the same password
+    pub reused: Vec<Vec<String>>,
+    pub weak: Vec<WeakEntry>,
+    pub old: Vec<OldEntry>,
+    pub missing_username: Vec<String>,
+    pub missing_url: Vec<String>,
+    /// Groups of labels which differ only by case
+    pub case_duplicates: Vec<Vec<String>>,
+    /// Entries with passwords found in breaches, None if they weren't checked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breached: Option<Vec<BreachedEntry>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WeakEntry {
+    pub label: String,
+    pub score: u8,
+    /// Estimated number of guesses, as a power of 10
+    pub guesses_log10: f64,
+    pub crack_time: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BreachedEntry {
+    pub label: String,
+    /// How many times the password appeared in breaches
+    pub count: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct OldEntry {
+    pub label: String,
+    pub age_days: u64,
+}
+
+impl Audit {
+    pub fn is_clean(&self) -> bool {
+        self.reused.is_empty() && self.weak.is_empty() && self.old.is_empty()
+            && self.missing_username.is_empty() && self.missing_url.is_empty() && self.case_duplicates.is_empty()
+            && self.breached.as_ref().is_none_or(Vec::is_empty)
+    }
+}
+
+/// Decrypts all passwords of the vault and looks for reused, weak and old ones,
+/// entries without a user name or URL and labels differing only by case.
+/// With a local copy of Pwned Passwords also for passwords from breaches.
+pub fn audit(vault: &Vault, options: &AuditOptions) -> Result<Audit> {
+    let breached = options.breached.as_ref().map(BreachedPasswords::new).transpose()?;
+    let mut labels: Vec<&str> = vault.iter_labels().collect();
+    labels.sort();
+
+    let mut report = Audit { entries: labels.len(), breached: breached.as_ref().map(|_| Vec::new()), ..Audit::default() };
+    // Only hashes of passwords are kept to find the same ones
+    let mut by_password: HashMap<[u8; 32], Vec<String>> = HashMap::new();
+    let mut by_lowercase: HashMap<String, Vec<String>> = HashMap::new();
+    let now = now();
+
+    for label in labels {
+        let entry = vault.entry(label)?;
+        let password = Zeroizing::new(vault.get(label)?);
+
+        by_password.entry(Sha256::digest(password.as_bytes()).into()).or_default().push(label.to_string());
+        by_lowercase.entry(label.to_lowercase()).or_default().push(label.to_string());
+
+        let mut user_inputs = vec![label];
+        user_inputs.extend(entry.username.as_deref());
+        let strength = estimate_strength(&password, &user_inputs);
+        if strength.score < options.min_score {
+            report.weak.push(WeakEntry {
+                label: label.to_string(),
+                score: strength.score,
+                guesses_log10: strength.guesses.log10(),
+                crack_time: strength.crack_time_display(),
+            });
+        }
+
+        // Entries from before timestamps were saved have 0, their age is unknown
+        let changed = entry.password_changed();
+        let age_days = now.saturating_sub(changed) / DAY;
+        if options.max_age_days > 0 && changed > 0 && age_days > options.max_age_days {
+            report.old.push(OldEntry { label: label.to_string(), age_days });
+        }
+
+        if let (Some(breached), Some(found)) = (&breached, &mut report.breached) {
+            let count = breached.count(&password)?;
+            if count > 0 {
+                found.push(BreachedEntry { label: label.to_string(), count });
+            }
+        }
+
+        if entry.username.as_deref().is_none_or(str::is_empty) {
+            report.missing_username.push(label.to_string());
+        }
+        if entry.urls.is_empty() {
+            report.missing_url.push(label.to_string());
+        }
+    }
+
+    report.reused = groups(by_password);
+    report.case_duplicates = groups(by_lowercase);
+    Ok(report)
+}
+
+/// Groups of more than one label, sorted by their first label
+fn groups<K>(map: HashMap<K, Vec<String>>) -> Vec<Vec<String>> {
+    let mut groups: Vec<Vec<String>> = map.into_values().filter(|labels| labels.len() > 1).collect();
+    groups.sort();
+    groups
+}
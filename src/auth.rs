@@ -1,8 +1,10 @@
-use argon2::{Argon2};
+use argon2::{Algorithm, Argon2, Params, Version};
 use rand::TryRngCore;
+use std::fmt;
 use std::fs::{self, File};
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
 use rand::rngs::OsRng;
 
 use aes_gcm::{
@@ -10,100 +12,157 @@ use aes_gcm::{
     Aes256Gcm, Nonce, Key
 };
 
-const SALT_FILE: &str = "passman_data/salt.bin";
-const VERIFY_FILE: &str = "passman_data/verify.bin";
+use zeroize::Zeroizing;
 
-/// Checks if salt file and verification token files exist - if password was set before
-pub fn master_password_exists() -> bool {
-    PathBuf::from(get_path(SALT_FILE)).exists() && PathBuf::from(get_path(VERIFY_FILE)).exists()
+use crate::error::{Error, Result};
+
+// Files used before everything was moved to a single vault file,
+// they were kept in the same directory
+const LEGACY_SALT_FILE: &str = "salt.bin";
+const LEGACY_VERIFY_FILE: &str = "verify.bin";
+
+// Calibration never goes above this much memory
+const MAX_CALIBRATION_MEMORY_KIB: u32 = 1024 * 1024;
+
+/// Parameters of the Argon2 key derivation, stored in the vault header
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KdfParams {
+    /// 0 = Argon2d, 1 = Argon2i, 2 = Argon2id
+    pub algorithm: u8,
+    pub version: u32,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
 }
 
-/// Generates a new random salt, stores it in a file and returns it
-pub fn generate_and_store_salt() -> [u8; 16] {
-    let mut salt = [0u8; 16];
-    OsRng.try_fill_bytes(&mut salt).expect("Error when creating salt");
+impl Default for KdfParams {
+    /// Same parameters as `Argon2::default()`, which was used before they were stored
+    fn default() -> Self {
+        KdfParams {
+            algorithm: 2,
+            version: 0x13,
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    /// Builds Argon2 instance, fails if parameters are invalid
+    pub fn argon2(&self) -> Result<Argon2<'static>> {
+        let algorithm = match self.algorithm {
+            0 => Algorithm::Argon2d,
+            1 => Algorithm::Argon2i,
+            2 => Algorithm::Argon2id,
+            _ => return Err(Error::Kdf(format!("nieznany wariant algorytmu: {}", self.algorithm))),
+        };
+        let version = Version::try_from(self.version).map_err(|e| Error::Kdf(e.to_string()))?;
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| Error::Kdf(e.to_string()))?;
+
+        Ok(Argon2::new(algorithm, version, params))
+    }
 
-    let path = PathBuf::from(get_path(SALT_FILE));
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).expect("Unable to create salt directory");
+    /// True if any of the costs is lower than in other
+    pub fn is_weaker_than(&self, other: &KdfParams) -> bool {
+        self.memory_kib < other.memory_kib || self.iterations < other.iterations
     }
+}
 
-    let mut file = File::create(&path).expect("Unable to create salt file");
-    file.write_all(&salt).expect("Unable to write salt to file");
+impl fmt::Display for KdfParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let algorithm = match self.algorithm {
+            0 => "Argon2d",
+            1 => "Argon2i",
+            2 => "Argon2id",
+            _ => "?",
+        };
+        write!(f, "{} (wersja {:#x}), pamięć {} KiB, iteracje {}, wątki {}",
+            algorithm, self.version, self.memory_kib, self.iterations, self.parallelism)
+    }
+}
 
-    return salt;
+/// Measures how long it takes to derive a key with given parameters
+pub fn measure_kdf(kdf: &KdfParams) -> Result<Duration> {
+    let start = Instant::now();
+    derive_master_key("calibration", &[0u8; 16], kdf)?;
+    Ok(start.elapsed())
 }
 
-/// Loads the salt from the file, returns None if file doesn't exist
-pub fn load_salt() -> Option<[u8; 16]> {
-    let path = PathBuf::from(get_path(SALT_FILE));
-    if !path.exists() {
-        return None;
+/// Benchmarks this machine and picks Argon2id parameters for which
+/// deriving the key takes about target time. Never returns parameters
+/// weaker than the default ones.
+pub fn calibrate_kdf(target: Duration, parallelism: u32) -> Result<KdfParams> {
+    let mut kdf = KdfParams { iterations: 1, parallelism, ..KdfParams::default() };
+    kdf.memory_kib = kdf.memory_kib.max(8 * parallelism);
+
+    // Memory cost matters most against GPUs, so first raise it while a single pass
+    // takes less than a quarter of the target
+    let mut pass = measure_kdf(&kdf)?;
+    while pass * 4 < target && kdf.memory_kib * 2 <= MAX_CALIBRATION_MEMORY_KIB {
+        kdf.memory_kib *= 2;
+        pass = measure_kdf(&kdf)?;
     }
 
-    let mut file = File::open(&path).ok()?;
-    let mut salt = [0u8; 16];
-    file.read_exact(&mut salt).ok()?;
-    
-    return Some(salt);
+    // Then use the rest of the time for more passes
+    let passes = (target.as_secs_f64() / pass.as_secs_f64().max(f64::EPSILON)).round() as u32;
+    kdf.iterations = passes.max(KdfParams::default().iterations);
+
+    Ok(kdf)
 }
 
-/// Derives a master key from the master password and salt using Argon2
-pub fn derive_master_key(master_password: &str, salt: &[u8]) -> [u8; 32] {
-    let argon2 = Argon2::default();
-    let mut output_key = [0u8; 32]; // 256-bit key
-    argon2.hash_password_into(master_password.as_bytes(), salt, &mut output_key)
-        .expect("Argon2 key derivation failed");
-    
-    return output_key;
+/// Checks if salt file and verification token files of the old format exist in dir
+pub fn legacy_profile_exists(dir: &Path) -> bool {
+    dir.join(LEGACY_SALT_FILE).exists() && dir.join(LEGACY_VERIFY_FILE).exists()
 }
 
-/// Creates and stores a random verification token encrypted with master_key.
-/// Call this on first master password setup.
-pub fn create_verification_token(master_key: &[u8]) {
-    // Generates random token
-    let mut token = [0u8; 32];
-    OsRng.try_fill_bytes(&mut token).expect("Error generating verification token");
+/// Generates a new random salt
+pub fn generate_salt() -> Result<[u8; 16]> {
+    let mut salt = [0u8; 16];
+    OsRng.try_fill_bytes(&mut salt).map_err(|e| Error::Crypto(e.to_string()))?;
 
-    // Szyfrujemy token AES-GCM, klucz master_key, nonce losowy 12 bajtów
-    let key = Key::<Aes256Gcm>::from_slice(master_key);
-    let cipher = Aes256Gcm::new(key);
+    Ok(salt)
+}
 
-    let mut nonce_bytes = [0u8; 12];
-    OsRng.try_fill_bytes(&mut nonce_bytes).expect("Error generating nonce");
-    let nonce = Nonce::from_slice(&nonce_bytes);
+/// Generates a random key, for vaults whose key isn't derived from the password
+pub fn generate_key() -> Result<Zeroizing<[u8; 32]>> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    OsRng.try_fill_bytes(key.as_mut()).map_err(|e| Error::Crypto(e.to_string()))?;
 
-    let ciphertext = cipher.encrypt(nonce, token.as_ref())
-        .expect("Encryption failed");
+    Ok(key)
+}
 
-    // Save nonce and ciphertext in verify.bin
-    let path = PathBuf::from(get_path(VERIFY_FILE));
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).expect("Unable to create verify directory");
-    }
-    let mut file = File::create(path).expect("Unable to create verify file");
+/// Loads the salt from the old salt file
+pub fn load_legacy_salt(dir: &Path) -> Result<[u8; 16]> {
+    let mut file = File::open(dir.join(LEGACY_SALT_FILE))?;
+    let mut salt = [0u8; 16];
+    file.read_exact(&mut salt)?;
 
-    // Format: [nonce(12 bytes)] + [ciphertext]
-    file.write_all(&nonce_bytes).expect("Write nonce failed");
-    file.write_all(&ciphertext).expect("Write ciphertext failed");
+    Ok(salt)
 }
 
-/// Verifies master password by trying to decrypt verification token saved when creating the password.
-/// Returns true if it correctly decrypts verification token, false otherwise.
-pub fn verify_master_key(master_key: &[u8]) -> bool {
-    let path = PathBuf::from(get_path(VERIFY_FILE));
-    if !path.exists() {
-        panic!("Verification file doesn't exist!");
-    }
+/// Derives a master key from the master password and salt using Argon2
+pub fn derive_master_key(master_password: &str, salt: &[u8], kdf: &KdfParams) -> Result<Zeroizing<[u8; 32]>> {
+    let argon2 = kdf.argon2()?;
+    let mut output_key = Zeroizing::new([0u8; 32]); // 256-bit key
+    argon2.hash_password_into(master_password.as_bytes(), salt, output_key.as_mut())
+        .map_err(|e| Error::Kdf(e.to_string()))?;
 
-    let mut file = File::open(path).expect("Unable to open verify file");
+    Ok(output_key)
+}
+
+/// Verifies master password by trying to decrypt verification token of the old format.
+/// Returns true if it correctly decrypts verification token, false otherwise.
+pub fn verify_legacy_master_key(dir: &Path, master_key: &[u8]) -> Result<bool> {
+    let mut file = File::open(dir.join(LEGACY_VERIFY_FILE))?;
     let mut contents = Vec::new();
-    file.read_to_end(&mut contents).expect("Unable to read verify file");
+    file.read_to_end(&mut contents)?;
 
     // Lengths is never changed in the program, if it's different then the file
     // must have been modified by someone
     if contents.len() < 12 {
-        panic!("Master key verification file was tampered with");
+        return Err(Error::Tampered);
     }
 
     let (nonce_bytes, ciphertext) = contents.split_at(12);
@@ -112,12 +171,13 @@ pub fn verify_master_key(master_key: &[u8]) -> bool {
     let cipher = Aes256Gcm::new(key);
     let nonce = Nonce::from_slice(nonce_bytes);
 
-    cipher.decrypt(nonce, ciphertext).is_ok()
+    Ok(cipher.decrypt(nonce, ciphertext).is_ok())
 }
 
-/// Get full path to the given file
-fn get_path(file_name: &str) -> PathBuf{
-    let mut path = dirs::data_dir().expect("Couldn't find default data directory");
-    path.push(file_name);
-    return path;
-}
\ No newline at end of file
+/// Deletes salt and verification token files of the old format,
+/// call after their contents were moved to the vault file
+pub fn remove_legacy_files(dir: &Path) -> Result<()> {
+    fs::remove_file(dir.join(LEGACY_SALT_FILE))?;
+    fs::remove_file(dir.join(LEGACY_VERIFY_FILE))?;
+    Ok(())
+}
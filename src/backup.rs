@@ -0,0 +1,64 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use crate::atomic::write_atomic;
+use crate::error::Result;
+
+/// How many newest backups are kept, older ones are deleted
+pub const MAX_BACKUPS: usize = 10;
+
+const BACKUP_DIR: &str = "backups";
+const TIME_FORMAT: &str = "%Y%m%d-%H%M%S%3f";
+
+/// Previous version of the vault file
+pub struct Backup {
+    pub path: PathBuf,
+    pub time: DateTime<Local>,
+}
+
+/// Copies the vault file into the backups directory next to it before it gets overwritten
+pub fn backup_file(vault_path: &Path) -> Result<()> {
+    if !vault_path.exists() {
+        return Ok(());
+    }
+
+    let data = fs::read(vault_path)?;
+    let name = format!("{}-{}.bin", file_stem(vault_path), Utc::now().format(TIME_FORMAT));
+    write_atomic(&backup_dir(vault_path).join(name), &data)?;
+
+    // Remove the oldest ones
+    for old in list_backups(vault_path).into_iter().skip(MAX_BACKUPS) {
+        fs::remove_file(old.path)?;
+    }
+    Ok(())
+}
+
+/// Lists backups of the given vault file, newest first
+pub fn list_backups(vault_path: &Path) -> Vec<Backup> {
+    let Ok(dir) = fs::read_dir(backup_dir(vault_path)) else {
+        return Vec::new();
+    };
+
+    let prefix = format!("{}-", file_stem(vault_path));
+    let mut backups: Vec<Backup> = dir
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let time = name.strip_prefix(&prefix)?.strip_suffix(".bin")?;
+            let time = NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok()?;
+            Some(Backup { path: entry.path(), time: Utc.from_utc_datetime(&time).with_timezone(&Local) })
+        })
+        .collect();
+
+    backups.sort_by_key(|backup| Reverse(backup.time));
+    backups
+}
+
+fn backup_dir(vault_path: &Path) -> PathBuf {
+    vault_path.parent().unwrap_or(Path::new(".")).join(BACKUP_DIR)
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem().unwrap_or_default().to_string_lossy().into_owned()
+}
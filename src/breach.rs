@@ -0,0 +1,112 @@
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use data_encoding::HEXUPPER;
+use sha1::{Digest, Sha1};
+use crate::error::Result;
+
+// Range files are named by the first 5 characters of the hash
+const PREFIX_LEN: usize = 5;
+
+/// Local copy of Pwned Passwords (SHA-1). Either a directory of range files
+/// (as saved by the official downloader: `21BD1.txt` with `SUFFIX:COUNT` lines)
+/// or a single file of `HASH:COUNT` lines ordered by hash.
+pub struct BreachedPasswords {
+    path: PathBuf,
+}
+
+impl BreachedPasswords {
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if !path.exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("nie ma {}", path.display())).into());
+        }
+        Ok(BreachedPasswords { path })
+    }
+
+    /// How many times password appeared in breaches, 0 if it isn't in the list.
+    /// Only its hash is looked up.
+    pub fn count(&self, password: &str) -> Result<u64> {
+        let hash = HEXUPPER.encode(&Sha1::digest(password.as_bytes()));
+        if self.path.is_dir() {
+            self.count_in_range(&hash)
+        } else {
+            count_in_ordered_file(&self.path, &hash)
+        }
+    }
+
+    fn count_in_range(&self, hash: &str) -> Result<u64> {
+        let (prefix, suffix) = hash.split_at(PREFIX_LEN);
+        let file = [format!("{}.txt", prefix), prefix.to_string()].into_iter()
+            .map(|name| self.path.join(name))
+            .find(|path| path.is_file())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound,
+                format!("brak pliku zakresu {} w {}", prefix, self.path.display())))?;
+
+        // Range files are small, about 2000 lines
+        let data = fs::read_to_string(file)?;
+        let lines: Vec<&str> = data.lines().filter(|line| !line.trim().is_empty()).collect();
+        let found = lines.binary_search_by(|line| compare(line, suffix));
+        Ok(found.map(|i| parse_count(lines[i])).unwrap_or(0))
+    }
+}
+
+/// Binary search over byte offsets of a file too big to load.
+/// Each step reads the first line starting at or after the middle offset.
+fn count_in_ordered_file(path: &Path, hash: &str) -> Result<u64> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut low = 0;
+    let mut high = file.get_ref().metadata()?.len();
+
+    while low < high {
+        let middle = low + (high - low) / 2;
+        let Some((start, next, line)) = line_at(&mut file, middle)? else {
+            high = middle;
+            continue;
+        };
+        // No line starts between middle and high
+        if start >= high {
+            high = middle;
+            continue;
+        }
+
+        match compare(&line, hash) {
+            Ordering::Equal => return Ok(parse_count(&line)),
+            Ordering::Less => low = next,
+            Ordering::Greater => high = middle,
+        }
+    }
+    Ok(0)
+}
+
+/// First line starting at or after offset, with its start and the start of the next one
+fn line_at(file: &mut BufReader<File>, offset: u64) -> io::Result<Option<(u64, u64, String)>> {
+    let mut start = offset;
+    if offset > 0 {
+        // Skip the rest of the line offset is in, unless it's the first character
+        file.seek(SeekFrom::Start(offset - 1))?;
+        let mut skipped = Vec::new();
+        start = offset - 1 + file.read_until(b'\n', &mut skipped)? as u64;
+    } else {
+        file.seek(SeekFrom::Start(0))?;
+    }
+
+    let mut line = String::new();
+    let read = file.read_line(&mut line)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    Ok(Some((start, start + read as u64, line)))
+}
+
+/// Compares hash (or its suffix) at the start of a line with the one searched for
+fn compare(line: &str, hash: &str) -> Ordering {
+    let line_hash = line.split(':').next().unwrap_or("").trim();
+    line_hash.to_ascii_uppercase().as_str().cmp(hash)
+}
+
+fn parse_count(line: &str) -> u64 {
+    // Some dumps have no counts, being in the list still means at least once
+    line.split(':').nth(1).and_then(|count| count.trim().parse().ok()).unwrap_or(1)
+}
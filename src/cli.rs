@@ -2,11 +2,24 @@ use reedline::{default_emacs_keybindings, ColumnarMenu, Completer, Emacs, KeyCod
 
 use reedline::{PromptEditMode, PromptHistorySearch};
 use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use passman::{SearchIndex, SEPARATOR};
+
+// Commands which take a label of an existing password as their argument
+const LABEL_COMMANDS: [&str; 13] = ["get", "totp", "show", "remove", "rename", "mv", "edit", "rotate", "history", "restore", "info", "set", "unset"];
+// Commands which take a folder as their argument
+const FOLDER_COMMANDS: [&str; 1] = ["ls"];
+// Fuzzy matches offered when no label starts with the typed text
+const MAX_FUZZY_SUGGESTIONS: usize = 20;
+
+/// Labels offered by the completer, replaced while the line editor waits for input
+pub type SharedIndex = Arc<Mutex<SearchIndex>>;
 
 // Custom completer to handle commands and optional labels
 pub struct CommandAndLabelCompleter {
     commands: Vec<String>,
-    labels: Vec<String>,
+    index: SharedIndex,
 }
 
 impl Completer for CommandAndLabelCompleter {
@@ -36,7 +49,7 @@ impl Completer for CommandAndLabelCompleter {
                 if cmd.starts_with(completing_word) {
 
                     // Add whitespace to the suggestion if we expect a second argument
-                    if cmd == "get" || cmd == "remove" || cmd == "new"{
+                    if LABEL_COMMANDS.contains(&cmd.as_str()) || FOLDER_COMMANDS.contains(&cmd.as_str()) || ["new", "add", "find"].contains(&cmd.as_str()) {
                         suggestions.push(Suggestion {
                             value: cmd.clone(),
                             description: None,
@@ -63,36 +76,67 @@ impl Completer for CommandAndLabelCompleter {
                     }
                 }
             }
-        } else if parts.len() >= 1 {
-            // Complete label for get and remove
+        } else if !parts.is_empty() {
+            // Complete label for commands that take one, it is always the first argument
             let command = parts[0];
-            if command == "get" || command == "remove" {
-                for label in &self.labels {
-                    if label.starts_with(completing_word) {
-                        suggestions.push(Suggestion {
-                            value: label.clone(),
-                            description: None,
-                            extra: None,
-                            style: None,
-                            span: Span {
-                                start: word_start,
-                                end: pos,
-                            },
-                            append_whitespace: false,
-                        });
-                    }
+            let completing_first_argument = parts.len() == 1 || (parts.len() == 2 && !input.ends_with(' '));
+            let folders_only = FOLDER_COMMANDS.contains(&command);
+            if (LABEL_COMMANDS.contains(&command) || folders_only) && completing_first_argument {
+                let index = self.index.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                let mut values: Vec<String> = complete_segment(index.labels(), completing_word, folders_only).into_iter().collect();
+                // Typed text can also be a part of the label, username, URL or tag, as in `find`
+                if values.is_empty() && !folders_only && !completing_word.is_empty() {
+                    values = index.search(completing_word).into_iter()
+                        .take(MAX_FUZZY_SUGGESTIONS)
+                        .map(|found| found.label)
+                        .collect();
+                }
+                for value in values {
+                    // Folder is followed by the next part of the label, not by another argument
+                    let append_whitespace = !value.ends_with(SEPARATOR);
+                    suggestions.push(Suggestion {
+                        value,
+                        description: None,
+                        extra: None,
+                        style: None,
+                        span: Span {
+                            start: word_start,
+                            end: pos,
+                        },
+                        append_whitespace,
+                    });
                 }
             }
         }
 
-        return suggestions;
+        suggestions
     }
 }
 
+/// Completes labels one folder at a time, like a shell completes paths: labels in
+/// a folder below the typed part are suggested as the folder, ending with the separator
+fn complete_segment<'a>(labels: impl Iterator<Item = &'a str>, word: &str, folders_only: bool) -> BTreeSet<String> {
+    labels
+        .filter_map(|label| {
+            let rest = label.strip_prefix(word)?;
+            match rest.find(SEPARATOR) {
+                Some(end) => Some(label[..word.len() + end + SEPARATOR.len_utf8()].to_string()),
+                None if folders_only => None,
+                None => Some(label.to_string()),
+            }
+        })
+        .collect()
+}
+
+/// Replaces completions, also while the line editor is reading a line
+pub fn set_index(shared: &SharedIndex, index: SearchIndex) {
+    *shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = index;
+}
+
 /// Constumes both parameters, they must be cloned
-pub fn bulid_line_editor(labels: Vec<String>, commands: Vec<String>) -> Reedline{
+pub fn bulid_line_editor(index: SharedIndex, commands: Vec<String>) -> Reedline{
 
-    let completer = Box::new(CommandAndLabelCompleter{commands: commands, labels: labels});
+    let completer = Box::new(CommandAndLabelCompleter{commands, index});
     // Use the interactive menu to select options from the completer
     let completion_menu = Box::new(ColumnarMenu::default().with_name("completion_menu"));
     // Set up the required keybindings
@@ -108,17 +152,23 @@ pub fn bulid_line_editor(labels: Vec<String>, commands: Vec<String>) -> Reedline
 
     let edit_mode = Box::new(Emacs::new(keybindings));
 
-    return Reedline::create()
+    Reedline::create()
         .with_completer(completer)
         .with_menu(ReedlineMenu::EngineCompleter(completion_menu))
-        .with_edit_mode(edit_mode);
+        .with_edit_mode(edit_mode)
 }
 
-pub struct MyPrompt;
+pub struct MyPrompt {
+    /// Name of the current vault, not shown for the default one
+    pub vault: Option<String>,
+}
 
 impl Prompt for MyPrompt {
     fn render_prompt_left(&self) -> Cow<'_, str> {
-        Cow::Owned("\x1b[32mpassman> \x1b[0m".to_string()) // all green prompt
+        match &self.vault {
+            Some(vault) => Cow::Owned(format!("\x1b[32mpassman [{}]> \x1b[0m", vault)), // all green prompt
+            None => Cow::Owned("\x1b[32mpassman> \x1b[0m".to_string()),
+        }
     }
 
     fn render_prompt_right(&self) -> Cow<'_, str> {
@@ -136,4 +186,102 @@ impl Prompt for MyPrompt {
     fn render_prompt_history_search_indicator(&self, _history_search: PromptHistorySearch) -> Cow<'_, str> {
         Cow::Borrowed("? ") // for history search on ctrl+R
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use passman::{KdfParams, Vault};
+    use super::*;
+
+    const LABELS: [&str; 6] = ["Email/Work/outlook", "Email/gmail", "bank", "github", "gitlab", "praca/github-enterprise"];
+
+    fn completer(test: &str) -> CommandAndLabelCompleter {
+        let dir = std::env::temp_dir().join(format!("passman-cli-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let kdf = KdfParams { memory_kib: 64, iterations: 1, parallelism: 1, ..KdfParams::default() };
+        let mut vault = Vault::create_with_kdf(dir.join("vault.bin"), "test-password", kdf).unwrap();
+        for label in LABELS {
+            vault.insert(label, "secret").unwrap();
+        }
+        vault.entry_mut("bank").unwrap().username = Some(String::from("jan.kowalski"));
+        let index = Arc::new(Mutex::new(SearchIndex::new(&vault)));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let commands = ["get", "generate", "ls", "exit", "export"].map(String::from).to_vec();
+        CommandAndLabelCompleter { commands, index }
+    }
+
+    fn complete(completer: &mut CommandAndLabelCompleter, line: &str) -> Vec<(String, bool)> {
+        completer.complete(line, line.len()).into_iter()
+            .inspect(|suggestion| assert_eq!(suggestion.span.end, line.len()))
+            .map(|suggestion| (suggestion.value, suggestion.append_whitespace))
+            .collect()
+    }
+
+    fn values(completer: &mut CommandAndLabelCompleter, line: &str) -> Vec<String> {
+        complete(completer, line).into_iter().map(|(value, _)| value).collect()
+    }
+
+    #[test]
+    fn segments() {
+        let labels = || LABELS.into_iter();
+        let set = |values: &[&str]| values.iter().map(|value| value.to_string()).collect::<BTreeSet<_>>();
+        assert_eq!(complete_segment(labels(), "", false), set(&["Email/", "bank", "github", "gitlab", "praca/"]));
+        assert_eq!(complete_segment(labels(), "", true), set(&["Email/", "praca/"]));
+        assert_eq!(complete_segment(labels(), "Email/", false), set(&["Email/Work/", "Email/gmail"]));
+        assert_eq!(complete_segment(labels(), "Email/", true), set(&["Email/Work/"]));
+        assert_eq!(complete_segment(labels(), "git", false), set(&["github", "gitlab"]));
+        assert!(complete_segment(labels(), "gmail", false).is_empty());
+        assert!(complete_segment(labels(), "Email/Work/outlook/", false).is_empty());
+    }
+
+    #[test]
+    fn commands() {
+        let mut completer = completer("commands");
+        assert_eq!(complete(&mut completer, "ge"), [(String::from("get"), true), (String::from("generate"), false)]);
+        assert_eq!(values(&mut completer, "ex"), ["exit", "export"]);
+        assert_eq!(values(&mut completer, "").len(), 5);
+        assert!(values(&mut completer, "zzz").is_empty());
+    }
+
+    #[test]
+    fn labels_and_folders_by_prefix() {
+        let mut completer = completer("prefix");
+        assert_eq!(values(&mut completer, "get "), ["Email/", "bank", "github", "gitlab", "praca/"]);
+        assert_eq!(complete(&mut completer, "get gi"), [(String::from("github"), true), (String::from("gitlab"), true)]);
+        // Folders are completed without a space, so the label can go on
+        assert_eq!(complete(&mut completer, "get Em"), [(String::from("Email/"), false)]);
+        assert_eq!(values(&mut completer, "get Email/"), ["Email/Work/", "Email/gmail"]);
+        assert_eq!(values(&mut completer, "get Email/Work/"), ["Email/Work/outlook"]);
+        assert_eq!(completer.complete("get Email/", 10)[0].span.start, 4);
+
+        assert_eq!(values(&mut completer, "ls "), ["Email/", "praca/"]);
+        assert_eq!(values(&mut completer, "ls Email/"), ["Email/Work/"]);
+        // Only the first argument is a label
+        assert!(values(&mut completer, "get github ").is_empty());
+        assert!(values(&mut completer, "generate gi").is_empty());
+    }
+
+    #[test]
+    fn fuzzy_fallback() {
+        let mut completer = completer("fuzzy");
+        // No label starts with it, matches are ordered like in find - best first, shorter first
+        assert_eq!(values(&mut completer, "get thb"), ["github", "praca/github-enterprise"]);
+        let index = completer.index.lock().unwrap().search("thb").into_iter().map(|found| found.label).collect::<Vec<_>>();
+        assert_eq!(values(&mut completer, "get thb"), index);
+        // Other fields match too
+        assert_eq!(values(&mut completer, "get kowal"), ["bank"]);
+        // Prefix matches win, so fuzzy ones aren't mixed in
+        assert_eq!(values(&mut completer, "get gith"), ["github"]);
+    }
+
+    #[test]
+    fn no_matches() {
+        let mut completer = completer("none");
+        assert!(values(&mut completer, "get zzz").is_empty());
+        // Folders aren't matched fuzzily
+        assert!(values(&mut completer, "ls mail").is_empty());
+        assert!(values(&mut completer, "ls bank").is_empty());
+    }
+}
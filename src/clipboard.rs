@@ -0,0 +1,123 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, ExitCode, Stdio};
+use std::thread;
+use std::time::Duration;
+use clipboard::{ClipboardContext, ClipboardProvider};
+use data_encoding::HEXLOWER;
+use sha2::{Digest, Sha256};
+use passman::{Error, Result};
+
+/// Fingerprint of clipboard contents, so the helper can recognize
+/// the secret without ever getting it
+pub fn fingerprint(text: &str) -> String {
+    HEXLOWER.encode(&Sha256::digest(text.as_bytes()))
+}
+
+/// Starts a separate passman process which clears the clipboard after given time,
+/// if it still holds text with given fingerprint. It keeps running after we exit.
+pub fn spawn_clear(fingerprint: &str, after: Duration) -> Result<()> {
+    let mut command = Command::new(std::env::current_exe()?);
+    command.arg("--clear-clipboard-after").arg(after.as_secs().to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    // Own process group, so Ctrl+C in the terminal doesn't stop it
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    let mut child = command.spawn()?;
+    // Fingerprint goes through a pipe, it shouldn't be visible in the process list
+    child.stdin.take()
+        .ok_or_else(|| Error::Io(io::Error::other("brak wejścia procesu czyszczącego schowek")))?
+        .write_all(fingerprint.as_bytes())?;
+    Ok(())
+}
+
+/// Body of the helper process started by `spawn_clear`
+pub fn run_helper(after_secs: u64) -> ExitCode {
+    let mut fingerprint = String::new();
+    if io::stdin().read_to_string(&mut fingerprint).is_err() {
+        return ExitCode::FAILURE;
+    }
+
+    thread::sleep(Duration::from_secs(after_secs));
+
+    match clear_if_unchanged(fingerprint.trim()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(_) => ExitCode::FAILURE,
+    }
+}
+
+fn clear_if_unchanged(expected: &str) -> Result<()> {
+    let mut clipboard: ClipboardContext = ClipboardProvider::new().map_err(|e| Error::Clipboard(e.to_string()))?;
+    clear_if_matches(&mut clipboard, expected)
+}
+
+/// Empties the clipboard, unless the user copied something else since
+pub fn clear_if_matches(clipboard: &mut impl ClipboardProvider, expected: &str) -> Result<()> {
+    let contents = clipboard.get_contents().map_err(|e| Error::Clipboard(e.to_string()))?;
+    if fingerprint(&contents) == expected {
+        clipboard.set_contents(String::new()).map_err(|e| Error::Clipboard(e.to_string()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use super::*;
+
+    /// Clipboard kept in memory, optionally failing every access
+    struct Memory {
+        contents: String,
+        broken: bool,
+    }
+
+    impl ClipboardProvider for Memory {
+        fn new() -> std::result::Result<Self, Box<dyn Error>> {
+            Ok(Memory { contents: String::new(), broken: false })
+        }
+
+        fn get_contents(&mut self) -> std::result::Result<String, Box<dyn Error>> {
+            if self.broken {
+                return Err("brak schowka".into());
+            }
+            Ok(self.contents.clone())
+        }
+
+        fn set_contents(&mut self, contents: String) -> std::result::Result<(), Box<dyn Error>> {
+            if self.broken {
+                return Err("brak schowka".into());
+            }
+            self.contents = contents;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn fingerprint_is_sha256() {
+        assert_eq!(fingerprint(""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(fingerprint("sekret").len(), 64);
+        assert_ne!(fingerprint("sekret"), fingerprint("sekret "));
+        assert!(!fingerprint("sekret").contains("sekret"));
+    }
+
+    #[test]
+    fn clears_only_the_copied_secret() {
+        let mut clipboard = Memory { contents: String::from("sekret"), broken: false };
+        clear_if_matches(&mut clipboard, &fingerprint("sekret")).unwrap();
+        assert_eq!(clipboard.contents, "");
+
+        // The user copied something else in the meantime
+        clipboard.contents = String::from("coś innego");
+        clear_if_matches(&mut clipboard, &fingerprint("sekret")).unwrap();
+        assert_eq!(clipboard.contents, "coś innego");
+    }
+
+    #[test]
+    fn reports_clipboard_errors() {
+        let mut clipboard = Memory { contents: String::from("sekret"), broken: true };
+        assert!(matches!(clear_if_matches(&mut clipboard, &fingerprint("sekret")), Err(passman::Error::Clipboard(_))));
+    }
+}
@@ -0,0 +1,999 @@
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use clipboard::{ClipboardContext, ClipboardProvider};
+use rpassword::read_password;
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+use chrono::{DateTime, Local};
+use zeroize::Zeroizing;
+use passman::{audit, calibrate_kdf, estimate_strength, export_plaintext, measure_kdf, move_target, read_import, Audit, AuditOptions, Capitalization, ColumnMapping, Error,
+    Folder, Found, Generator, Import, KdfParams, MatchedField, Passphrase, Recipe, Result, SearchIndex, Totp, Vault, DEFAULT_HISTORY_DEPTH, SEPARATOR};
+use crate::args::{Case, CharClass, Command, EntryDetails, ExportFormat, Field, GeneratorArgs, ImportFormat, KdfAction, OnConflict, PassphraseArgs, Secret, TrashAction};
+use crate::clipboard::{clear_if_matches, fingerprint, spawn_clear};
+use crate::config::{Config, OnWeak, StrengthPolicy};
+use crate::picker::pick;
+use crate::reveal::{mask, reveal};
+
+/// Unlocked vaults together with resources of the front end
+pub struct Session {
+    /// Vault commands work on
+    pub vault: Vault,
+    /// Name of the current vault - profile name or path
+    pub name: String,
+    // Other vaults unlocked in this session with their names
+    others: Vec<(String, Vault)>,
+    // Created on first use, so commands that don't need it work without a display
+    clipboard: Option<ClipboardContext>,
+    // Fingerprint of the last secret copied to the clipboard
+    copied: Option<String>,
+    /// Copied secrets are cleared from the clipboard after this time, never if it is zero
+    pub clipboard_timeout: Duration,
+    /// Secrets shown by `show` are hidden after this time, only on a key press if it is zero
+    pub reveal_timeout: Duration,
+    /// Generator presets from the config file
+    pub generators: BTreeMap<String, Generator>,
+    /// Required strength of passwords typed by the user
+    pub strength: StrengthPolicy,
+    /// Days after which audit reports a password as old
+    pub password_max_age: u64,
+    /// Previous passwords kept with each entry
+    pub history_depth: usize,
+    /// Days after which removed entries are deleted from the trash, never if it is zero
+    pub trash_retention: u64,
+}
+
+impl Session {
+    pub fn new(name: String, vault: Vault) -> Self {
+        Session { vault, name, others: Vec::new(), clipboard: None, copied: None, clipboard_timeout: Duration::ZERO, reveal_timeout: Duration::ZERO, generators: BTreeMap::new(), strength: StrengthPolicy::default(), password_max_age: 0, history_depth: DEFAULT_HISTORY_DEPTH, trash_retention: 0 }
+    }
+
+    /// Applies settings from the config file
+    pub fn configure(&mut self, config: &Config) {
+        self.clipboard_timeout = config.clipboard_timeout();
+        self.reveal_timeout = config.reveal_timeout();
+        self.generators = config.generators.clone();
+        self.strength = config.strength.clone();
+        self.password_max_age = config.password_max_age();
+        self.history_depth = config.history_depth();
+        self.trash_retention = config.trash_retention();
+        purge_expired(&mut self.vault, self.trash_retention);
+    }
+
+    /// Makes an already unlocked vault with given path current.
+    /// Returns false if there is no such vault in this session.
+    pub fn switch_to(&mut self, path: &Path) -> bool {
+        if same_file(self.vault.path(), path) {
+            return true;
+        }
+
+        let Some(i) = self.others.iter().position(|(_, vault)| same_file(vault.path(), path)) else {
+            return false;
+        };
+        let (name, vault) = self.others.swap_remove(i);
+        self.add(name, vault);
+        true
+    }
+
+    /// Makes newly unlocked vault current, previous one stays unlocked
+    pub fn add(&mut self, name: String, mut vault: Vault) {
+        purge_expired(&mut vault, self.trash_retention);
+        let previous_name = std::mem::replace(&mut self.name, name);
+        let previous = std::mem::replace(&mut self.vault, vault);
+        self.others.push((previous_name, previous));
+    }
+
+    /// Names and paths of all unlocked vaults, current one first
+    pub fn unlocked(&self) -> Vec<(&str, &Path)> {
+        std::iter::once((self.name.as_str(), self.vault.path()))
+            .chain(self.others.iter().map(|(name, vault)| (name.as_str(), vault.path())))
+            .collect()
+    }
+
+    fn clipboard(&mut self) -> Result<&mut ClipboardContext> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(ClipboardProvider::new().map_err(|e| Error::Clipboard(e.to_string()))?);
+        }
+        Ok(self.clipboard.as_mut().unwrap())
+    }
+
+    /// Copies secret to the clipboard and schedules clearing it
+    fn copy(&mut self, secret: String) -> Result<()> {
+        let fingerprint = fingerprint(&secret);
+        // Helper starts first, a secret it couldn't clear never gets to the clipboard
+        if !self.clipboard_timeout.is_zero() {
+            spawn_clear(&fingerprint, self.clipboard_timeout)?;
+        }
+        self.clipboard()?.set_contents(secret).map_err(|e| Error::Clipboard(e.to_string()))?;
+        self.copied = Some(fingerprint);
+        Ok(())
+    }
+
+    /// Clear potential password in clipboard, if it was copied in this session
+    /// and the user didn't copy anything else since
+    pub fn clear_clipboard(&mut self) -> Result<()> {
+        if let (Some(clipboard), Some(copied)) = (self.clipboard.as_mut(), self.copied.take()) {
+            clear_if_matches(clipboard, &copied)?;
+        }
+        Ok(())
+    }
+
+    /// Message telling when the copied secret disappears from the clipboard
+    fn clear_notice(&self) -> String {
+        if self.clipboard_timeout.is_zero() {
+            return String::new();
+        }
+        format!(" Zostanie usunięte za {} s.", self.clipboard_timeout.as_secs())
+    }
+}
+
+/// Runs one command on an unlocked session
+pub fn execute(session: &mut Session, command: Command) -> Result<()> {
+    match command {
+        Command::New { label, length, generator, passphrase, details } => {
+            // Check if this label isn't already used
+            if session.vault.contains(&label) {
+                return Err(Error::LabelExists);
+            }
+
+            let recipe = secret_recipe(&session.generators, length, generator, passphrase)?;
+            let (password, entropy) = (recipe.generate()?, recipe.entropy()?);
+            add_entry(&mut session.vault, &label, &password, Some(recipe), details)?;
+            println!("Hasło {} pomyślnie zapisane (entropia: {:.0} bitów)", label, entropy);
+        }
+        Command::Generate { length, generator, passphrase } => {
+            generate(&session.generators, length, generator, passphrase)?;
+        }
+        Command::Add { label, stdin, allow_weak, details } => {
+            // Check if this label isn't already used
+            if session.vault.contains(&label) {
+                return Err(Error::LabelExists);
+            }
+
+            let password = read_new_password(stdin)?;
+            let mut user_inputs = vec![label.as_str()];
+            user_inputs.extend(details.username.as_deref());
+            check_strength(&password, &user_inputs, &session.strength, allow_weak, !stdin)?;
+            add_entry(&mut session.vault, &label, &password, None, details)?;
+            println!("Dodano hasło {}", label);
+        }
+        Command::Rename { label, new_label } => {
+            session.vault.rename(&label, &new_label)?;
+            session.vault.save()?;
+            println!("Zmieniono etykietę {} na {}", label, new_label);
+        }
+        Command::Mv { source, destination } => {
+            // Entry with such label is moved before a folder with the same name
+            if session.vault.contains(&source) {
+                let new_label = move_target(&source, &destination);
+                session.vault.rename(&source, &new_label)?;
+                session.vault.save()?;
+                println!("Przeniesiono {} do {}", source, new_label);
+                return Ok(());
+            }
+
+            let folder = source.trim_end_matches(SEPARATOR);
+            let new_folder = move_target(folder, &destination);
+            let count = session.vault.rename_folder(folder, &new_folder)?;
+            session.vault.save()?;
+            println!("Przeniesiono folder {} do {} (haseł: {})", folder, new_folder, count);
+        }
+        Command::Ls { folder } => {
+            let folder = folder.unwrap_or_default();
+            let folder = folder.trim_matches(SEPARATOR);
+            let Some(tree) = Folder::from_labels(session.vault.iter_labels(), folder) else {
+                if folder.is_empty() {
+                    println!("Sejf jest pusty");
+                    return Ok(());
+                }
+                return Err(Error::FolderNotFound);
+            };
+
+            // Top folder is shown like `tree` shows the current directory
+            if folder.is_empty() {
+                println!(".");
+            } else {
+                println!("{}{}", folder, SEPARATOR);
+            }
+            print_tree(&tree, "");
+        }
+        Command::Edit { label, stdin, allow_weak } => {
+            let username = session.vault.entry(&label)?.username.clone();
+            let password = read_new_password(stdin)?;
+            let mut user_inputs = vec![label.as_str()];
+            user_inputs.extend(username.as_deref());
+            check_strength(&password, &user_inputs, &session.strength, allow_weak, !stdin)?;
+
+            session.vault.set_password(&label, &password, session.history_depth)?;
+            // A typed password can't be generated again
+            session.vault.entry_mut(&label)?.recipe = None;
+            session.vault.save()?;
+            println!("Zmieniono hasło {}, poprzednie zostało zapisane w historii", label);
+        }
+        Command::Rotate { label, length, generator, passphrase } => {
+            // Rules given now replace the ones the password was generated with
+            let recipe = if length.is_some() || generator.is_set() || passphrase.words.is_some() {
+                secret_recipe(&session.generators, length, generator, passphrase)?
+            } else {
+                session.vault.entry(&label)?.recipe.clone().unwrap_or_else(|| Recipe::Password(Generator::default()))
+            };
+            let (password, entropy) = (recipe.generate()?, recipe.entropy()?);
+
+            session.vault.set_password(&label, &password, session.history_depth)?;
+            session.vault.entry_mut(&label)?.recipe = Some(recipe);
+            session.vault.save()?;
+            println!("Nowe hasło {} zapisane (entropia: {:.0} bitów), poprzednie zostało zapisane w historii", label, entropy);
+        }
+        Command::Remove { label, yes } => {
+            // Check if there is such password to remove
+            if !session.vault.contains(&label) {
+                return Err(Error::LabelNotFound);
+            }
+
+            if !confirm(&format!("Czy na pewno chcesz usunąć hasło {}? Trafi do kosza, skąd można je przywrócić.", label), yes)? {
+                return Err(Error::NotConfirmed);
+            }
+
+            session.vault.remove(&label)?;
+            session.vault.save()?;
+            println!("Przeniesiono hasło {} do kosza", label);
+        }
+        Command::History { label } => {
+            let history = session.vault.history(&label)?;
+            if history.is_empty() {
+                println!("Hasło {} nie ma historii", label);
+                return Ok(());
+            }
+
+            println!("Poprzednie hasła {} (od najnowszego):", label);
+            for (i, item) in history.iter().rev().enumerate() {
+                println!("{:>3}. zmienione {}", i + 1, format_time(Some(item.replaced)));
+            }
+        }
+        Command::Restore { label, number, yes } => {
+            let history = session.vault.history(&label)?;
+            // Listed newest first
+            let index = number.checked_sub(1).and_then(|i| history.len().checked_sub(i + 1)).ok_or(Error::HistoryNotFound)?;
+
+            let question = format!("Czy na pewno chcesz przywrócić hasło {} zmienione {}? Obecne zostanie zapisane w historii.",
+                label, format_time(Some(history[index].replaced)));
+            if !confirm(&question, yes)? {
+                return Err(Error::NotConfirmed);
+            }
+
+            session.vault.restore_password(&label, index, session.history_depth)?;
+            session.vault.save()?;
+            println!("Przywrócono poprzednie hasło {}", label);
+        }
+        Command::Trash { action } => match action.unwrap_or(TrashAction::List) {
+            TrashAction::List => {
+                let trash = session.vault.trash();
+                if trash.is_empty() {
+                    println!("Kosz jest pusty");
+                    return Ok(());
+                }
+                for trashed in trash.iter().rev() {
+                    println!("{} (usunięte {})", trashed.label, format_time(Some(trashed.deleted)));
+                }
+                if session.trash_retention > 0 {
+                    println!("Hasła są usuwane z kosza na dobre po {} dniach", session.trash_retention);
+                }
+            }
+            TrashAction::Restore { label } => {
+                session.vault.restore_trashed(&label)?;
+                session.vault.save()?;
+                println!("Przywrócono hasło {} z kosza", label);
+            }
+            TrashAction::Purge { yes } => {
+                let count = session.vault.trash().len();
+                if count == 0 {
+                    println!("Kosz jest pusty");
+                    return Ok(());
+                }
+                if !confirm(&format!("Czy na pewno chcesz na dobre usunąć hasła z kosza ({})? Tej akcji nie można odwrócić.", count), yes)? {
+                    return Err(Error::NotConfirmed);
+                }
+
+                session.vault.purge_trash(u64::MAX);
+                session.vault.save()?;
+                println!("Opróżniono kosz");
+            }
+        },
+        Command::Find { query, list } => {
+            let query = query.join(" ");
+            let found = SearchIndex::new(&session.vault).search(&query);
+            if found.is_empty() {
+                println!("Nie znaleziono haseł pasujących do {}", query);
+                return Ok(());
+            }
+
+            let lines: Vec<String> = found.iter().map(describe_found).collect();
+            // Picker needs a terminal, in scripts the results are only printed
+            if list || !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+                lines.iter().for_each(|line| println!("{}", line));
+                return Ok(());
+            }
+
+            let Some(index) = pick(&format!("Hasła pasujące do {} (najlepsze najpierw):", query), &lines)? else {
+                return Ok(());
+            };
+            println!("{}", found[index].label);
+            get_password(session, &found[index].label, false)?;
+        }
+        Command::Get { label, stdout } => get_password(session, &label, stdout)?,
+        Command::Totp { label, stdout } => {
+            let totp = session.vault.totp(&label)?.ok_or(Error::NoTotp)?;
+            let now = unix_time();
+            let code = totp.code_at(now);
+
+            if stdout {
+                println!("{}", code);
+            } else {
+                session.copy(code)?;
+                println!("Kod skopiowany do schowka, ważny jeszcze {} s.{}", totp.remaining(now), session.clear_notice());
+            }
+        }
+        Command::Show { label, field, mask: masked, timeout } => {
+            let (name, secret) = match field {
+                Secret::Password => ("Hasło", Some(session.vault.get(&label)?)),
+                Secret::Notes => ("Notatka", session.vault.notes(&label)?),
+                Secret::Totp => ("Sekret TOTP", Some(session.vault.totp_secret(&label)?.ok_or(Error::NoTotp)?)),
+            };
+            let Some(secret) = secret.map(Zeroizing::new) else {
+                println!("Hasło {} nie ma notatki", label);
+                return Ok(());
+            };
+            let secret = if masked { mask(&secret) } else { secret };
+
+            let timeout = timeout.map(Duration::from_secs).unwrap_or(session.reveal_timeout);
+            reveal(&format!("{} {}", name, label), &secret, timeout)?;
+        }
+        Command::Info { label } => {
+            let entry = session.vault.entry(&label)?;
+
+            println!("{}", label);
+            println!("  Użytkownik: {}", entry.username.as_deref().unwrap_or("-"));
+            println!("  Adresy: {}", join_or_dash(&entry.urls));
+            println!("  Tagi: {}", join_or_dash(&entry.tags));
+            if entry.has_notes() {
+                println!("  Notatka: jest (komenda show --field notes)");
+            }
+            if let Some(totp) = session.vault.totp(&label)? {
+                println!("  TOTP: {}, {} cyfr, co {} s{}", totp.algorithm, totp.digits, totp.period,
+                    totp.issuer.map(|issuer| format!(" ({})", issuer)).unwrap_or_default());
+            }
+            println!("  Utworzono: {}", format_time(Some(entry.created)));
+            println!("  Zmieniono: {}", format_time(Some(entry.modified)));
+            println!("  Ostatnio użyto: {}", format_time(entry.last_used));
+            if !entry.history().is_empty() {
+                println!("  Poprzednie hasła: {} (komenda history)", entry.history().len());
+            }
+        }
+        Command::Set { label, field, mut values, append } => {
+            if !session.vault.contains(&label) {
+                return Err(Error::LabelNotFound);
+            }
+
+            // Arguments can be seen by other processes and end up in shell history,
+            // so notes and secrets are always typed after the command
+            if matches!(field, Field::Notes | Field::Totp) {
+                if !values.is_empty() {
+                    return Err(Error::SecretInArguments);
+                }
+                values.push(read_field(field)?);
+            }
+            if values.is_empty() {
+                return Err(Error::MissingValue);
+            }
+
+            match field {
+                Field::Notes => session.vault.set_notes(&label, Some(&values.join(" ")))?,
+                Field::Totp => session.vault.set_totp(&label, Some(&values.concat()))?,
+                _ => {}
+            }
+
+            let entry = session.vault.entry_mut(&label)?;
+            match field {
+                Field::Username => entry.username = Some(values.join(" ")),
+                Field::Notes | Field::Totp => {}
+                Field::Url => {
+                    if !append {
+                        entry.urls.clear();
+                    }
+                    entry.urls.extend(values);
+                }
+                Field::Tags => {
+                    if !append {
+                        entry.tags.clear();
+                    }
+                    entry.tags.extend(values);
+                }
+            }
+            entry.touch();
+            session.vault.save()?;
+            println!("Zapisano zmiany w {}", label);
+        }
+        Command::Unset { label, field } => {
+            match field {
+                Field::Notes => session.vault.set_notes(&label, None)?,
+                Field::Totp => session.vault.set_totp(&label, None)?,
+                _ => {}
+            }
+
+            let entry = session.vault.entry_mut(&label)?;
+            match field {
+                Field::Username => entry.username = None,
+                Field::Notes | Field::Totp => {}
+                Field::Url => entry.urls.clear(),
+                Field::Tags => entry.tags.clear(),
+            }
+            entry.touch();
+            session.vault.save()?;
+            println!("Zapisano zmiany w {}", label);
+        }
+        Command::ChangePassword { yes, stdin, allow_weak } => {
+            if !confirm("Czy na pewno chcesz zmienić główne hasło?", yes)? {
+                return Err(Error::NotConfirmed);
+            }
+
+            let new_password = read_new_password(stdin)?;
+            check_strength(&new_password, &[], &session.strength, allow_weak, !stdin)?;
+            session.vault.change_password(&new_password)?;
+            println!("Nowe hasło ustawione!");
+        }
+        Command::RestoreBackup { number, yes, stdin } => {
+            let backups = session.vault.backups();
+
+            let Some(number) = number else {
+                if backups.is_empty() {
+                    println!("Brak kopii zapasowych");
+                }
+                for (i, backup) in backups.iter().enumerate() {
+                    println!("{:>3}. {}", i + 1, backup.time.format("%Y-%m-%d %H:%M:%S"));
+                }
+                return Ok(());
+            };
+
+            let backup = number.checked_sub(1).and_then(|i| backups.get(i)).ok_or(Error::BackupNotFound)?;
+            let question = format!("Czy na pewno chcesz przywrócić kopię z {}? Obecny stan zostanie zapisany jako nowa kopia.",
+                backup.time.format("%Y-%m-%d %H:%M:%S"));
+            if !confirm(&question, yes)? {
+                return Err(Error::NotConfirmed);
+            }
+
+            match session.vault.restore_backup(backup, None) {
+                Err(Error::WrongPassword) => {
+                    // Backup was made before master password or KDF parameters changed
+                    println!("Kopia jest zaszyfrowana innym hasłem.");
+                    let password = read_master_password(stdin)?;
+                    session.vault.restore_backup(backup, Some(&password))?;
+                }
+                result => result?,
+            }
+            println!("Przywrócono kopię zapasową");
+        }
+        Command::Audit { json, max_age, breached } => {
+            let options = AuditOptions {
+                min_score: session.strength.min_score,
+                max_age_days: max_age.unwrap_or(session.password_max_age),
+                breached,
+            };
+            let report = audit(&session.vault, &options)?;
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).map_err(|e| io::Error::other(e.to_string()))?);
+            } else {
+                print_audit(&report, &options);
+            }
+        }
+        Command::Import { path, format, columns, on_conflict, dry_run } => {
+            let format = match format {
+                Some(ImportFormat::Bitwarden) => passman::ImportFormat::Bitwarden,
+                Some(ImportFormat::Keepass) => passman::ImportFormat::KeePass,
+                Some(ImportFormat::OnePassword) => passman::ImportFormat::OnePassword,
+                Some(ImportFormat::Csv) => passman::ImportFormat::Csv,
+                None => passman::ImportFormat::detect(&path),
+            };
+            let import = read_import(&path, format, &ColumnMapping::parse(&columns)?)?;
+            import_entries(&mut session.vault, &import, on_conflict, dry_run)?;
+        }
+        Command::Export { path, format, yes, stdin, allow_weak } => {
+            // Checked again when the file is created, this only saves asking for passwords
+            if path.exists() {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("plik {} już istnieje", path.display())).into());
+            }
+            let format = match format {
+                ExportFormat::Encrypted => {
+                    println!("Plik zostanie zaszyfrowany osobnym hasłem eksportu.");
+                    let password = read_new_password(stdin)?;
+                    check_strength(&password, &[], &session.strength, allow_weak, !stdin)?;
+                    session.vault.export_encrypted(&path, &password)?;
+                    println!("Wyeksportowano sejf do {}. Można go otworzyć opcją --vault, podając hasło eksportu.", path.display());
+                    return Ok(());
+                }
+                ExportFormat::Csv => passman::ExportFormat::Csv,
+                ExportFormat::Json => passman::ExportFormat::Json,
+            };
+
+            println!("Plik {} będzie zawierał wszystkie hasła, notatki i sekrety TOTP bez szyfrowania.", path.display());
+            if !confirm("Czy na pewno chcesz zapisać je bez szyfrowania?", yes)? {
+                return Err(Error::NotConfirmed);
+            }
+            // Unlocked session alone isn't enough to get everything out in plaintext
+            let password = read_master_password(stdin)?;
+            if !session.vault.check_password(&password)? {
+                return Err(Error::WrongPassword);
+            }
+
+            let count = export_plaintext(&session.vault, &path, format)?;
+            println!("Wyeksportowane hasła: {}. Usuń plik {}, gdy nie będzie już potrzebny.", count, path.display());
+        }
+        Command::Kdf { action } => match action.unwrap_or(KdfAction::Show) {
+            KdfAction::Show => {
+                let kdf = session.vault.kdf()?;
+                println!("{}", kdf);
+                println!("Czas odblokowania: {} ms", measure_kdf(&kdf)?.as_millis());
+            }
+            KdfAction::Set { memory, iterations, parallelism, yes, stdin } => {
+                let current = session.vault.kdf()?;
+                let kdf = KdfParams {
+                    memory_kib: memory.unwrap_or(current.memory_kib),
+                    iterations: iterations.unwrap_or(current.iterations),
+                    parallelism: parallelism.unwrap_or(current.parallelism),
+                    ..current
+                };
+                change_kdf(session, kdf, yes, stdin)?;
+            }
+            KdfAction::Calibrate { target_ms, parallelism, yes, stdin } => {
+                println!("Trwa pomiar...");
+                let kdf = calibrate_kdf(Duration::from_millis(target_ms), parallelism)?;
+                println!("Wybrano: {}", kdf);
+                change_kdf(session, kdf, yes, stdin)?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Copies the password to the clipboard or prints it, and notes when it was used
+fn get_password(session: &mut Session, label: &str, stdout: bool) -> Result<()> {
+    let password = session.vault.get(label)?;
+
+    if stdout {
+        println!("{}", password);
+    } else {
+        session.copy(password)?;
+        println!("Hasło skopiowane do schowka!{}", session.clear_notice());
+    }
+
+    // Time of use isn't worth failing the command over, nor rewriting a KeePass database
+    if !session.vault.is_read_only() && !session.vault.is_keepass() {
+        session.vault.entry_mut(label)?.mark_used();
+        if let Err(error) = session.vault.save_usage() {
+            eprintln!("Nie zapisano czasu użycia hasła: {}", error);
+        }
+    }
+    Ok(())
+}
+
+/// Label of a search result with the other fields the query matched
+fn describe_found(found: &Found) -> String {
+    if found.matched.is_empty() {
+        return found.label.clone();
+    }
+    let fields: Vec<String> = found.matched.iter().map(|(field, value)| {
+        let name = match field {
+            MatchedField::Label => "etykieta",
+            MatchedField::Username => "użytkownik",
+            MatchedField::Url => "adres",
+            MatchedField::Tag => "tag",
+        };
+        format!("{}: {}", name, value)
+    }).collect();
+    format!("{} ({})", found.label, fields.join(", "))
+}
+
+/// Prints subfolders and entries of the folder like `tree` does, folders first
+fn print_tree(folder: &Folder, indent: &str) {
+    let count = folder.folders.len() + folder.entries.len();
+    let folders = folder.folders.iter().map(|(name, subfolder)| (name, Some(subfolder)));
+    let entries = folder.entries.iter().map(|name| (name, None));
+    for (i, (name, subfolder)) in folders.chain(entries).enumerate() {
+        let (branch, nested) = if i + 1 == count { ("└── ", "    ") } else { ("├── ", "│   ") };
+        match subfolder {
+            Some(subfolder) => {
+                println!("{}{}{}{} ({})", indent, branch, name, SEPARATOR, subfolder.count());
+                print_tree(subfolder, &format!("{}{}", indent, nested));
+            }
+            None => println!("{}{}{}", indent, branch, name),
+        }
+    }
+}
+
+/// Adds new entry with metadata given when creating it and saves the vault
+fn add_entry(vault: &mut Vault, label: &str, password: &str, recipe: Option<Recipe>, details: EntryDetails) -> Result<()> {
+    let notes = details.notes.then(|| read_field(Field::Notes)).transpose()?;
+    let totp = details.totp.then(|| read_field(Field::Totp)).transpose()?.map(Zeroizing::new);
+    // Check the secret first, so an invalid one doesn't leave half added entry
+    if let Some(totp) = &totp {
+        Totp::parse(totp)?;
+    }
+
+    let entry = vault.insert(label, password)?;
+    entry.username = details.username;
+    entry.urls = details.urls;
+    entry.tags = details.tags;
+    entry.recipe = recipe;
+    if notes.is_some() {
+        vault.set_notes(label, notes.as_deref())?;
+    }
+    if let Some(totp) = &totp {
+        vault.set_totp(label, Some(totp))?;
+    }
+    vault.save()
+}
+
+/// Reads notes or a TOTP secret typed after the command. On a terminal asks for it
+/// (without echo for the secret), otherwise reads the next line of stdin.
+fn read_field(field: Field) -> Result<String> {
+    if !io::stdin().is_terminal() {
+        return read_line(io::stdin().lock());
+    }
+
+    match field {
+        Field::Totp => {
+            print!("Sekret TOTP: ");
+            io::stdout().flush()?;
+            Ok(read_password()?)
+        }
+        _ => {
+            print!("Notatka: ");
+            io::stdout().flush()?;
+            read_line(io::stdin().lock())
+        }
+    }
+}
+
+/// Adds imported entries to the vault and saves it once at the end. Taken labels
+/// are resolved as on_conflict says. With dry_run only lists what would be done.
+fn import_entries(vault: &mut Vault, import: &Import, on_conflict: OnConflict, dry_run: bool) -> Result<()> {
+    // Labels the dry run would add, the vault itself isn't changed
+    let mut planned = HashSet::new();
+    let (mut imported, mut skipped, mut conflicts) = (0, 0, 0);
+
+    for entry in &import.entries {
+        let taken = |label: &str| vault.contains(label) || planned.contains(label);
+        let label = if !taken(&entry.label) {
+            Some(entry.label.clone())
+        } else {
+            match on_conflict {
+                OnConflict::Skip => None,
+                OnConflict::Rename => Some(free_label(&entry.label, taken)),
+                OnConflict::Ask if dry_run => {
+                    println!("? {} - etykieta jest zajęta", entry.label);
+                    conflicts += 1;
+                    continue;
+                }
+                OnConflict::Ask => ask_label(&entry.label, taken)?,
+            }
+        };
+
+        let Some(label) = label else {
+            println!("- {} - pominięte, etykieta jest zajęta", entry.label);
+            skipped += 1;
+            continue;
+        };
+        let details = [entry.username.clone(), entry.urls.first().cloned()].into_iter().flatten().collect::<Vec<_>>().join(", ");
+        match (label == entry.label, details.is_empty()) {
+            (true, true) => println!("+ {}", label),
+            (true, false) => println!("+ {} ({})", label, details),
+            (false, _) => println!("+ {} (zamiast {})", label, entry.label),
+        }
+        imported += 1;
+
+        if dry_run {
+            planned.insert(label);
+            continue;
+        }
+        let stored = vault.insert(&label, &entry.password)?;
+        stored.username = entry.username.clone();
+        stored.urls = entry.urls.clone();
+        stored.tags = entry.tags.clone();
+        if entry.notes.is_some() {
+            vault.set_notes(&label, entry.notes.as_deref())?;
+        }
+        // One bad secret shouldn't stop the whole import
+        if let Some(Err(error)) = entry.totp.as_deref().map(|totp| vault.set_totp(&label, Some(totp))) {
+            println!("  {} zapisano bez TOTP: {}", label, error);
+        }
+    }
+
+    if dry_run {
+        println!("Do zaimportowania: {}, pominięte: {}, do rozstrzygnięcia: {}, bez hasła: {}", imported, skipped, conflicts, import.without_password);
+        println!("Nic nie zostało zapisane.");
+        return Ok(());
+    }
+    if imported > 0 {
+        vault.save()?;
+    }
+    println!("Zaimportowane hasła: {}, pominięte: {}, bez hasła: {}", imported, skipped, import.without_password);
+    Ok(())
+}
+
+/// First free label made by appending a number, e.g. github-2
+fn free_label(label: &str, taken: impl Fn(&str) -> bool) -> String {
+    (2..).map(|n| format!("{}-{}", label, n)).find(|candidate| !taken(candidate)).unwrap_or_else(|| label.to_string())
+}
+
+/// Asks what to do with an imported entry whose label is taken.
+/// None means it should be skipped.
+fn ask_label(label: &str, taken: impl Fn(&str) -> bool) -> Result<Option<String>> {
+    let suggestion = free_label(label, &taken);
+    loop {
+        print!("Hasło {} już istnieje. Enter - pomiń, + - zapisz jako {}, albo podaj nową etykietę: ", label, suggestion);
+        io::stdout().flush()?;
+        let answer = read_line(io::stdin().lock())?;
+        match answer.trim() {
+            "" => return Ok(None),
+            "+" => return Ok(Some(suggestion)),
+            new if taken(new) => println!("Etykieta {} też jest zajęta.", new),
+            new => return Ok(Some(new.to_string())),
+        }
+    }
+}
+
+/// Deletes entries which stayed in the trash for longer than retention days.
+/// Nothing is saved, the vault is written without them with the next change.
+fn purge_expired(vault: &mut Vault, retention: u64) {
+    if retention > 0 {
+        vault.purge_trash(unix_time().saturating_sub(retention * 24 * 60 * 60));
+    }
+}
+
+/// Current time as unix timestamp in seconds
+fn unix_time() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Compares paths of files, also when one of them is relative
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn join_or_dash(values: &[String]) -> String {
+    if values.is_empty() {
+        return String::from("-");
+    }
+    values.join(", ")
+}
+
+/// Formats unix timestamp in local time, "-" if it is unknown
+fn format_time(timestamp: Option<u64>) -> String {
+    match timestamp.filter(|t| *t != 0).and_then(|t| DateTime::from_timestamp(t as i64, 0)) {
+        Some(time) => time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(),
+        None => String::from("-"),
+    }
+}
+
+/// Prints a new password or passphrase without storing it, entropy goes to stderr
+/// so the secret can be piped somewhere
+pub fn generate(generators: &BTreeMap<String, Generator>, length: Option<usize>,
+    generator: GeneratorArgs, passphrase: PassphraseArgs) -> Result<()> {
+    let recipe = secret_recipe(generators, length, generator, passphrase)?;
+    println!("{}", recipe.generate()?);
+    eprintln!("Entropia: {:.0} bitów", recipe.entropy()?);
+    Ok(())
+}
+
+/// Rules for a passphrase if the number of words is given, for a random password otherwise
+fn secret_recipe(generators: &BTreeMap<String, Generator>, length: Option<usize>,
+    generator: GeneratorArgs, passphrase: PassphraseArgs) -> Result<Recipe> {
+    if let Some(words) = passphrase.words {
+        let passphrase = Passphrase {
+            words,
+            separator: passphrase.separator,
+            capitalization: match passphrase.case {
+                Case::Lower => Capitalization::Lower,
+                Case::Title => Capitalization::Title,
+                Case::Random => Capitalization::Random,
+            },
+            digits: passphrase.digits,
+        };
+        return Ok(Recipe::Passphrase(passphrase));
+    }
+
+    Ok(Recipe::Password(password_generator(generators, generator, length)?))
+}
+
+/// Rules of the generator preset given in args (or the default ones),
+/// with options given directly on top of them
+fn password_generator(generators: &BTreeMap<String, Generator>, args: GeneratorArgs, length: Option<usize>) -> Result<Generator> {
+    let mut generator = match &args.preset {
+        Some(name) => generators.get(name).cloned()
+            .ok_or_else(|| Error::Config(format!("nie ma zestawu reguł generatora {}", name)))?,
+        None => Generator::default(),
+    };
+
+    if let Some(length) = length {
+        generator.length = length;
+    }
+    if !args.chars.is_empty() {
+        generator.lowercase = args.chars.contains(&CharClass::Lower);
+        generator.uppercase = args.chars.contains(&CharClass::Upper);
+        generator.digits = args.chars.contains(&CharClass::Digits);
+        generator.symbols = args.chars.contains(&CharClass::Symbols);
+    }
+    if let Some(exclude) = args.exclude {
+        generator.exclude.push_str(&exclude);
+    }
+    generator.exclude_similar |= args.no_similar;
+    generator.min_lowercase = args.min_lower.unwrap_or(generator.min_lowercase);
+    generator.min_uppercase = args.min_upper.unwrap_or(generator.min_uppercase);
+    generator.min_digits = args.min_digits.unwrap_or(generator.min_digits);
+    generator.min_symbols = args.min_symbols.unwrap_or(generator.min_symbols);
+
+    Ok(generator)
+}
+
+/// Asks user a yes/no question, 'T' confirms, anything else cancels.
+/// Doesn't ask anything when assume_yes is set.
+pub fn confirm(question: &str, assume_yes: bool) -> Result<bool> {
+    if assume_yes {
+        return Ok(true);
+    }
+
+    print!("{} T/[N] ", question);
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    Ok(input.trim() == "T")
+}
+
+/// Reads a new password - from the terminal (typed twice) or as one line from stdin
+pub fn read_new_password(from_stdin: bool) -> Result<String> {
+    if from_stdin {
+        return read_line(io::stdin().lock());
+    }
+
+    password_input()?.ok_or(Error::PasswordMismatch)
+}
+
+fn print_audit(report: &Audit, options: &AuditOptions) {
+    println!("Sprawdzone hasła: {}", report.entries);
+    if report.is_clean() {
+        println!("Nie znaleziono problemów.");
+        return;
+    }
+
+    if !report.reused.is_empty() {
+        println!("Te same hasła:");
+        for labels in &report.reused {
+            println!("  {}", labels.join(", "));
+        }
+    }
+    if !report.weak.is_empty() {
+        println!("Słabe hasła (ocena niższa niż {}/4):", options.min_score);
+        for weak in &report.weak {
+            println!("  {} - ocena {}/4, czas złamania: {}", weak.label, weak.score, weak.crack_time);
+        }
+    }
+    if !report.old.is_empty() {
+        println!("Hasła starsze niż {} dni:", options.max_age_days);
+        for old in &report.old {
+            println!("  {} - {} dni", old.label, old.age_days);
+        }
+    }
+    if let Some(breached) = report.breached.as_ref().filter(|breached| !breached.is_empty()) {
+        println!("Hasła z wycieków:");
+        for entry in breached {
+            println!("  {} - wystąpiło {} razy", entry.label, entry.count);
+        }
+    }
+    if !report.missing_username.is_empty() {
+        println!("Bez nazwy użytkownika: {}", report.missing_username.join(", "));
+    }
+    if !report.missing_url.is_empty() {
+        println!("Bez adresu strony: {}", report.missing_url.join(", "));
+    }
+    if !report.case_duplicates.is_empty() {
+        println!("Etykiety różniące się tylko wielkością liter:");
+        for labels in &report.case_duplicates {
+            println!("  {}", labels.join(", "));
+        }
+    }
+}
+
+/// Shows estimated strength of a new password typed by the user. Passwords below
+/// the minimum score are refused, or used only if allowed with allow_weak
+/// or confirmed in the terminal. Empty passwords are always refused.
+pub fn check_strength(password: &str, user_inputs: &[&str], policy: &StrengthPolicy, allow_weak: bool, interactive: bool) -> Result<()> {
+    if password.is_empty() {
+        println!("Hasło nie może być puste.");
+        return Err(Error::WeakPassword);
+    }
+
+    let strength = estimate_strength(password, user_inputs);
+    println!("Siła hasła: {}/4, szacowany czas złamania: {}", strength.score, strength.crack_time_display());
+    if let Some(warning) = &strength.warning {
+        println!("  {}", warning);
+    }
+    for suggestion in &strength.suggestions {
+        println!("  - {}", suggestion);
+    }
+
+    if strength.score >= policy.min_score || (policy.on_weak == OnWeak::Confirm && allow_weak) {
+        return Ok(());
+    }
+    if policy.on_weak == OnWeak::Refuse {
+        return Err(Error::WeakPassword);
+    }
+    if !interactive {
+        eprintln!("Słabe hasło można zapisać z opcją --allow-weak.");
+        return Err(Error::WeakPassword);
+    }
+
+    let question = format!("Hasło jest słabsze niż wymagane {}/4. Czy mimo to chcesz go użyć?", policy.min_score);
+    if !confirm(&question, false)? {
+        return Err(Error::NotConfirmed);
+    }
+    Ok(())
+}
+
+/// Reads one line without the trailing newline
+pub fn read_line(mut reader: impl BufRead) -> Result<String> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    let len = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(len);
+    Ok(line)
+}
+
+/// Returns None is user didn't repeat the password correctly
+pub fn password_input() -> Result<Option<String>> {
+    print!("Podaj nowe hasło: ");
+    io::stdout().flush()?;
+    let password = read_password()?;
+
+    print!("Powtórz hasło: ");
+    io::stdout().flush()?;
+    let password_repeat = read_password()?;
+
+    if password == password_repeat {
+        Ok(Some(password))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Reads master password - from the terminal or as one line from stdin
+pub fn read_master_password(from_stdin: bool) -> Result<String> {
+    if from_stdin {
+        return read_line(io::stdin().lock());
+    }
+
+    print!("Wprowadź hasło: ");
+    io::stdout().flush()?;
+    Ok(read_password()?)
+}
+
+/// Re-encrypts the vault with a key derived using new KDF parameters.
+/// Asks for the master password again, as the new key can't be derived without it.
+fn change_kdf(session: &mut Session, kdf: KdfParams, assume_yes: bool, from_stdin: bool) -> Result<()> {
+    kdf.argon2()?;
+
+    if kdf.is_weaker_than(&session.vault.kdf()?)
+        && !confirm("Nowe parametry są słabsze niż obecne. Czy na pewno chcesz je ustawić?", assume_yes)? {
+        return Err(Error::NotConfirmed);
+    }
+
+    let password = read_master_password(from_stdin)?;
+    session.vault.change_kdf(&password, kdf)?;
+    println!("Nowe parametry ustawione, czas odblokowania: {} ms", measure_kdf(&kdf)?.as_millis());
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use serde::Deserialize;
+use passman::{default_vault_path, Error, Generator, Result, DEFAULT_HISTORY_DEPTH};
+
+// Relative to the system's config directory
+const CONFIG_FILE: &str = "passman/config.toml";
+/// Environment variable with path of the vault to use
+pub const VAULT_ENV: &str = "PASSMAN_VAULT";
+
+/// Seconds after which copied secrets are cleared from the clipboard
+pub const DEFAULT_CLIPBOARD_TIMEOUT: u64 = 20;
+
+/// Seconds without any command after which the REPL locks itself
+pub const DEFAULT_LOCK_TIMEOUT: u64 = 5 * 60;
+
+/// Days after which `audit` reports a password as old
+pub const DEFAULT_PASSWORD_MAX_AGE: u64 = 365;
+
+/// Seconds after which `show` hides the secret
+pub const DEFAULT_REVEAL_TIMEOUT: u64 = 15;
+
+/// Days after which removed entries are deleted from the trash for good
+pub const DEFAULT_TRASH_RETENTION: u64 = 30;
+
+/// Name of the vault in the default location, when no profile is chosen
+pub const DEFAULT_PROFILE: &str = "domyślny";
+
+/// Contents of the config file, everything is optional
+#[derive(Deserialize, Default)]
+pub struct Config {
+    /// Profile used when none is chosen
+    pub default: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Seconds, 0 turns clearing off
+    pub clipboard_timeout: Option<u64>,
+    /// Seconds, 0 turns locking off
+    pub lock_timeout: Option<u64>,
+    /// Seconds, 0 keeps secrets shown until a key is pressed
+    pub reveal_timeout: Option<u64>,
+    /// Named password generator rules for `new --preset`
+    #[serde(default)]
+    pub generators: BTreeMap<String, Generator>,
+    #[serde(default)]
+    pub strength: StrengthPolicy,
+    /// Days, 0 turns the check off
+    pub password_max_age: Option<u64>,
+    /// Previous passwords kept with each entry, 0 turns the history off
+    pub history_depth: Option<usize>,
+    /// Days, 0 keeps removed entries until the trash is purged
+    pub trash_retention: Option<u64>,
+}
+
+/// Named vault
+#[derive(Deserialize)]
+pub struct Profile {
+    pub path: PathBuf,
+    /// Opened without saving any changes, e.g. a KeePass database shared with KeePassXC
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// Minimum strength of passwords typed by the user (master password and `add`)
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrengthPolicy {
+    /// Score from 0 to 4, see `passman::estimate_strength`
+    pub min_score: u8,
+    pub on_weak: OnWeak,
+}
+
+impl Default for StrengthPolicy {
+    fn default() -> Self {
+        StrengthPolicy { min_score: 3, on_weak: OnWeak::Confirm }
+    }
+}
+
+/// What happens with passwords below the minimum score
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnWeak {
+    /// Used only after confirmation
+    Confirm,
+    Refuse,
+}
+
+impl Config {
+    /// Reads the config file, missing file means default config
+    pub fn load() -> Result<Config> {
+        let Some(path) = config_path() else {
+            return Ok(Config::default());
+        };
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let data = fs::read_to_string(&path)?;
+        toml::from_str(&data).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn clipboard_timeout(&self) -> Duration {
+        Duration::from_secs(self.clipboard_timeout.unwrap_or(DEFAULT_CLIPBOARD_TIMEOUT))
+    }
+
+    pub fn lock_timeout(&self) -> Duration {
+        Duration::from_secs(self.lock_timeout.unwrap_or(DEFAULT_LOCK_TIMEOUT))
+    }
+
+    pub fn reveal_timeout(&self) -> Duration {
+        Duration::from_secs(self.reveal_timeout.unwrap_or(DEFAULT_REVEAL_TIMEOUT))
+    }
+
+    pub fn password_max_age(&self) -> u64 {
+        self.password_max_age.unwrap_or(DEFAULT_PASSWORD_MAX_AGE)
+    }
+
+    pub fn history_depth(&self) -> usize {
+        self.history_depth.unwrap_or(DEFAULT_HISTORY_DEPTH)
+    }
+
+    pub fn trash_retention(&self) -> u64 {
+        self.trash_retention.unwrap_or(DEFAULT_TRASH_RETENTION)
+    }
+
+    /// Path of the vault of a profile
+    pub fn profile_path(&self, name: &str) -> Result<PathBuf> {
+        let profile = self.profiles.get(name)
+            .ok_or_else(|| Error::Config(format!("nie ma profilu {}", name)))?;
+        Ok(expand_home(&profile.path))
+    }
+
+    /// Picks the vault to open, in order: --vault, --profile, PASSMAN_VAULT,
+    /// default profile from the config file, default location.
+    /// Returns name to show to the user and path of the vault.
+    pub fn resolve(&self, vault: Option<PathBuf>, profile: Option<&str>) -> Result<(String, PathBuf)> {
+        self.resolve_with(vault, profile, env::var_os(VAULT_ENV))
+    }
+
+    /// `resolve` with the value of PASSMAN_VAULT given
+    fn resolve_with(&self, vault: Option<PathBuf>, profile: Option<&str>, env_vault: Option<OsString>) -> Result<(String, PathBuf)> {
+        if let Some(path) = vault {
+            return Ok((path.display().to_string(), path));
+        }
+        if let Some(name) = profile {
+            return Ok((name.to_string(), self.profile_path(name)?));
+        }
+        if let Some(path) = env_vault.filter(|path| !path.is_empty()) {
+            let path = PathBuf::from(path);
+            return Ok((path.display().to_string(), path));
+        }
+        if let Some(name) = &self.default {
+            return Ok((name.clone(), self.profile_path(name)?));
+        }
+        Ok((String::from(DEFAULT_PROFILE), default_vault_path()?))
+    }
+
+    /// True if a profile with the vault at path is marked read-only.
+    /// Paths are compared canonicalized, so `./x.kdbx` or a symlink match too.
+    pub fn is_read_only(&self, path: &Path) -> bool {
+        let path = canonical(path);
+        self.profiles.values().any(|profile| profile.read_only && canonical(&expand_home(&profile.path)) == path)
+    }
+
+    /// Resolves argument of the REPL `use` command - name of a profile or path of a vault
+    pub fn resolve_name(&self, name: &str) -> Result<PathBuf> {
+        if self.profiles.contains_key(name) {
+            return self.profile_path(name);
+        }
+        Ok(expand_home(Path::new(name)))
+    }
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(CONFIG_FILE))
+}
+
+/// Absolute path without symlinks, or the path itself if the file doesn't exist
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Replaces leading ~ with the home directory
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "default = \"praca\"\n\
+        [profiles.praca]\npath = \"/sejfy/praca.bin\"\n\
+        [profiles.dom]\npath = \"/sejfy/dom.bin\"\n";
+
+    #[test]
+    fn resolve_precedence() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let without_default = Config { default: None, ..toml::from_str(CONFIG).unwrap() };
+        let default = default_vault_path().unwrap();
+        let cases = [
+            (&config, Some("/inny.bin"), Some("dom"), Some("/env.bin"), ("/inny.bin", Path::new("/inny.bin"))),
+            (&config, None, Some("dom"), Some("/env.bin"), ("dom", Path::new("/sejfy/dom.bin"))),
+            (&config, None, None, Some("/env.bin"), ("/env.bin", Path::new("/env.bin"))),
+            (&config, None, None, None, ("praca", Path::new("/sejfy/praca.bin"))),
+            // Empty variable counts as not set
+            (&config, None, None, Some(""), ("praca", Path::new("/sejfy/praca.bin"))),
+            (&without_default, None, None, Some("/env.bin"), ("/env.bin", Path::new("/env.bin"))),
+            (&without_default, None, None, None, (DEFAULT_PROFILE, default.as_path())),
+            (&Config::default(), None, None, None, (DEFAULT_PROFILE, default.as_path())),
+        ];
+
+        for (config, vault, profile, env_vault, (name, path)) in cases {
+            let resolved = config.resolve_with(vault.map(PathBuf::from), profile, env_vault.map(OsString::from)).unwrap();
+            assert_eq!(resolved, (name.to_string(), path.to_path_buf()), "{:?} {:?} {:?}", vault, profile, env_vault);
+        }
+    }
+
+    #[test]
+    fn resolve_unknown_profile() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        assert!(matches!(config.resolve_with(None, Some("brak"), None), Err(Error::Config(_))));
+        let config = Config { default: Some(String::from("brak")), ..Config::default() };
+        assert!(matches!(config.resolve_with(None, None, None), Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn read_only_compares_canonical_paths() {
+        let dir = env::temp_dir().join(format!("passman-config-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let path = dir.join("shared.kdbx");
+        fs::write(&path, "").unwrap();
+
+        let config: Config = toml::from_str(&format!(
+            "[profiles.keepass]\npath = {:?}\nread_only = true\n", dir.join("sub/../shared.kdbx"),
+        )).unwrap();
+        assert!(config.is_read_only(&path));
+        assert!(config.is_read_only(&dir.join("./shared.kdbx")));
+        assert!(!config.is_read_only(&dir.join("other.kdbx")));
+
+        #[cfg(unix)]
+        {
+            let link = dir.join("link.kdbx");
+            std::os::unix::fs::symlink(&path, &link).unwrap();
+            assert!(config.is_read_only(&link));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use rand::{rngs::OsRng, TryRngCore};
+use aes_gcm::{Aes256Gcm, Key, Nonce,
+aead::{Aead, KeyInit, Payload}};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use crate::auth::KdfParams;
+use crate::error::{Error, Result};
+
+/*
+ * Layout of the vault file (all numbers little endian):
+ *
+ *  magic           8 bytes  "PASSMAN\0"
+ *  format version  u16
+ *  kdf algorithm   u8       0 = Argon2d, 1 = Argon2i, 2 = Argon2id
+ *  kdf version     u32      0x13
+ *  memory cost     u32      KiB
+ *  iterations      u32
+ *  parallelism     u32
+ *  salt length     u8
+ *  salt            salt length bytes
+ *  key check       16 bytes (since version 2) HMAC-SHA256 of KEY_CHECK_LABEL with the key, truncated
+ *  nonce           12 bytes
+ *  ciphertext      rest of the file, AES-256-GCM of the body
+ *
+ * Everything before the nonce is authenticated as associated data,
+ * so changing KDF parameters or salt makes decryption fail.
+ * Key check tells a wrong password apart from a modified file.
+ */
+
+const MAGIC: &[u8; 8] = b"PASSMAN\0";
+pub const FORMAT_VERSION: u16 = 2;
+const NONCE_LEN: usize = 12;
+const KEY_CHECK_LEN: usize = 16;
+const KEY_CHECK_LABEL: &[u8] = b"passman key check";
+
+/// Unencrypted part of the vault file - everything needed to derive the key
+#[derive(Clone)]
+pub struct Header {
+    pub kdf: KdfParams,
+    pub salt: Vec<u8>,
+}
+
+impl Header {
+    fn encode(&self, key_check: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.push(self.kdf.algorithm);
+        bytes.extend_from_slice(&self.kdf.version.to_le_bytes());
+        bytes.extend_from_slice(&self.kdf.memory_kib.to_le_bytes());
+        bytes.extend_from_slice(&self.kdf.iterations.to_le_bytes());
+        bytes.extend_from_slice(&self.kdf.parallelism.to_le_bytes());
+        bytes.push(self.salt.len() as u8);
+        bytes.extend_from_slice(&self.salt);
+        bytes.extend_from_slice(key_check);
+        bytes
+    }
+
+    /// Parses header from the beginning of the file, returns it with
+    /// the key check (missing in version 1) and length of the header in bytes
+    fn decode(bytes: &[u8]) -> Result<(Header, Option<&[u8]>, usize)> {
+        let mut reader = Reader { bytes, pos: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Error::CorruptVault(String::from("to nie jest plik sejfu passman")));
+        }
+        let version = u16::from_le_bytes(reader.array()?);
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(Error::CorruptVault(format!("nieobsługiwana wersja formatu {}", version)));
+        }
+
+        let kdf = KdfParams {
+            algorithm: reader.take(1)?[0],
+            version: u32::from_le_bytes(reader.array()?),
+            memory_kib: u32::from_le_bytes(reader.array()?),
+            iterations: u32::from_le_bytes(reader.array()?),
+            parallelism: u32::from_le_bytes(reader.array()?),
+        };
+        let salt_len = reader.take(1)?[0] as usize;
+        let salt = reader.take(salt_len)?.to_vec();
+        let key_check = if version >= 2 { Some(reader.take(KEY_CHECK_LEN)?) } else { None };
+
+        Ok((Header { kdf, salt }, key_check, reader.pos))
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos + len)
+            .ok_or_else(|| Error::CorruptVault(String::from("plik jest za krótki")))?;
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        // Length is always N, so conversion can't fail
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+}
+
+/// Reads only the header, without decrypting anything
+pub fn read_header(file: &[u8]) -> Result<Header> {
+    Header::decode(file).map(|(header, _, _)| header)
+}
+
+fn key_check(master_key: &[u8]) -> Vec<u8> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(master_key).expect("HMAC accepts keys of any length");
+    mac.update(KEY_CHECK_LABEL);
+    mac.finalize().into_bytes()[..KEY_CHECK_LEN].to_vec()
+}
+
+/// Encrypts body with master_key and returns complete contents of the vault file
+pub fn seal(header: &Header, body: &[u8], master_key: &[u8]) -> Result<Vec<u8>> {
+    let mut file = header.encode(&key_check(master_key));
+
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.try_fill_bytes(&mut nonce).map_err(|e| Error::Crypto(e.to_string()))?;
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(master_key));
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), Payload { msg: body, aad: &file })
+        .map_err(|e| Error::Crypto(e.to_string()))?;
+
+    file.extend_from_slice(&nonce);
+    file.extend_from_slice(&ciphertext);
+    Ok(file)
+}
+
+/// Decrypts body of the vault file. Fails with WrongPassword if master_key
+/// doesn't match and with Tampered if the file was modified.
+pub fn open(file: &[u8], master_key: &[u8]) -> Result<Vec<u8>> {
+    let (_, stored_check, header_len) = Header::decode(file)?;
+
+    // Not a secret, comparing in constant time isn't needed
+    if let Some(stored_check) = stored_check
+        && stored_check != key_check(master_key).as_slice() {
+        return Err(Error::WrongPassword);
+    }
+
+    let (aad, rest) = file.split_at(header_len);
+    if rest.len() < NONCE_LEN {
+        return Err(Error::CorruptVault(String::from("plik jest za krótki")));
+    }
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(master_key));
+    cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| {
+            // Files without key check can't tell these two cases apart
+            if stored_check.is_some() { Error::Tampered } else { Error::WrongPassword }
+        })
+}
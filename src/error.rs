@@ -0,0 +1,132 @@
+use std::fmt;
+use std::io;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Everything that can go wrong in passman. The REPL prints the message and keeps
+/// running, non-interactive mode exits with the code returned by `exit_code`.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file (or the terminal) failed
+    Io(io::Error),
+    /// Vault file is not in a format we can read
+    CorruptVault(String),
+    /// Master password doesn't match the vault
+    WrongPassword,
+    /// Key is correct, but encrypted data was modified
+    Tampered,
+    Clipboard(String),
+    /// Invalid Argon2 parameters or key derivation failure
+    Kdf(String),
+    /// Random number generator or cipher failure
+    Crypto(String),
+    Generator(String),
+    NoProfile,
+    LabelExists,
+    LabelNotFound,
+    PasswordMismatch,
+    NotConfirmed,
+    MissingValue,
+    BackupNotFound,
+    /// Config file can't be read or names a profile which isn't there
+    Config(String),
+    /// TOTP secret or otpauth URI can't be parsed
+    Totp(String),
+    /// Entry has no TOTP secret
+    NoTotp,
+    /// New password is weaker than the config file allows
+    WeakPassword,
+    /// Export of another password manager can't be read
+    Import(String),
+    /// KeePass database can't be read or written
+    Kdbx(String),
+    /// Vault was opened read-only and can't be saved
+    ReadOnly,
+    HistoryNotFound,
+    FolderNotFound,
+    /// New password is the same as the current one
+    PasswordUnchanged,
+    /// Notes or TOTP secret given as an argument, where other processes can see it
+    SecretInArguments,
+}
+
+impl Error {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Io(_) => 1,
+            // 2 is also used by clap for invalid usage
+            Error::MissingValue => 2,
+            Error::WrongPassword => 3,
+            Error::NoProfile => 4,
+            Error::LabelNotFound => 5,
+            Error::LabelExists => 6,
+            Error::PasswordMismatch => 7,
+            Error::NotConfirmed => 8,
+            Error::Clipboard(_) => 9,
+            Error::Kdf(_) => 10,
+            Error::BackupNotFound => 11,
+            Error::CorruptVault(_) => 12,
+            Error::Tampered => 13,
+            Error::Crypto(_) => 14,
+            Error::Generator(_) => 15,
+            Error::Config(_) => 16,
+            Error::Totp(_) => 17,
+            Error::NoTotp => 18,
+            Error::WeakPassword => 19,
+            Error::Import(_) => 20,
+            Error::Kdbx(_) => 21,
+            Error::ReadOnly => 22,
+            Error::HistoryNotFound => 23,
+            Error::FolderNotFound => 24,
+            Error::PasswordUnchanged => 25,
+            Error::SecretInArguments => 26,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "Błąd odczytu lub zapisu: {}", error),
+            Error::CorruptVault(reason) => write!(f, "Plik sejfu jest uszkodzony: {}", reason),
+            Error::WrongPassword => write!(f, "Niepoprawne hasło!"),
+            Error::Tampered => write!(f, "Zaszyfrowane dane zostały zmienione, nie można ich odczytać!"),
+            Error::Clipboard(error) => write!(f, "Brak dostępu do systemowego schowka: {}", error),
+            Error::Kdf(error) => write!(f, "Błąd tworzenia klucza (Argon2): {}", error),
+            Error::Crypto(error) => write!(f, "Błąd szyfrowania: {}", error),
+            Error::Generator(error) => write!(f, "{}", error),
+            Error::NoProfile => write!(f, "Nie znaleziono profilu. Utwórz sejf komendą init albo uruchom passman bez argumentów, aby ustawić główne hasło."),
+            Error::LabelExists => write!(f, "Już istnieje hasło z tą etykietą!"),
+            Error::LabelNotFound => write!(f, "Nie ma zapisanego hasła z taką etykietą"),
+            Error::PasswordMismatch => write!(f, "Powtórzone hasło musi być identyczne jak pierwsze!"),
+            Error::NotConfirmed => write!(f, "Nie potwierdzono operacji"),
+            Error::MissingValue => write!(f, "Podaj wartość pola"),
+            Error::BackupNotFound => write!(f, "Nie ma kopii zapasowej o takim numerze"),
+            Error::Config(error) => write!(f, "Błąd konfiguracji: {}", error),
+            Error::Totp(error) => write!(f, "Niepoprawny sekret TOTP: {}", error),
+            Error::NoTotp => write!(f, "To hasło nie ma ustawionego sekretu TOTP"),
+            Error::WeakPassword => write!(f, "Hasło jest za słabe"),
+            Error::Import(error) => write!(f, "Nie można zaimportować pliku: {}", error),
+            Error::Kdbx(error) => write!(f, "Błąd bazy KeePass: {}", error),
+            Error::ReadOnly => write!(f, "Sejf jest otwarty tylko do odczytu, zmiany nie zostały zapisane"),
+            Error::HistoryNotFound => write!(f, "W historii hasła nie ma pozycji o takim numerze"),
+            Error::FolderNotFound => write!(f, "Nie ma folderu o takiej nazwie"),
+            Error::PasswordUnchanged => write!(f, "Nowe hasło jest takie samo jak obecne, nic nie zmieniono"),
+            Error::SecretInArguments => write!(f, "Notatki ani sekretu TOTP nie podaje się w komendzie - zostaną odczytane po jej uruchomieniu"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::CorruptVault(error.to_string())
+    }
+}
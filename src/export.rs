@@ -0,0 +1,103 @@
+use std::io;
+use std::path::Path;
+use serde::Serialize;
+use zeroize::{Zeroize, Zeroizing};
+use crate::atomic::write_private;
+use crate::error::Result;
+use crate::vault::Vault;
+
+// Column names match the ones `read_import` looks for, so the file can be imported back
+const CSV_HEADER: [&str; 9] = ["label", "username", "password", "url", "notes", "tags", "totp", "created", "modified"];
+
+/// Formats of plaintext exports, see `export_plaintext`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One row per entry, URLs separated with spaces and tags with commas
+    Csv,
+    /// Array of entries with all their fields
+    Json,
+}
+
+/// Decrypted entry, zeroed when it's dropped
+#[derive(Serialize)]
+struct ExportedEntry {
+    label: String,
+    username: Option<String>,
+    password: String,
+    urls: Vec<String>,
+    notes: Option<String>,
+    tags: Vec<String>,
+    totp: Option<String>,
+    created: u64,
+    modified: u64,
+    last_used: Option<u64>,
+}
+
+impl Drop for ExportedEntry {
+    fn drop(&mut self) {
+        self.password.zeroize();
+        self.notes.zeroize();
+        self.totp.zeroize();
+    }
+}
+
+/// Writes all entries of the vault with decrypted passwords, notes and TOTP
+/// secrets to a new file, readable only by its owner. Fails if the file exists.
+/// Returns the number of exported entries.
+pub fn export_plaintext(vault: &Vault, path: &Path, format: ExportFormat) -> Result<usize> {
+    let entries = exported_entries(vault)?;
+    let data = match format {
+        ExportFormat::Csv => csv(&entries)?,
+        ExportFormat::Json => Zeroizing::new(serde_json::to_vec_pretty(&entries)?),
+    };
+    write_private(path, &data)?;
+    Ok(entries.len())
+}
+
+/// Entries sorted by label
+fn exported_entries(vault: &Vault) -> Result<Vec<ExportedEntry>> {
+    let mut labels: Vec<&str> = vault.iter_labels().collect();
+    labels.sort();
+
+    labels.into_iter().map(|label| {
+        let entry = vault.entry(label)?;
+        Ok(ExportedEntry {
+            label: label.to_string(),
+            username: entry.username.clone(),
+            password: vault.get(label)?,
+            urls: entry.urls.clone(),
+            notes: vault.notes(label)?,
+            tags: entry.tags.clone(),
+            totp: vault.totp_secret(label)?,
+            created: entry.created,
+            modified: entry.modified,
+            last_used: entry.last_used,
+        })
+    }).collect()
+}
+
+fn csv(entries: &[ExportedEntry]) -> Result<Zeroizing<Vec<u8>>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let to_io = |e: csv::Error| io::Error::other(e.to_string());
+
+    writer.write_record(CSV_HEADER).map_err(to_io)?;
+    for entry in entries {
+        let mut record = [
+            entry.label.clone(),
+            entry.username.clone().unwrap_or_default(),
+            entry.password.clone(),
+            entry.urls.join(" "),
+            entry.notes.clone().unwrap_or_default(),
+            entry.tags.join(","),
+            entry.totp.clone().unwrap_or_default(),
+            entry.created.to_string(),
+            entry.modified.to_string(),
+        ];
+        let result = writer.write_record(&record).map_err(to_io);
+        record.zeroize();
+        result?;
+    }
+
+    let data = writer.into_inner().map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(Zeroizing::new(data))
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+/// Separates folders in labels, e.g. `work/aws/prod` is the entry `prod` in the folder `work/aws`
+pub const SEPARATOR: char = '/';
+
+/// Folder of entries, built from their labels
+#[derive(Default)]
+pub struct Folder {
+    /// Subfolders by their names
+    pub folders: BTreeMap<String, Folder>,
+    /// Names of the entries directly in this folder (last parts of their labels), sorted
+    pub entries: Vec<String>,
+}
+
+impl Folder {
+    /// Tree of the entries in folder and its subfolders, all of them if folder is empty.
+    /// None if there are no entries in it.
+    pub fn from_labels<'a>(labels: impl IntoIterator<Item = &'a str>, folder: &str) -> Option<Folder> {
+        let mut tree = Folder::default();
+        let mut found = false;
+        for label in labels {
+            let Some(relative) = relative_label(label, folder) else {
+                continue;
+            };
+            found = true;
+
+            let mut parts: Vec<&str> = relative.split(SEPARATOR).collect();
+            let name = parts.pop().unwrap_or(relative);
+            let mut folder = &mut tree;
+            for part in parts {
+                folder = folder.folders.entry(part.to_string()).or_default();
+            }
+            folder.entries.push(name.to_string());
+        }
+
+        tree.sort();
+        found.then_some(tree)
+    }
+
+    /// Number of entries in the folder and all its subfolders
+    pub fn count(&self) -> usize {
+        self.entries.len() + self.folders.values().map(Folder::count).sum::<usize>()
+    }
+
+    fn sort(&mut self) {
+        self.entries.sort();
+        self.folders.values_mut().for_each(Folder::sort);
+    }
+}
+
+/// Rest of the label after the folder, None if the entry isn't in the folder or
+/// one of its subfolders. Every label is in the empty folder, the top one.
+pub fn relative_label<'a>(label: &'a str, folder: &str) -> Option<&'a str> {
+    let folder = folder.trim_matches(SEPARATOR);
+    if folder.is_empty() {
+        return Some(label);
+    }
+    label.strip_prefix(folder)?.strip_prefix(SEPARATOR)
+}
+
+/// Label of an entry with the name in the folder, the top one if folder is empty
+pub fn join_label(folder: &str, name: &str) -> String {
+    let folder = folder.trim_matches(SEPARATOR);
+    if folder.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}{}{}", folder, SEPARATOR, name)
+    }
+}
+
+/// Last part of a label, the name without folders
+pub fn base_name(label: &str) -> &str {
+    let label = label.trim_end_matches(SEPARATOR);
+    label.rsplit(SEPARATOR).next().unwrap_or(label)
+}
+
+/// New label of an entry or folder moved to destination. Destination ending with
+/// the separator (or only the separator, for the top folder) is a folder to move it into,
+/// otherwise it is the new label itself.
+pub fn move_target(label: &str, destination: &str) -> String {
+    if destination.is_empty() || destination.ends_with(SEPARATOR) {
+        join_label(destination, base_name(label))
+    } else {
+        destination.trim_start_matches(SEPARATOR).to_string()
+    }
+}
@@ -0,0 +1,155 @@
+use rand::seq::{IndexedRandom, SliceRandom};
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::passphrase::Passphrase;
+
+/// Length of generated passwords when none is given
+pub const DEFAULT_PASSWORD_LEN: usize = 32;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+// Characters easy to confuse with each other in some fonts
+const SIMILAR: &str = "iIlL1|oO0'\"`";
+
+/// Rules for generating random passwords. Every enabled character class
+/// appears in the password at least once, or as many times as its minimum says.
+///
+/// Can be read from the config file, missing fields have default values.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Generator {
+    pub length: usize,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    /// Characters which never appear in the password
+    pub exclude: String,
+    /// Leave out look-alikes, like 1, l and I
+    pub exclude_similar: bool,
+    pub min_lowercase: usize,
+    pub min_uppercase: usize,
+    pub min_digits: usize,
+    pub min_symbols: usize,
+}
+
+/// Rules a secret was generated with, kept with the entry so it can be
+/// generated again the same way when the password is rotated
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Recipe {
+    Password(Generator),
+    Passphrase(Passphrase),
+}
+
+impl Recipe {
+    pub fn generate(&self) -> Result<String> {
+        match self {
+            Recipe::Password(generator) => generator.generate(),
+            Recipe::Passphrase(passphrase) => passphrase.generate(),
+        }
+    }
+
+    /// Bits of entropy of generated secrets
+    pub fn entropy(&self) -> Result<f64> {
+        match self {
+            Recipe::Password(generator) => generator.entropy(),
+            Recipe::Passphrase(passphrase) => Ok(passphrase.entropy()),
+        }
+    }
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Generator {
+            length: DEFAULT_PASSWORD_LEN,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            exclude: String::new(),
+            exclude_similar: false,
+            min_lowercase: 0,
+            min_uppercase: 0,
+            min_digits: 0,
+            min_symbols: 0,
+        }
+    }
+}
+
+impl Generator {
+    /// Generates a random password. Fails if the rules can't be met,
+    /// e.g. minimums add up to more than the length.
+    pub fn generate(&self) -> Result<String> {
+        let classes = self.classes()?;
+        let mut rng = rand::rng();
+
+        // Required characters of each class first, the rest from all of them
+        let mut password: Vec<char> = Vec::with_capacity(self.length);
+        for (chars, min) in &classes {
+            for _ in 0..*min {
+                password.extend(chars.choose(&mut rng));
+            }
+        }
+
+        let all: Vec<char> = classes.into_iter().flat_map(|(chars, _)| chars).collect();
+        while password.len() < self.length {
+            password.extend(all.choose(&mut rng));
+        }
+
+        // Required characters shouldn't always be at the start
+        password.shuffle(&mut rng);
+        Ok(password.into_iter().collect())
+    }
+
+    /// Bits of entropy of generated passwords, a bit less in fact because of the minimums
+    pub fn entropy(&self) -> Result<f64> {
+        let characters: usize = self.classes()?.iter().map(|(chars, _)| chars.len()).sum();
+        Ok(self.length as f64 * (characters as f64).log2())
+    }
+
+    /// Allowed characters of every enabled class, with the number of them required
+    fn classes(&self) -> Result<Vec<(Vec<char>, usize)>> {
+        if self.length == 0 {
+            return Err(Error::Generator(String::from("Długość hasła musi być dodatnią liczbą całkowitą!")));
+        }
+
+        let classes = [
+            ("małych liter", LOWERCASE, self.lowercase, self.min_lowercase),
+            ("wielkich liter", UPPERCASE, self.uppercase, self.min_uppercase),
+            ("cyfr", DIGITS, self.digits, self.min_digits),
+            ("symboli", SYMBOLS, self.symbols, self.min_symbols),
+        ];
+
+        let mut allowed = Vec::new();
+        for (name, chars, enabled, min) in classes {
+            if !enabled {
+                if min > 0 {
+                    return Err(Error::Generator(format!("Wymagana liczba {} jest podana, ale są one wyłączone", name)));
+                }
+                continue;
+            }
+
+            let chars: Vec<char> = chars.chars()
+                .filter(|c| !self.exclude.contains(*c))
+                .filter(|c| !self.exclude_similar || !SIMILAR.contains(*c))
+                .collect();
+            if chars.is_empty() {
+                return Err(Error::Generator(format!("Po wykluczeniu znaków nie zostało nic z {}", name)));
+            }
+            allowed.push((chars, min.max(1)));
+        }
+
+        if allowed.is_empty() {
+            return Err(Error::Generator(String::from("Trzeba włączyć przynajmniej jedną klasę znaków")));
+        }
+        let required: usize = allowed.iter().map(|(_, min)| min).sum();
+        if required > self.length {
+            return Err(Error::Generator(format!("Hasło o długości {} nie zmieści {} wymaganych znaków", self.length, required)));
+        }
+
+        Ok(allowed)
+    }
+}
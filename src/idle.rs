@@ -0,0 +1,207 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use passman::{Result, SearchIndex, Vault};
+use crate::cli::{self, SharedIndex};
+use crate::commands::Session;
+use crate::config::Config;
+
+// How often the watchdog looks at the session
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// REPL session, which can be locked by the watchdog thread
+pub enum State {
+    Unlocked(Box<Session>),
+    /// Vaults were dropped (with their keys) after inactivity,
+    /// the one at path needs the master password again
+    Locked { name: String, path: PathBuf, read_only: bool },
+}
+
+pub struct Guarded {
+    pub state: State,
+    last_activity: Instant,
+    /// Labels completed by the line editor
+    index: SharedIndex,
+}
+
+impl Guarded {
+    /// Name of the current vault, also when locked
+    pub fn name(&self) -> &str {
+        match &self.state {
+            State::Unlocked(session) => &session.name,
+            State::Locked { name, .. } => name,
+        }
+    }
+
+    /// Resets the inactivity timer
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// Clears copied secret from the clipboard and drops all unlocked vaults,
+    /// which zeroizes their keys. Only the current vault is remembered and
+    /// its labels aren't completed until it is unlocked again.
+    pub fn lock_session(&mut self) {
+        let State::Unlocked(session) = &self.state else {
+            return;
+        };
+        let locked = State::Locked {
+            name: session.name.clone(),
+            path: session.vault.path().to_path_buf(),
+            read_only: session.vault.is_read_only(),
+        };
+
+        if let State::Unlocked(mut session) = std::mem::replace(&mut self.state, locked) {
+            // Nobody would see an error here, the helper process clears it anyway
+            let _ = session.clear_clipboard();
+        }
+        cli::set_index(&self.index, SearchIndex::default());
+    }
+
+    /// Locks the session if there was no activity for timeout, returns true if it did
+    pub fn lock_if_idle(&mut self, timeout: Duration) -> bool {
+        if !matches!(self.state, State::Unlocked(_)) || self.last_activity.elapsed() < timeout {
+            return false;
+        }
+        self.lock_session();
+        true
+    }
+
+    /// Unlocks the locked vault again with open, which asks for the master password
+    pub fn unlock(&mut self, open: impl FnOnce(&Path, bool) -> Result<Vault>, config: &Config) -> Result<()> {
+        let State::Locked { name, path, read_only } = &self.state else {
+            return Ok(());
+        };
+        let mut session = Session::new(name.clone(), open(path, *read_only)?);
+        session.configure(config);
+        cli::set_index(&self.index, SearchIndex::new(&session.vault));
+        self.state = State::Unlocked(Box::new(session));
+        self.touch();
+        Ok(())
+    }
+}
+
+pub type Shared = Arc<Mutex<Guarded>>;
+
+pub fn new_shared(session: Session, index: SharedIndex) -> Shared {
+    Arc::new(Mutex::new(Guarded { state: State::Unlocked(Box::new(session)), last_activity: Instant::now(), index }))
+}
+
+/// Locks shared state. A panic while it was held doesn't make it unusable,
+/// the session is still in a consistent state between commands.
+pub fn lock(shared: &Shared) -> MutexGuard<'_, Guarded> {
+    shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Starts a thread which locks the session after timeout without any command.
+/// It stops when the REPL drops the shared state.
+pub fn start_watchdog(shared: &Shared, timeout: Duration) {
+    let shared = Arc::downgrade(shared);
+    thread::spawn(move || loop {
+        thread::sleep(CHECK_INTERVAL);
+        let Some(shared) = shared.upgrade() else {
+            break;
+        };
+
+        lock(&shared).lock_if_idle(timeout);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use passman::{Error, KdfParams};
+    use super::*;
+
+    const PASSWORD: &str = "test-password";
+
+    /// Unlocked state with a vault of one entry, and the directory to remove afterwards
+    fn unlocked(test: &str) -> (Shared, SharedIndex, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("passman-idle-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let kdf = KdfParams { memory_kib: 64, iterations: 1, parallelism: 1, ..KdfParams::default() };
+        let mut vault = Vault::create_with_kdf(dir.join("vault.bin"), PASSWORD, kdf).unwrap();
+        vault.insert("github", "gh-secret").unwrap();
+        vault.save().unwrap();
+
+        let index = Arc::new(Mutex::new(SearchIndex::new(&vault)));
+        let shared = new_shared(Session::new(String::from("test"), vault), index.clone());
+        (shared, index, dir)
+    }
+
+    fn open_with(password: &str) -> impl FnOnce(&Path, bool) -> Result<Vault> + '_ {
+        move |path, read_only| {
+            let mut vault = Vault::open(path, password)?;
+            vault.set_read_only(read_only);
+            Ok(vault)
+        }
+    }
+
+    #[test]
+    fn locks_after_timeout() {
+        let (shared, index, dir) = unlocked("timeout");
+        let mut guarded = lock(&shared);
+        assert!(!guarded.lock_if_idle(Duration::from_secs(60)));
+        assert!(matches!(guarded.state, State::Unlocked(_)));
+
+        guarded.last_activity -= Duration::from_secs(61);
+        assert!(guarded.lock_if_idle(Duration::from_secs(60)));
+        // Session with the vault is dropped, only where to find it is kept
+        match &guarded.state {
+            State::Locked { name, path, read_only } => {
+                assert_eq!(name, "test");
+                assert_eq!(path, &dir.join("vault.bin"));
+                assert!(!read_only);
+            }
+            State::Unlocked(_) => panic!("session wasn't locked"),
+        }
+        assert_eq!(guarded.name(), "test");
+        assert!(*index.lock().unwrap() == SearchIndex::default());
+        drop(guarded);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn activity_resets_timer() {
+        let (shared, _, dir) = unlocked("activity");
+        let mut guarded = lock(&shared);
+        guarded.last_activity -= Duration::from_secs(61);
+        guarded.touch();
+        assert!(!guarded.lock_if_idle(Duration::from_secs(60)));
+        assert!(matches!(guarded.state, State::Unlocked(_)));
+        drop(guarded);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn locked_session_needs_password() {
+        let (shared, index, dir) = unlocked("password");
+        let mut guarded = lock(&shared);
+        guarded.lock_session();
+
+        let config = Config::default();
+        assert!(matches!(guarded.unlock(open_with("wrong"), &config), Err(Error::WrongPassword)));
+        assert!(matches!(guarded.state, State::Locked { .. }));
+        assert!(*index.lock().unwrap() == SearchIndex::default());
+
+        guarded.unlock(open_with(PASSWORD), &config).unwrap();
+        let State::Unlocked(session) = &guarded.state else {
+            panic!("session wasn't unlocked");
+        };
+        assert_eq!(session.vault.get("github").unwrap(), "gh-secret");
+        assert!(*index.lock().unwrap() == SearchIndex::new(&session.vault));
+        drop(guarded);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn watchdog_locks_idle_session() {
+        let (shared, _, dir) = unlocked("watchdog");
+        start_watchdog(&shared, Duration::ZERO);
+        thread::sleep(CHECK_INTERVAL * 2);
+        assert!(matches!(lock(&shared).state, State::Locked { .. }));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
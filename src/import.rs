@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use serde_json::Value;
+use zeroize::Zeroize;
+use crate::error::{Error, Result};
+
+// File with items inside a 1Password export archive
+const ONE_PASSWORD_DATA: &str = "export.data";
+
+/// Formats of other password managers `read_import` understands
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// Unencrypted JSON export
+    Bitwarden,
+    /// KeePass 2 XML export, also from KeePassXC
+    KeePass,
+    /// 1PUX archive
+    OnePassword,
+    /// Any CSV with a header, columns are found by their names
+    /// (Chrome, Firefox, Bitwarden, LastPass) or given with `ColumnMapping`
+    Csv,
+}
+
+impl ImportFormat {
+    /// Guesses format by the file extension, CSV if it's unknown
+    pub fn detect(path: &Path) -> ImportFormat {
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => ImportFormat::Bitwarden,
+            "xml" => ImportFormat::KeePass,
+            "1pux" => ImportFormat::OnePassword,
+            _ => ImportFormat::Csv,
+        }
+    }
+}
+
+/// Entry read from an export, before it's added to the vault.
+/// Password is zeroed when it's dropped.
+#[derive(Clone, Debug, Default)]
+pub struct ImportedEntry {
+    pub label: String,
+    pub password: String,
+    pub username: Option<String>,
+    pub urls: Vec<String>,
+    pub notes: Option<String>,
+    pub tags: Vec<String>,
+    pub totp: Option<String>,
+}
+
+impl Drop for ImportedEntry {
+    fn drop(&mut self) {
+        self.password.zeroize();
+        self.notes.zeroize();
+        self.totp.zeroize();
+    }
+}
+
+/// Entries of an export, with the number of items which were left out
+/// because they have no password (e.g. secure notes or cards)
+#[derive(Debug, Default)]
+pub struct Import {
+    pub entries: Vec<ImportedEntry>,
+    pub without_password: usize,
+}
+
+/// Fields of an entry which can be read from a CSV column
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum CsvField {
+    Label,
+    Password,
+    Username,
+    Url,
+    Notes,
+    Tags,
+    Totp,
+}
+
+impl CsvField {
+    const ALL: [CsvField; 7] = [CsvField::Label, CsvField::Password, CsvField::Username, CsvField::Url,
+        CsvField::Notes, CsvField::Tags, CsvField::Totp];
+
+    fn name(self) -> &'static str {
+        match self {
+            CsvField::Label => "label",
+            CsvField::Password => "password",
+            CsvField::Username => "username",
+            CsvField::Url => "url",
+            CsvField::Notes => "notes",
+            CsvField::Tags => "tags",
+            CsvField::Totp => "totp",
+        }
+    }
+
+    /// Usual names of the column in exports of browsers and password managers
+    fn known_columns(self) -> &'static [&'static str] {
+        match self {
+            CsvField::Label => &["label", "name", "title"],
+            CsvField::Password => &["password", "login_password", "pass"],
+            CsvField::Username => &["username", "login_username", "login", "user", "email"],
+            CsvField::Url => &["url", "login_uri", "uri", "website", "origin_url"],
+            CsvField::Notes => &["notes", "note", "extra", "comments"],
+            CsvField::Tags => &["tags", "tag", "folder", "grouping", "group"],
+            CsvField::Totp => &["totp", "login_totp", "otp", "otpauth"],
+        }
+    }
+}
+
+/// Names of CSV columns holding the fields, for exports with unusual headers.
+/// Fields without a mapping are looked for under their usual names.
+#[derive(Clone, Debug, Default)]
+pub struct ColumnMapping {
+    columns: HashMap<CsvField, String>,
+}
+
+impl ColumnMapping {
+    /// Parses `field=column` pairs, e.g. `label=Title`. Fields are
+    /// label, password, username, url, notes, tags and totp.
+    pub fn parse(pairs: &[String]) -> Result<ColumnMapping> {
+        let mut columns = HashMap::new();
+        for pair in pairs {
+            let (field, column) = pair.split_once('=')
+                .ok_or_else(|| Error::Import(format!("mapowanie kolumny musi mieć postać pole=kolumna: {}", pair)))?;
+            let field = CsvField::ALL.into_iter().find(|f| f.name() == field.trim())
+                .ok_or_else(|| Error::Import(format!("nieznane pole {}", field)))?;
+            columns.insert(field, column.trim().to_lowercase());
+        }
+        Ok(ColumnMapping { columns })
+    }
+}
+
+/// Reads entries from an export of another password manager
+pub fn read_import(path: &Path, format: ImportFormat, mapping: &ColumnMapping) -> Result<Import> {
+    let entries = match format {
+        ImportFormat::Bitwarden => bitwarden(&fs::read_to_string(path)?)?,
+        ImportFormat::KeePass => keepass(&fs::read_to_string(path)?)?,
+        ImportFormat::OnePassword => one_password(path)?,
+        ImportFormat::Csv => csv(&fs::read(path)?, mapping)?,
+    };
+
+    let mut import = Import::default();
+    for mut entry in entries {
+        if entry.password.is_empty() {
+            import.without_password += 1;
+            continue;
+        }
+
+        entry.label = entry.label.trim().to_string();
+        entry.username = entry.username.take().map(|username| username.trim().to_string()).filter(|username| !username.is_empty());
+        entry.urls.retain(|url| !url.trim().is_empty());
+        entry.tags.retain(|tag| !tag.trim().is_empty());
+        entry.notes = entry.notes.take().filter(|notes| !notes.trim().is_empty());
+        entry.totp = entry.totp.take().filter(|totp| !totp.trim().is_empty());
+        if entry.label.is_empty() {
+            entry.label = default_label(&entry);
+        }
+        import.entries.push(entry);
+    }
+    Ok(import)
+}
+
+/// Label for entries without a name - host of the first URL or the user name
+fn default_label(entry: &ImportedEntry) -> String {
+    entry.urls.first().map(|url| host(url))
+        .filter(|host| !host.is_empty())
+        .or_else(|| entry.username.clone())
+        .unwrap_or_else(|| String::from("import"))
+}
+
+/// Host of an URL without the www. prefix, e.g. github.com for https://www.github.com/login
+fn host(url: &str) -> String {
+    let rest = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = host.rsplit('@').next().unwrap_or(host);
+    let host = host.split(':').next().unwrap_or(host);
+    host.strip_prefix("www.").unwrap_or(host).to_string()
+}
+
+fn text(value: &Value) -> Option<String> {
+    value.as_str().map(String::from)
+}
+
+fn bitwarden(data: &str) -> Result<Vec<ImportedEntry>> {
+    let json: Value = serde_json::from_str(data).map_err(|e| Error::Import(e.to_string()))?;
+    if json["encrypted"].as_bool() == Some(true) {
+        return Err(Error::Import(String::from("eksport Bitwardena jest zaszyfrowany, wyeksportuj sejf jako niezaszyfrowany JSON")));
+    }
+    let items = json["items"].as_array()
+        .ok_or_else(|| Error::Import(String::from("brak listy items, to nie jest eksport Bitwardena")))?;
+
+    let folders: HashMap<&str, &str> = json["folders"].as_array().into_iter().flatten()
+        .filter_map(|folder| Some((folder["id"].as_str()?, folder["name"].as_str()?)))
+        .collect();
+
+    Ok(items.iter().map(|item| {
+        let login = &item["login"];
+        ImportedEntry {
+            label: text(&item["name"]).unwrap_or_default(),
+            password: text(&login["password"]).unwrap_or_default(),
+            username: text(&login["username"]),
+            urls: login["uris"].as_array().into_iter().flatten().filter_map(|uri| text(&uri["uri"])).collect(),
+            notes: text(&item["notes"]),
+            tags: item["folderId"].as_str().and_then(|id| folders.get(id)).map(|name| name.to_string()).into_iter().collect(),
+            totp: text(&login["totp"]),
+        }
+    }).collect())
+}
+
+fn keepass(data: &str) -> Result<Vec<ImportedEntry>> {
+    let document = roxmltree::Document::parse(data).map_err(|e| Error::Import(e.to_string()))?;
+    let root = document.root_element();
+    if !root.has_tag_name("KeePassFile") {
+        return Err(Error::Import(String::from("to nie jest eksport XML KeePassa")));
+    }
+
+    let child_text = |node: roxmltree::Node, name: &str| {
+        node.children().find(|child| child.has_tag_name(name)).and_then(|child| child.text()).map(String::from)
+    };
+    // Deleted entries stay in the recycle bin group
+    let recycle_bin = root.children().find(|node| node.has_tag_name("Meta"))
+        .and_then(|meta| child_text(meta, "RecycleBinUUID"));
+
+    let mut entries = Vec::new();
+    let mut groups: Vec<(roxmltree::Node, Option<String>)> = root.children()
+        .filter(|node| node.has_tag_name("Root"))
+        .flat_map(|node| node.children().filter(|child| child.has_tag_name("Group")))
+        // The top group is the database itself, its name isn't a tag
+        .map(|group| (group, None))
+        .collect();
+
+    while let Some((group, tag)) = groups.pop() {
+        if recycle_bin.is_some() && child_text(group, "UUID") == recycle_bin {
+            continue;
+        }
+
+        for child in group.children().filter(|child| child.is_element()) {
+            if child.has_tag_name("Group") {
+                groups.push((child, child_text(child, "Name")));
+                continue;
+            }
+            if !child.has_tag_name("Entry") {
+                continue;
+            }
+
+            // Only direct strings, older versions of the entry are in History
+            let mut strings: Vec<(String, String)> = child.children()
+                .filter(|node| node.has_tag_name("String"))
+                .filter_map(|node| Some((child_text(node, "Key")?, child_text(node, "Value").unwrap_or_default())))
+                .collect();
+            let mut take = |key: &str| strings.iter().position(|(k, _)| k == key).map(|i| strings.remove(i).1);
+
+            let mut entry = ImportedEntry {
+                label: take("Title").unwrap_or_default(),
+                password: take("Password").unwrap_or_default(),
+                username: take("UserName"),
+                urls: take("URL").into_iter().collect(),
+                notes: take("Notes"),
+                tags: tag.iter().cloned().collect(),
+                totp: take("otp").or_else(|| take("TimeOtp-Secret-Base32")),
+            };
+            if let Some(tags) = child_text(child, "Tags") {
+                entry.tags.extend(tags.split([';', ',']).map(|tag| tag.trim().to_string()));
+            }
+
+            // Custom fields would be lost otherwise
+            let custom: Vec<String> = strings.iter()
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(key, value)| format!("{}: {}", key, value))
+                .collect();
+            if !custom.is_empty() {
+                let notes = entry.notes.take().filter(|notes| !notes.is_empty()).into_iter().chain(custom);
+                entry.notes = Some(notes.collect::<Vec<_>>().join("\n"));
+            }
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+fn one_password(path: &Path) -> Result<Vec<ImportedEntry>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?).map_err(|e| Error::Import(e.to_string()))?;
+    let mut data = String::new();
+    archive.by_name(ONE_PASSWORD_DATA).map_err(|e| Error::Import(format!("{}: {}", ONE_PASSWORD_DATA, e)))?
+        .read_to_string(&mut data)?;
+    let json: Value = serde_json::from_str(&data).map_err(|e| Error::Import(e.to_string()))?;
+
+    let mut entries = Vec::new();
+    for account in json["accounts"].as_array().into_iter().flatten() {
+        for vault in account["vaults"].as_array().into_iter().flatten() {
+            for item in vault["items"].as_array().into_iter().flatten() {
+                // Older exports wrap every item
+                let item = if item["item"].is_object() { &item["item"] } else { item };
+                if matches!(item["state"].as_str(), Some("archived" | "deleted")) {
+                    continue;
+                }
+                entries.push(one_password_item(item));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn one_password_item(item: &Value) -> ImportedEntry {
+    let overview = &item["overview"];
+    let details = &item["details"];
+    let login_field = |designation: &str| {
+        details["loginFields"].as_array().into_iter().flatten()
+            .find(|field| field["designation"].as_str() == Some(designation))
+            .and_then(|field| text(&field["value"]))
+    };
+
+    let mut urls: Vec<String> = text(&overview["url"]).into_iter().collect();
+    for url in overview["urls"].as_array().into_iter().flatten().filter_map(|url| text(&url["url"])) {
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    let totp = details["sections"].as_array().into_iter().flatten()
+        .flat_map(|section| section["fields"].as_array().into_iter().flatten())
+        .find_map(|field| text(&field["value"]["totp"]));
+
+    ImportedEntry {
+        label: text(&overview["title"]).unwrap_or_default(),
+        password: login_field("password").or_else(|| text(&details["password"])).unwrap_or_default(),
+        username: login_field("username"),
+        urls,
+        notes: text(&details["notesPlain"]),
+        tags: overview["tags"].as_array().into_iter().flatten().filter_map(text).collect(),
+        totp,
+    }
+}
+
+fn csv(data: &[u8], mapping: &ColumnMapping) -> Result<Vec<ImportedEntry>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(data);
+    let headers: Vec<String> = reader.headers().map_err(|e| Error::Import(e.to_string()))?
+        .iter().map(|header| header.trim().trim_start_matches('\u{feff}').to_lowercase()).collect();
+
+    let mut columns = HashMap::new();
+    for field in CsvField::ALL {
+        let column = match mapping.columns.get(&field) {
+            Some(name) => Some(headers.iter().position(|header| header == name)
+                .ok_or_else(|| Error::Import(format!("nie ma kolumny {}", name)))?),
+            None => field.known_columns().iter().find_map(|name| headers.iter().position(|header| header == name)),
+        };
+        if let Some(column) = column {
+            columns.insert(field, column);
+        }
+    }
+    if !columns.contains_key(&CsvField::Password) {
+        return Err(Error::Import(String::from("nie znaleziono kolumny z hasłem, podaj ją opcją --columns password=<kolumna>")));
+    }
+
+    let mut entries = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| Error::Import(e.to_string()))?;
+        let get = |field| columns.get(&field).and_then(|&i| record.get(i)).map(String::from).filter(|value| !value.is_empty());
+        entries.push(ImportedEntry {
+            label: get(CsvField::Label).unwrap_or_default(),
+            password: get(CsvField::Password).unwrap_or_default(),
+            username: get(CsvField::Username),
+            // Exports of passman keep all URLs in one column
+            urls: get(CsvField::Url).map(|urls| urls.split_whitespace().map(String::from).collect()).unwrap_or_default(),
+            notes: get(CsvField::Notes),
+            tags: get(CsvField::Tags).map(|tags| tags.split([',', ';']).map(|tag| tag.trim().to_string()).collect()).unwrap_or_default(),
+            totp: get(CsvField::Totp),
+        });
+    }
+    Ok(entries)
+}
@@ -0,0 +1,573 @@
+use std::io::{Read, Write};
+use aes::Aes256;
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use data_encoding::BASE64;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, TryRngCore};
+use salsa20::Salsa20;
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::Zeroizing;
+use crate::auth::KdfParams;
+use crate::error::{Error, Result};
+use crate::xml::Element;
+
+/*
+ * Layout of a KDBX 4 file (KeePass 2.35+, KeePassXC 2.7+), numbers little endian:
+ *
+ *  signatures      8 bytes  03 D9 A2 9A 67 FB 4B B5
+ *  version         u16 minor, u16 major (4)
+ *  header fields   u8 id, u32 length, data - cipher, compression, master seed,
+ *                  IV, KDF parameters (variant dictionary), ..., ending with id 0
+ *  SHA-256 of the header
+ *  HMAC-SHA256 of the header
+ *  blocks          HMAC-SHA256, u32 length, data - ending with an empty block
+ *
+ * Blocks hold the encrypted, optionally gzipped payload: inner header (key of
+ * the stream cipher hiding protected values, attachments) and the XML document.
+ * Key is derived from SHA-256(SHA-256(password)), key files aren't supported.
+ */
+
+const SIGNATURE: [u8; 8] = [0x03, 0xD9, 0xA2, 0x9A, 0x67, 0xFB, 0x4B, 0xB5];
+const MAJOR_VERSION: u16 = 4;
+
+const END_OF_HEADER: u8 = 0;
+const CIPHER_ID: u8 = 2;
+const COMPRESSION: u8 = 3;
+const MASTER_SEED: u8 = 4;
+const ENCRYPTION_IV: u8 = 7;
+const KDF_PARAMETERS: u8 = 11;
+
+const INNER_STREAM_ID: u8 = 1;
+const INNER_STREAM_KEY: u8 = 2;
+const INNER_BINARY: u8 = 3;
+const STREAM_SALSA20: u32 = 2;
+const STREAM_CHACHA20: u32 = 3;
+// Fixed nonce of the Salsa20 inner stream
+const SALSA20_NONCE: [u8; 8] = [0xE8, 0x30, 0x09, 0x4B, 0x97, 0x20, 0x5D, 0x2A];
+
+const CIPHER_AES256: [u8; 16] = [0x31, 0xC1, 0xF2, 0xE6, 0xBF, 0x71, 0x43, 0x50, 0xBE, 0x58, 0x05, 0x21, 0x6A, 0xFC, 0x5A, 0xFF];
+const CIPHER_CHACHA20: [u8; 16] = [0xD6, 0x03, 0x8A, 0x2B, 0x8B, 0x6F, 0x4C, 0xB5, 0xA5, 0x24, 0x33, 0x9A, 0x31, 0xDB, 0xB5, 0x9A];
+const KDF_AES: [u8; 16] = [0xC9, 0xD9, 0xF3, 0x9A, 0x62, 0x8A, 0x44, 0x60, 0xBF, 0x74, 0x0D, 0x08, 0xC1, 0x8A, 0x4F, 0xEA];
+const KDF_ARGON2D: [u8; 16] = [0xEF, 0x63, 0x6D, 0xDF, 0x8C, 0x29, 0x44, 0x4B, 0x91, 0xF7, 0xA9, 0xA4, 0x03, 0xE3, 0x0A, 0x0C];
+const KDF_ARGON2ID: [u8; 16] = [0x9E, 0x29, 0x8B, 0x19, 0x56, 0xDB, 0x47, 0x73, 0xB2, 0x3D, 0xFC, 0x3E, 0xC6, 0xF0, 0xA1, 0xE6];
+
+// Same size KeePass uses
+const BLOCK_SIZE: usize = 1024 * 1024;
+const HEADER_BLOCK_INDEX: u64 = u64::MAX;
+
+// KDF parameters come from the file before it's authenticated, so a crafted file could
+// make opening it run for hours or allocate all memory. KeePassXC stays far below these.
+const MAX_AES_KDF_ROUNDS: u64 = 100_000_000;
+const MAX_ARGON2_MEMORY_KIB: u32 = 4 * 1024 * 1024;
+const MAX_ARGON2_ITERATIONS: u32 = 1000;
+
+/// Value of a variant dictionary, the format of KDF parameters
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Variant {
+    U32(u32),
+    U64(u64),
+    Bool(bool),
+    I32(i32),
+    I64(i64),
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+/// Entries in the order they were in the file
+type Variants = Vec<(String, Variant)>;
+
+/// Opened KDBX 4 database with everything needed to write it back
+#[derive(Clone)]
+pub struct Kdbx {
+    minor_version: u16,
+    cipher: Vec<u8>,
+    compression: u32,
+    kdf: Variants,
+    // Fields passman doesn't use (e.g. public custom data), written back as they were
+    other_fields: Vec<(u8, Vec<u8>)>,
+    composite_key: Zeroizing<[u8; 32]>,
+    transformed_key: Zeroizing<[u8; 32]>,
+    // Attachments with their flags byte, the document refers to them by index
+    binaries: Vec<Zeroizing<Vec<u8>>>,
+}
+
+pub fn is_kdbx(data: &[u8]) -> bool {
+    data.starts_with(&SIGNATURE)
+}
+
+/// Key made from the password alone, before the KDF
+pub fn composite_key(password: &str) -> Zeroizing<[u8; 32]> {
+    let inner: Zeroizing<[u8; 32]> = Zeroizing::new(Sha256::digest(password.as_bytes()).into());
+    Zeroizing::new(Sha256::digest(inner.as_ref()).into())
+}
+
+impl Kdbx {
+    /// Decrypts the database. Returns it with its XML document,
+    /// protected values in it are already in plaintext.
+    pub fn open(data: &[u8], composite_key: Zeroizing<[u8; 32]>) -> Result<(Kdbx, Element)> {
+        let mut reader = Reader { bytes: data, pos: 0 };
+        if reader.take(SIGNATURE.len())? != SIGNATURE {
+            return Err(Error::Kdbx(String::from("to nie jest plik KeePass")));
+        }
+        let minor_version = reader.u16()?;
+        let major_version = reader.u16()?;
+        if major_version != MAJOR_VERSION {
+            return Err(Error::Kdbx(format!("obsługiwany jest tylko format KDBX 4, plik ma wersję {}.{}", major_version, minor_version)));
+        }
+
+        let (mut cipher, mut compression, mut seed, mut iv, mut kdf) = (None, 0, None, None, None);
+        let mut other_fields = Vec::new();
+        loop {
+            let id = reader.u8()?;
+            let length = reader.u32()? as usize;
+            let value = reader.take(length)?;
+            match id {
+                END_OF_HEADER => break,
+                CIPHER_ID => cipher = Some(value.to_vec()),
+                COMPRESSION => compression = Reader { bytes: value, pos: 0 }.u32()?,
+                MASTER_SEED => seed = Some(value),
+                ENCRYPTION_IV => iv = Some(value),
+                KDF_PARAMETERS => kdf = Some(parse_variants(value)?),
+                _ => other_fields.push((id, value.to_vec())),
+            }
+        }
+        let missing = |name: &str| Error::Kdbx(format!("brak pola nagłówka: {}", name));
+        let cipher = cipher.ok_or_else(|| missing("szyfr"))?;
+        let seed = seed.ok_or_else(|| missing("ziarno klucza"))?;
+        let iv = iv.ok_or_else(|| missing("wektor inicjujący"))?;
+        let kdf = kdf.ok_or_else(|| missing("parametry KDF"))?;
+
+        let header = &data[..reader.pos];
+        if reader.take(32)? != Sha256::digest(header).as_slice() {
+            return Err(Error::Kdbx(String::from("nagłówek jest uszkodzony")));
+        }
+        let header_hmac = reader.take(32)?;
+
+        let transformed_key = transform_key(composite_key.as_ref(), &kdf)?;
+        let hmac_key = hmac_key(seed, transformed_key.as_ref());
+        // Only the right key gives the right HMAC, that's how KeePass tells a wrong password
+        if block_hmac(&hmac_key, HEADER_BLOCK_INDEX, &[header]).verify_slice(header_hmac).is_err() {
+            return Err(Error::WrongPassword);
+        }
+
+        let mut encrypted = Vec::new();
+        for index in 0.. {
+            let hmac = reader.take(32)?;
+            let length = reader.take(4)?;
+            let block = reader.take(u32::from_le_bytes(length.try_into().unwrap()) as usize)?;
+            if block_hmac(&hmac_key, index, &[length, block]).verify_slice(hmac).is_err() {
+                return Err(Error::Tampered);
+            }
+            if block.is_empty() {
+                break;
+            }
+            encrypted.extend_from_slice(block);
+        }
+
+        let key = payload_key(seed, transformed_key.as_ref());
+        let mut payload = decrypt(&cipher, key.as_ref(), iv, encrypted)?;
+        if compression == 1 {
+            let mut decompressed = Zeroizing::new(Vec::new());
+            GzDecoder::new(payload.as_slice()).read_to_end(&mut decompressed)
+                .map_err(|e| Error::Kdbx(format!("nie można rozpakować danych: {}", e)))?;
+            payload = decompressed;
+        }
+
+        let mut reader = Reader { bytes: &payload, pos: 0 };
+        let (mut stream_id, mut stream_key, mut binaries) = (None, None, Vec::new());
+        loop {
+            let id = reader.u8()?;
+            let length = reader.u32()? as usize;
+            let value = reader.take(length)?;
+            match id {
+                END_OF_HEADER => break,
+                INNER_STREAM_ID => stream_id = Some(Reader { bytes: value, pos: 0 }.u32()?),
+                INNER_STREAM_KEY => stream_key = Some(Zeroizing::new(value.to_vec())),
+                INNER_BINARY => binaries.push(Zeroizing::new(value.to_vec())),
+                _ => {}
+            }
+        }
+
+        let xml = std::str::from_utf8(&payload[reader.pos..])
+            .map_err(|_| Error::Kdbx(String::from("dokument XML nie jest poprawnym UTF-8")))?;
+        let mut document = Element::parse(xml)?;
+        let mut stream = InnerStream::new(stream_id.unwrap_or(STREAM_CHACHA20), &stream_key.ok_or_else(|| missing("klucz strumienia"))?)?;
+        document.visit_mut(&mut |element| {
+            if is_protected(element) {
+                let mut value = Zeroizing::new(BASE64.decode(element.text().trim().as_bytes())
+                    .map_err(|e| Error::Kdbx(format!("chronione pole nie jest w base64: {}", e)))?);
+                stream.apply(&mut value);
+                element.set_text(std::str::from_utf8(&value).map_err(|_| Error::Tampered)?);
+            }
+            Ok(())
+        })?;
+
+        let kdbx = Kdbx { minor_version, cipher, compression, kdf, other_fields, composite_key, transformed_key, binaries };
+        Ok((kdbx, document))
+    }
+
+    /// Encrypts the document and returns complete contents of the file.
+    /// Master seed, IV and the key of the inner stream are new every time.
+    pub fn seal(&self, document: &Element) -> Result<Vec<u8>> {
+        let seed = random_bytes(32)?;
+        let iv = random_bytes(if self.cipher == CIPHER_CHACHA20 { 12 } else { 16 })?;
+
+        let mut header = SIGNATURE.to_vec();
+        header.extend_from_slice(&self.minor_version.to_le_bytes());
+        header.extend_from_slice(&MAJOR_VERSION.to_le_bytes());
+        push_field(&mut header, CIPHER_ID, &self.cipher);
+        push_field(&mut header, COMPRESSION, &self.compression.to_le_bytes());
+        push_field(&mut header, MASTER_SEED, &seed);
+        push_field(&mut header, ENCRYPTION_IV, &iv);
+        push_field(&mut header, KDF_PARAMETERS, &encode_variants(&self.kdf));
+        for (id, value) in &self.other_fields {
+            push_field(&mut header, *id, value);
+        }
+        push_field(&mut header, END_OF_HEADER, b"\r\n\r\n");
+
+        let hmac_key = hmac_key(&seed, self.transformed_key.as_ref());
+        let mut file = header.clone();
+        file.extend_from_slice(&Sha256::digest(&header));
+        file.extend_from_slice(&block_hmac(&hmac_key, HEADER_BLOCK_INDEX, &[&header]).finalize().into_bytes());
+
+        let stream_key = Zeroizing::new(random_bytes(64)?);
+        let mut payload = Zeroizing::new(Vec::new());
+        push_field(&mut payload, INNER_STREAM_ID, &STREAM_CHACHA20.to_le_bytes());
+        push_field(&mut payload, INNER_STREAM_KEY, &stream_key);
+        for binary in &self.binaries {
+            push_field(&mut payload, INNER_BINARY, binary);
+        }
+        push_field(&mut payload, END_OF_HEADER, &[]);
+
+        let mut document = document.clone();
+        let mut stream = InnerStream::new(STREAM_CHACHA20, &stream_key)?;
+        document.visit_mut(&mut |element| {
+            if is_protected(element) {
+                let mut value = Zeroizing::new(element.text().into_bytes());
+                stream.apply(&mut value);
+                element.set_text(&BASE64.encode(&value));
+            }
+            Ok(())
+        })?;
+        payload.extend_from_slice(document.to_document().as_bytes());
+
+        if self.compression == 1 {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&payload)?;
+            payload = Zeroizing::new(encoder.finish()?);
+        }
+
+        let key = payload_key(&seed, self.transformed_key.as_ref());
+        let encrypted = encrypt(&self.cipher, key.as_ref(), &iv, &payload)?;
+        let blocks = encrypted.chunks(BLOCK_SIZE).chain(std::iter::once(&[][..]));
+        for (index, block) in blocks.enumerate() {
+            let length = (block.len() as u32).to_le_bytes();
+            file.extend_from_slice(&block_hmac(&hmac_key, index as u64, &[&length, block]).finalize().into_bytes());
+            file.extend_from_slice(&length);
+            file.extend_from_slice(block);
+        }
+        Ok(file)
+    }
+
+    /// Argon2 parameters of the file, fails for AES-KDF
+    pub fn kdf(&self) -> Result<KdfParams> {
+        argon2_params(&self.kdf)
+    }
+
+    pub fn composite_key(&self) -> Zeroizing<[u8; 32]> {
+        self.composite_key.clone()
+    }
+
+    pub fn check_password(&self, password: &str) -> Result<bool> {
+        Ok(transform_key(composite_key(password).as_ref(), &self.kdf)? == self.transformed_key)
+    }
+
+    /// Changes the password, with a new KDF salt. Takes effect when the file is sealed.
+    pub fn set_password(&mut self, password: &str) -> Result<()> {
+        let mut kdf = self.kdf.clone();
+        set_variant(&mut kdf, "S", Variant::Bytes(random_bytes(32)?));
+        let composite_key = composite_key(password);
+        self.transformed_key = transform_key(composite_key.as_ref(), &kdf)?;
+        self.composite_key = composite_key;
+        self.kdf = kdf;
+        Ok(())
+    }
+}
+
+fn is_protected(element: &Element) -> bool {
+    element.name == "Value" && element.attribute("Protected").is_some_and(|value| value.eq_ignore_ascii_case("true"))
+}
+
+/// Cipher hiding protected values inside the document. One stream runs
+/// through all of them, in document order.
+enum InnerStream {
+    ChaCha20(Box<ChaCha20>),
+    Salsa20(Box<Salsa20>),
+}
+
+impl InnerStream {
+    fn new(id: u32, key: &[u8]) -> Result<InnerStream> {
+        let invalid = |e: aes::cipher::InvalidLength| Error::Kdbx(e.to_string());
+        match id {
+            STREAM_CHACHA20 => {
+                let hash: Zeroizing<[u8; 64]> = Zeroizing::new(Sha512::digest(key).into());
+                Ok(InnerStream::ChaCha20(Box::new(ChaCha20::new_from_slices(&hash[..32], &hash[32..44]).map_err(invalid)?)))
+            }
+            STREAM_SALSA20 => {
+                let hash: Zeroizing<[u8; 32]> = Zeroizing::new(Sha256::digest(key).into());
+                Ok(InnerStream::Salsa20(Box::new(Salsa20::new_from_slices(hash.as_ref(), &SALSA20_NONCE).map_err(invalid)?)))
+            }
+            _ => Err(Error::Kdbx(format!("nieobsługiwany szyfr chronionych pól: {}", id))),
+        }
+    }
+
+    fn apply(&mut self, data: &mut [u8]) {
+        match self {
+            InnerStream::ChaCha20(cipher) => cipher.apply_keystream(data),
+            InnerStream::Salsa20(cipher) => cipher.apply_keystream(data),
+        }
+    }
+}
+
+fn decrypt(cipher: &[u8], key: &[u8], iv: &[u8], mut data: Vec<u8>) -> Result<Zeroizing<Vec<u8>>> {
+    let invalid = |e: aes::cipher::InvalidLength| Error::Kdbx(e.to_string());
+    if cipher == CIPHER_AES256 {
+        let decryptor = cbc::Decryptor::<Aes256>::new_from_slices(key, iv).map_err(invalid)?;
+        // Blocks were authenticated, bad padding means a broken writer
+        return decryptor.decrypt_padded_vec_mut::<Pkcs7>(&data).map(Zeroizing::new).map_err(|_| Error::Tampered);
+    }
+    if cipher == CIPHER_CHACHA20 {
+        ChaCha20::new_from_slices(key, iv).map_err(invalid)?.apply_keystream(&mut data);
+        return Ok(Zeroizing::new(data));
+    }
+    Err(Error::Kdbx(String::from("nieobsługiwany szyfr, obsługiwane są AES-256 i ChaCha20")))
+}
+
+fn encrypt(cipher: &[u8], key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let invalid = |e: aes::cipher::InvalidLength| Error::Kdbx(e.to_string());
+    if cipher == CIPHER_AES256 {
+        let encryptor = cbc::Encryptor::<Aes256>::new_from_slices(key, iv).map_err(invalid)?;
+        return Ok(encryptor.encrypt_padded_vec_mut::<Pkcs7>(data));
+    }
+    if cipher == CIPHER_CHACHA20 {
+        let mut data = data.to_vec();
+        ChaCha20::new_from_slices(key, iv).map_err(invalid)?.apply_keystream(&mut data);
+        return Ok(data);
+    }
+    Err(Error::Kdbx(String::from("nieobsługiwany szyfr, obsługiwane są AES-256 i ChaCha20")))
+}
+
+/// Runs the KDF from the header on the composite key
+fn transform_key(composite_key: &[u8], kdf: &Variants) -> Result<Zeroizing<[u8; 32]>> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    if kdf_uuid(kdf)? == KDF_AES {
+        let rounds = match variant(kdf, "R") {
+            Some(Variant::U64(rounds)) => *rounds,
+            _ => return Err(Error::Kdbx(String::from("brak liczby rund AES-KDF"))),
+        };
+        if rounds > MAX_AES_KDF_ROUNDS {
+            return Err(Error::Kdbx(format!("zbyt wiele rund AES-KDF: {} (najwyżej {})", rounds, MAX_AES_KDF_ROUNDS)));
+        }
+        let cipher = Aes256::new_from_slice(bytes_variant(kdf, "S")?).map_err(|e| Error::Kdbx(e.to_string()))?;
+        key.copy_from_slice(composite_key);
+        for _ in 0..rounds {
+            for block in key.chunks_exact_mut(16) {
+                cipher.encrypt_block(aes::Block::from_mut_slice(block));
+            }
+        }
+        let hash = Sha256::digest(key.as_ref());
+        key.copy_from_slice(&hash);
+        return Ok(key);
+    }
+
+    let params = argon2_params(kdf)?;
+    if params.memory_kib > MAX_ARGON2_MEMORY_KIB || params.iterations > MAX_ARGON2_ITERATIONS {
+        return Err(Error::Kdbx(format!("zbyt kosztowne parametry Argon2: {}", params)));
+    }
+    params.argon2()?.hash_password_into(composite_key, bytes_variant(kdf, "S")?, key.as_mut())
+        .map_err(|e| Error::Kdf(e.to_string()))?;
+    Ok(key)
+}
+
+fn argon2_params(kdf: &Variants) -> Result<KdfParams> {
+    let algorithm = match kdf_uuid(kdf)? {
+        KDF_ARGON2D => 0,
+        KDF_ARGON2ID => 2,
+        _ => return Err(Error::Kdbx(String::from("plik używa AES-KDF zamiast Argon2"))),
+    };
+    // Secret key and associated data aren't set by any known client
+    if variant(kdf, "K").is_some() || variant(kdf, "A").is_some() {
+        return Err(Error::Kdbx(String::from("nieobsługiwane parametry Argon2 (klucz albo dane powiązane)")));
+    }
+    let number = |key: &str| match variant(kdf, key) {
+        Some(Variant::U32(value)) => Some(*value),
+        Some(Variant::U64(value)) => u32::try_from(*value).ok(),
+        _ => None,
+    }.ok_or_else(|| Error::Kdbx(format!("brak parametru Argon2 {}", key)));
+
+    Ok(KdfParams {
+        algorithm,
+        version: number("V")?,
+        // In bytes in the file
+        memory_kib: match variant(kdf, "M") {
+            Some(Variant::U64(memory)) => u32::try_from(memory / 1024).map_err(|e| Error::Kdbx(e.to_string()))?,
+            _ => return Err(Error::Kdbx(String::from("brak parametru Argon2 M"))),
+        },
+        iterations: number("I")?,
+        parallelism: number("P")?,
+    })
+}
+
+fn kdf_uuid(kdf: &Variants) -> Result<[u8; 16]> {
+    bytes_variant(kdf, "$UUID")?.try_into().map_err(|_| Error::Kdbx(String::from("niepoprawny identyfikator KDF")))
+}
+
+fn variant<'a>(variants: &'a Variants, key: &str) -> Option<&'a Variant> {
+    variants.iter().find(|(name, _)| name == key).map(|(_, value)| value)
+}
+
+fn bytes_variant<'a>(variants: &'a Variants, key: &str) -> Result<&'a [u8]> {
+    match variant(variants, key) {
+        Some(Variant::Bytes(bytes)) => Ok(bytes),
+        _ => Err(Error::Kdbx(format!("brak parametru KDF {}", key))),
+    }
+}
+
+fn set_variant(variants: &mut Variants, key: &str, value: Variant) {
+    match variants.iter_mut().find(|(name, _)| name == key) {
+        Some((_, old)) => *old = value,
+        None => variants.push((key.to_string(), value)),
+    }
+}
+
+fn parse_variants(bytes: &[u8]) -> Result<Variants> {
+    let mut reader = Reader { bytes, pos: 0 };
+    // Major version 1, minor ones are compatible
+    if reader.u16()? >> 8 != 1 {
+        return Err(Error::Kdbx(String::from("nieobsługiwana wersja parametrów KDF")));
+    }
+
+    let mut variants = Vec::new();
+    loop {
+        let kind = reader.u8()?;
+        if kind == 0 {
+            return Ok(variants);
+        }
+        let name_length = reader.u32()? as usize;
+        let name = String::from_utf8_lossy(reader.take(name_length)?).into_owned();
+        let length = reader.u32()? as usize;
+        let value = reader.take(length)?;
+        let mut value_reader = Reader { bytes: value, pos: 0 };
+        let value = match kind {
+            0x04 => Variant::U32(value_reader.u32()?),
+            0x05 => Variant::U64(value_reader.u64()?),
+            0x08 => Variant::Bool(value_reader.u8()? != 0),
+            0x0C => Variant::I32(value_reader.u32()? as i32),
+            0x0D => Variant::I64(value_reader.u64()? as i64),
+            0x18 => Variant::String(String::from_utf8_lossy(value).into_owned()),
+            0x42 => Variant::Bytes(value.to_vec()),
+            _ => return Err(Error::Kdbx(format!("nieznany typ parametru KDF: {}", kind))),
+        };
+        variants.push((name, value));
+    }
+}
+
+fn encode_variants(variants: &Variants) -> Vec<u8> {
+    let mut bytes = 0x0100u16.to_le_bytes().to_vec();
+    for (name, value) in variants {
+        let (kind, data) = match value {
+            Variant::U32(value) => (0x04, value.to_le_bytes().to_vec()),
+            Variant::U64(value) => (0x05, value.to_le_bytes().to_vec()),
+            Variant::Bool(value) => (0x08, vec![*value as u8]),
+            Variant::I32(value) => (0x0C, value.to_le_bytes().to_vec()),
+            Variant::I64(value) => (0x0D, value.to_le_bytes().to_vec()),
+            Variant::String(value) => (0x18, value.as_bytes().to_vec()),
+            Variant::Bytes(value) => (0x42, value.clone()),
+        };
+        bytes.push(kind);
+        bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&data);
+    }
+    bytes.push(0);
+    bytes
+}
+
+fn push_field(bytes: &mut Vec<u8>, id: u8, value: &[u8]) {
+    bytes.push(id);
+    bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(value);
+}
+
+fn payload_key(seed: &[u8], transformed_key: &[u8]) -> Zeroizing<[u8; 32]> {
+    let mut hash = Sha256::new();
+    hash.update(seed);
+    hash.update(transformed_key);
+    Zeroizing::new(hash.finalize().into())
+}
+
+fn hmac_key(seed: &[u8], transformed_key: &[u8]) -> Zeroizing<[u8; 64]> {
+    let mut hash = Sha512::new();
+    hash.update(seed);
+    hash.update(transformed_key);
+    hash.update([1]);
+    Zeroizing::new(hash.finalize().into())
+}
+
+/// HMAC of a block with a key specific to its index. Header uses index u64::MAX,
+/// blocks are authenticated together with their index and length.
+/// `verify_slice` of the result compares in constant time.
+fn block_hmac(hmac_key: &[u8; 64], index: u64, parts: &[&[u8]]) -> Hmac<Sha256> {
+    let mut key = Sha512::new();
+    key.update(index.to_le_bytes());
+    key.update(hmac_key);
+    let key: Zeroizing<[u8; 64]> = Zeroizing::new(key.finalize().into());
+
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key.as_ref()).expect("HMAC accepts keys of any length");
+    if index != HEADER_BLOCK_INDEX {
+        mac.update(&index.to_le_bytes());
+    }
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+fn random_bytes(len: usize) -> Result<Vec<u8>> {
+    let mut bytes = vec![0u8; len];
+    OsRng.try_fill_bytes(&mut bytes).map_err(|e| Error::Crypto(e.to_string()))?;
+    Ok(bytes)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos.saturating_add(len))
+            .ok_or_else(|| Error::Kdbx(String::from("plik jest za krótki")))?;
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
@@ -0,0 +1,530 @@
+use std::collections::{HashMap, HashSet};
+use chrono::DateTime;
+use data_encoding::BASE64;
+use rand::{rngs::OsRng, TryRngCore};
+use zeroize::Zeroizing;
+use crate::error::{Error, Result};
+use crate::kdbx::Kdbx;
+use crate::password_storage::*;
+use crate::xml::{Element, Node};
+
+// KDBX 4 stores times as seconds since 0001-01-01
+const UNIX_EPOCH_OFFSET: i64 = 62_135_596_800;
+// Given to entries without a title
+const UNTITLED: &str = "bez-tytułu";
+// Name of the recycle bin group when passman makes one, the same KeePass gives it
+const RECYCLE_BIN: &str = "Recycle Bin";
+
+/// KeePass database behind a vault. Entries are taken out of the document when
+/// it is opened and put back into it when it is saved, so everything passman
+/// doesn't know about (history, attachments, custom fields, icons) stays as it was.
+/// The recycle bin is the trash of the vault.
+#[derive(Clone)]
+pub struct KeePass {
+    file: Kdbx,
+    // Decrypted document, encrypted again with the vault's key while it's in memory
+    document: EncryptedField,
+    // UUIDs of the entries from the document by their current labels
+    ids: HashMap<String, String>,
+    // Labels the entries got when the document was opened or last saved, by UUID
+    saved_labels: HashMap<String, String>,
+    // UUIDs of the entries in the recycle bin when the document was opened or last saved
+    saved_trash: HashSet<String>,
+}
+
+impl KeePass {
+    /// Decrypts the database and returns it with its entries and the entries
+    /// of its recycle bin, encrypted with master_key
+    pub fn open(data: &[u8], composite_key: Zeroizing<[u8; 32]>, master_key: &[u8]) -> Result<(KeePass, Entries, Trash)> {
+        let (file, document) = Kdbx::open(data, composite_key)?;
+        if document.name != "KeePassFile" {
+            return Err(Error::Kdbx(String::from("dokument nie jest bazą KeePassa")));
+        }
+
+        let recycle_bin = recycle_bin(&document);
+        let top = top_group(&document)?;
+        let mut found = HashMap::new();
+        read_group(top, &mut Vec::new(), recycle_bin.as_deref(), &mut found);
+        let mut entries = Entries::new();
+        let mut ids = HashMap::new();
+        for (label, element) in found {
+            entries.insert(label.clone(), read_entry(element, master_key)?);
+            ids.insert(label, element.child_text("UUID").unwrap_or_default());
+        }
+
+        // Entries were removed when they were moved to the recycle bin
+        let mut trash = Trash::new();
+        if let Some(bin) = recycle_bin.as_deref().and_then(|id| find_group(top, id)) {
+            let mut found = HashMap::new();
+            read_group(bin, &mut Vec::new(), None, &mut found);
+            for (label, element) in found {
+                let deleted = time(element, "LocationChanged").unwrap_or(0);
+                trash.push(TrashedEntry::new(&label, deleted, read_entry(element, master_key)?, element.child_text("UUID")));
+            }
+            trash.sort_by_key(|trashed| trashed.deleted);
+        }
+
+        let saved_labels = ids.iter().map(|(label, id)| (id.clone(), label.clone())).collect();
+        let saved_trash = trash.iter().filter_map(|trashed| trashed.id.clone()).collect();
+        let document = encrypt_field(&Zeroizing::new(document.to_document()), master_key)?;
+        Ok((KeePass { file, document, ids, saved_labels, saved_trash }, entries, trash))
+    }
+
+    /// The database file, for its key and KDF
+    pub fn file(&self) -> &Kdbx {
+        &self.file
+    }
+
+    pub fn file_mut(&mut self) -> &mut Kdbx {
+        &mut self.file
+    }
+
+    /// Entries were renamed in the vault, they are still the same entries in the database.
+    /// Pairs are old and new labels, a new label can be the old one of another entry.
+    pub fn renamed(&mut self, renames: &[(String, String)]) {
+        let ids: Vec<_> = renames.iter().map(|(label, new_label)| (self.ids.remove(label), new_label)).collect();
+        for (id, new_label) in ids {
+            if let Some(id) = id {
+                self.ids.insert(new_label.clone(), id);
+            }
+        }
+    }
+
+    /// Entry was moved to the trash, it will go to the recycle bin on save.
+    /// Returns its UUID, None if it isn't in the database yet.
+    pub fn removed(&mut self, label: &str) -> Option<String> {
+        self.ids.remove(label)
+    }
+
+    /// Entry was taken out of the trash, it will leave the recycle bin on save
+    pub fn restored(&mut self, label: &str, id: Option<String>) {
+        if let Some(id) = id {
+            self.ids.insert(label.to_string(), id);
+        }
+    }
+
+    /// Puts the entries into the document and returns contents of the database file.
+    /// Entries of the trash are kept in the recycle bin, the ones purged from it are deleted.
+    pub fn seal(&mut self, entries: &Entries, trash: &Trash, master_key: &[u8]) -> Result<Vec<u8>> {
+        let mut document = Element::parse(&Zeroizing::new(decrypt_field(&self.document, master_key)?))?;
+        let trashed: HashMap<String, u64> = trash.iter()
+            .filter_map(|trashed| Some((trashed.id.clone()?, trashed.deleted)))
+            .collect();
+        let mut recycle_bin = recycle_bin(&document);
+        if recycle_bin.is_none() && trashed.keys().any(|id| !self.saved_trash.contains(id)) {
+            let id = new_uuid()?;
+            let meta = document.child_or_insert("Meta");
+            meta.child_or_insert("RecycleBinEnabled").set_text("True");
+            meta.child_or_insert("RecycleBinUUID").set_text(&id);
+            meta.child_or_insert("RecycleBinChanged").set_text(&encode_time(now()));
+            recycle_bin = Some(id);
+        }
+        // -1 means no limit
+        let history_max_items = document.child("Meta").and_then(|meta| meta.child_text("HistoryMaxItems"))
+            .and_then(|max| max.trim().parse::<usize>().ok());
+
+        let root = document.child_mut("Root").ok_or_else(|| Error::Kdbx(String::from("brak elementu Root")))?;
+        let top = root.child_mut("Group").ok_or_else(|| Error::Kdbx(String::from("brak głównej grupy")))?;
+        let mut merge = Merge {
+            entries,
+            labels: self.ids.iter().map(|(label, id)| (id.clone(), label.clone())).collect(),
+            saved_labels: &self.saved_labels,
+            trashed: &trashed,
+            saved_trash: &self.saved_trash,
+            recycle_bin,
+            history_max_items,
+            master_key,
+            moved: Vec::new(),
+            to_bin: Vec::new(),
+            deleted: Vec::new(),
+        };
+        merge.update_group(top, false)?;
+
+        // Entries which changed label go to the group of the new one, new entries are created there
+        let mut placed: Vec<(String, Element)> = std::mem::take(&mut merge.moved);
+        let new_labels: Vec<&String> = entries.keys().filter(|label| !self.ids.contains_key(*label)).collect();
+        for label in new_labels {
+            let id = new_uuid()?;
+            self.ids.insert(label.clone(), id.clone());
+            placed.push((label.clone(), new_entry(&id)));
+        }
+        for (label, mut element) in placed {
+            let (path, title) = split_label(&label);
+            update_entry(&mut element, &entries[&label], Some(title), history_max_items, master_key)?;
+            group_at(top, &path)?.push(element);
+        }
+
+        let to_bin = std::mem::take(&mut merge.to_bin);
+        if !to_bin.is_empty() {
+            let id = merge.recycle_bin.clone().ok_or_else(|| Error::Kdbx(String::from("brak kosza")))?;
+            if find_group(top, &id).is_none() {
+                top.push(new_group(&id, RECYCLE_BIN));
+            }
+            let bin = find_group_mut(top, &id).expect("recycle bin was just added");
+            for (mut element, deleted) in to_bin {
+                element.child_or_insert("Times").child_or_insert("LocationChanged").set_text(&encode_time(deleted));
+                bin.push(element);
+            }
+        }
+
+        if !merge.deleted.is_empty() {
+            let deleted = std::mem::take(&mut merge.deleted);
+            let objects = root.child_or_insert("DeletedObjects");
+            for id in deleted {
+                let mut object = Element::new("DeletedObject");
+                object.push(Element::with_text("UUID", &id));
+                object.push(Element::with_text("DeletionTime", &encode_time(now())));
+                objects.push(object);
+            }
+        }
+
+        let data = self.file.seal(&document)?;
+        self.document = encrypt_field(&Zeroizing::new(document.to_document()), master_key)?;
+        self.saved_labels = self.ids.iter().map(|(label, id)| (id.clone(), label.clone())).collect();
+        self.saved_trash = trashed.into_keys().collect();
+        Ok(data)
+    }
+}
+
+/// State of putting entries back into the document
+struct Merge<'a> {
+    entries: &'a Entries,
+    // Current labels by UUID
+    labels: HashMap<String, String>,
+    saved_labels: &'a HashMap<String, String>,
+    // Times of removal of the entries in the trash, by UUID
+    trashed: &'a HashMap<String, u64>,
+    saved_trash: &'a HashSet<String>,
+    recycle_bin: Option<String>,
+    history_max_items: Option<usize>,
+    master_key: &'a [u8],
+    // Elements of renamed entries with their new labels, taken out of their groups
+    moved: Vec<(String, Element)>,
+    // Elements of entries moved to the trash since the last save, with their times of removal
+    to_bin: Vec<(Element, u64)>,
+    // UUIDs of entries purged from the trash or removed before they were saved
+    deleted: Vec<String>,
+}
+
+impl Merge<'_> {
+    fn update_group(&mut self, group: &mut Element, in_bin: bool) -> Result<()> {
+        let mut kept = Vec::new();
+        for child in std::mem::take(&mut group.children) {
+            let Node::Element(mut element) = child else {
+                kept.push(child);
+                continue;
+            };
+            if element.name == "Group" {
+                let in_bin = in_bin || (self.recycle_bin.is_some() && element.child_text("UUID") == self.recycle_bin);
+                self.update_group(&mut element, in_bin)?;
+            }
+            if element.name != "Entry" {
+                kept.push(Node::Element(element));
+                continue;
+            }
+
+            let id = element.child_text("UUID").unwrap_or_default();
+            match self.labels.get(&id) {
+                // Same label, same place - only the fields change
+                Some(label) if !in_bin && self.saved_labels.get(&id) == Some(label) => {
+                    update_entry(&mut element, &self.entries[label], None, self.history_max_items, self.master_key)?;
+                    kept.push(Node::Element(element));
+                }
+                // Renamed or restored from the trash
+                Some(label) => self.moved.push((label.clone(), element)),
+                None => match self.trashed.get(&id) {
+                    Some(_) if in_bin => kept.push(Node::Element(element)),
+                    Some(deleted) => self.to_bin.push((element, *deleted)),
+                    None if self.saved_labels.contains_key(&id) || self.saved_trash.contains(&id) => self.deleted.push(id),
+                    // Never loaded, e.g. a second entry with the same UUID
+                    None => kept.push(Node::Element(element)),
+                },
+            }
+        }
+        group.children = kept;
+        Ok(())
+    }
+}
+
+fn top_group(document: &Element) -> Result<&Element> {
+    document.child("Root").and_then(|root| root.child("Group"))
+        .ok_or_else(|| Error::Kdbx(String::from("brak głównej grupy")))
+}
+
+/// UUID of the group deleted entries are moved to. KeePass writes zeros when there is none.
+fn recycle_bin(document: &Element) -> Option<String> {
+    document.child("Meta").and_then(|meta| meta.child_text("RecycleBinUUID"))
+        .filter(|id| BASE64.decode(id.trim().as_bytes()).is_ok_and(|id| id.iter().any(|&byte| byte != 0)))
+}
+
+fn find_group<'a>(group: &'a Element, id: &str) -> Option<&'a Element> {
+    if group.child_text("UUID").as_deref() == Some(id) {
+        return Some(group);
+    }
+    group.elements().filter(|child| child.name == "Group").find_map(|child| find_group(child, id))
+}
+
+fn find_group_mut<'a>(group: &'a mut Element, id: &str) -> Option<&'a mut Element> {
+    if group.child_text("UUID").as_deref() == Some(id) {
+        return Some(group);
+    }
+    group.elements_mut().filter(|child| child.name == "Group").find_map(|child| find_group_mut(child, id))
+}
+
+/// Finds entries of the group and its subgroups, except for the recycle bin. Labels are
+/// paths of groups (without the group itself - the top one is the database) and the entry's title.
+fn read_group<'a>(group: &'a Element, path: &mut Vec<String>, recycle_bin: Option<&str>,
+                  found: &mut HashMap<String, &'a Element>) {
+    for element in group.elements() {
+        if element.name == "Group" {
+            if recycle_bin.is_some() && element.child_text("UUID").as_deref() == recycle_bin {
+                continue;
+            }
+            path.push(element.child_text("Name").unwrap_or_default());
+            read_group(element, path, recycle_bin, found);
+            path.pop();
+            continue;
+        }
+        if element.name != "Entry" {
+            continue;
+        }
+
+        let title = string(element, "Title").filter(|title| !title.is_empty()).unwrap_or_else(|| UNTITLED.to_string());
+        let base = path.iter().map(String::as_str).chain([title.as_str()]).collect::<Vec<_>>().join("/");
+        // Titles don't have to be unique in KeePass
+        let mut label = base.clone();
+        let mut n = 2;
+        while found.contains_key(&label) {
+            label = format!("{}-{}", base, n);
+            n += 1;
+        }
+        found.insert(label, element);
+    }
+}
+
+fn read_entry(element: &Element, master_key: &[u8]) -> Result<Entry> {
+    let mut entry = Entry::new(&Zeroizing::new(string(element, "Password").unwrap_or_default()), master_key)?;
+    entry.username = string(element, "UserName").filter(|username| !username.is_empty());
+    entry.urls = strings(element).into_iter()
+        .filter(|(key, value)| (key == "URL" || key.starts_with("KP2A_URL")) && !value.is_empty())
+        .map(|(_, value)| value)
+        .collect();
+    entry.set_notes(string(element, "Notes").filter(|notes| !notes.is_empty()).as_deref(), master_key)?;
+    let totp = string(element, "otp").or_else(|| string(element, "TimeOtp-Secret-Base32")).filter(|totp| !totp.is_empty());
+    entry.set_totp(totp.as_deref(), master_key)?;
+    entry.tags = element.child_text("Tags").unwrap_or_default()
+        .split([';', ','])
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+
+    entry.created = time(element, "CreationTime").unwrap_or(0);
+    entry.modified = time(element, "LastModificationTime").unwrap_or(entry.created);
+    entry.last_used = time(element, "LastAccessTime");
+
+    // Older versions of the entry, oldest first. Only those with another password than
+    // the next version count, each was replaced when the next one was made.
+    let versions: Vec<(Zeroizing<String>, u64)> = element.child("History").into_iter()
+        .flat_map(Element::elements)
+        .filter(|version| version.name == "Entry")
+        .map(|version| (Zeroizing::new(string(version, "Password").unwrap_or_default()), time(version, "LastModificationTime").unwrap_or(0)))
+        .chain([(Zeroizing::new(entry.password(master_key)?), entry.modified)])
+        .collect();
+    let mut history = Vec::new();
+    for pair in versions.windows(2) {
+        let [(password, _), (next_password, replaced)] = pair else { continue };
+        if password != next_password {
+            history.push(HistoryItem::new(password, *replaced, master_key)?);
+        }
+    }
+    entry.set_history(history);
+    Ok(entry)
+}
+
+/// Time of the entry from its Times element
+fn time(element: &Element, name: &str) -> Option<u64> {
+    element.child("Times").and_then(|times| times.child_text(name)).and_then(|time| decode_time(&time))
+}
+
+/// Writes fields of the entry into its element. Title is set only for
+/// entries which are new or were renamed, the rest of the label is the group.
+fn update_entry(element: &mut Element, entry: &Entry, title: Option<&str>, history_max_items: Option<usize>,
+                master_key: &[u8]) -> Result<()> {
+    let password = Zeroizing::new(entry.password(master_key)?);
+    if string(element, "Password").is_some_and(|current| current != *password) {
+        push_history(element, history_max_items);
+    }
+
+    if let Some(title) = title {
+        set_string(element, "Title", title, false);
+    }
+    set_string(element, "UserName", entry.username.as_deref().unwrap_or_default(), false);
+    set_string(element, "Password", &password, true);
+    set_string(element, "URL", entry.urls.first().map(String::as_str).unwrap_or_default(), false);
+
+    // More URLs are kept the way KeePassXC and Keepass2Android do it
+    element.retain(|child| !string_key(child).is_some_and(|key| key.starts_with("KP2A_URL")));
+    for (i, url) in entry.urls.iter().skip(1).enumerate() {
+        let key = if i == 0 { String::from("KP2A_URL") } else { format!("KP2A_URL_{}", i) };
+        set_string(element, &key, url, false);
+    }
+
+    set_string(element, "Notes", &Zeroizing::new(entry.notes(master_key)?.unwrap_or_default()), false);
+
+    // Kept in the format it came in, unless it changed
+    let totp = entry.totp(master_key)?.map(Zeroizing::new);
+    let current = string(element, "otp").or_else(|| string(element, "TimeOtp-Secret-Base32")).map(Zeroizing::new);
+    if totp != current {
+        element.retain(|child| !string_key(child).is_some_and(|key| key == "otp" || key.starts_with("TimeOtp-")));
+        if let Some(totp) = totp {
+            set_string(element, "otp", &totp, true);
+        }
+    }
+
+    if !entry.tags.is_empty() || element.child("Tags").is_some() {
+        element.child_or_insert("Tags").set_text(&entry.tags.join(";"));
+    }
+
+    let times = element.child_or_insert("Times");
+    times.child_or_insert("CreationTime").set_text(&encode_time(entry.created));
+    times.child_or_insert("LastModificationTime").set_text(&encode_time(entry.modified));
+    if let Some(last_used) = entry.last_used {
+        times.child_or_insert("LastAccessTime").set_text(&encode_time(last_used));
+    }
+    Ok(())
+}
+
+/// Keeps a copy of the entry in its history before the password changes, like KeePass does.
+/// Oldest versions over the limit of the database are dropped.
+fn push_history(element: &mut Element, max_items: Option<usize>) {
+    let mut version = element.clone();
+    version.retain(|child| child.name != "History");
+
+    let history = element.child_or_insert("History");
+    history.push(version);
+    if let Some(max_items) = max_items {
+        let mut excess = history.elements().count().saturating_sub(max_items);
+        history.retain(|_| if excess > 0 { excess -= 1; false } else { true });
+    }
+}
+
+/// Empty entry element in the layout KeePass writes
+fn new_entry(id: &str) -> Element {
+    let mut entry = Element::new("Entry");
+    entry.push(Element::with_text("UUID", id));
+    entry.push(Element::with_text("IconID", "0"));
+    entry.push(new_times());
+    let mut auto_type = Element::new("AutoType");
+    auto_type.push(Element::with_text("Enabled", "True"));
+    auto_type.push(Element::with_text("DataTransferObfuscation", "0"));
+    entry.push(auto_type);
+    entry.push(Element::new("History"));
+    entry
+}
+
+fn new_group(id: &str, name: &str) -> Element {
+    let mut group = Element::new("Group");
+    group.push(Element::with_text("UUID", id));
+    group.push(Element::with_text("Name", name));
+    group.push(Element::with_text("IconID", "48"));
+    group.push(new_times());
+    group.push(Element::with_text("IsExpanded", "True"));
+    group
+}
+
+fn new_times() -> Element {
+    let now = encode_time(now());
+    let mut times = Element::new("Times");
+    for name in ["CreationTime", "LastModificationTime", "LastAccessTime", "ExpiryTime", "LocationChanged"] {
+        times.push(Element::with_text(name, &now));
+    }
+    times.push(Element::with_text("Expires", "False"));
+    times.push(Element::with_text("UsageCount", "0"));
+    times
+}
+
+/// Group at path under the top group, missing groups are created
+fn group_at<'a>(top: &'a mut Element, path: &[&str]) -> Result<&'a mut Element> {
+    let mut group = top;
+    for name in path {
+        let is_named = |element: &Element| element.name == "Group" && element.child_text("Name").as_deref() == Some(name);
+        if !group.elements().any(is_named) {
+            group.push(new_group(&new_uuid()?, name));
+        }
+        group = group.elements_mut().find(|element| is_named(element)).expect("group was just added");
+    }
+    Ok(group)
+}
+
+/// Splits a label into the path of its group and the title
+fn split_label(label: &str) -> (Vec<&str>, &str) {
+    let mut path: Vec<&str> = label.split('/').filter(|part| !part.is_empty()).collect();
+    let title = path.pop().unwrap_or(label);
+    (path, title)
+}
+
+fn string_key(element: &Element) -> Option<String> {
+    if element.name == "String" { element.child_text("Key") } else { None }
+}
+
+/// Key-value pairs of the entry, in document order
+fn strings(element: &Element) -> Vec<(String, String)> {
+    element.elements()
+        .filter_map(|child| Some((string_key(child)?, child.child_text("Value").unwrap_or_default())))
+        .collect()
+}
+
+fn string(element: &Element, key: &str) -> Option<String> {
+    element.elements()
+        .find(|child| string_key(child).as_deref() == Some(key))
+        .map(|child| child.child_text("Value").unwrap_or_default())
+}
+
+/// Sets a string of the entry. New ones go after the other strings,
+/// before attachments, auto-type settings and history.
+fn set_string(element: &mut Element, key: &str, value: &str, protected: bool) {
+    if let Some(string) = element.elements_mut().find(|child| string_key(child).as_deref() == Some(key)) {
+        let value_element = string.child_or_insert("Value");
+        value_element.set_text(value);
+        if protected {
+            value_element.set_attribute("Protected", "True");
+        }
+        return;
+    }
+
+    let mut string = Element::new("String");
+    string.push(Element::with_text("Key", key));
+    let mut value_element = Element::with_text("Value", value);
+    if protected {
+        value_element.set_attribute("Protected", "True");
+    }
+    string.push(value_element);
+
+    let is_element = |child: &Node, names: &[&str]| matches!(child, Node::Element(e) if names.contains(&e.name.as_str()));
+    let position = match element.children.iter().rposition(|child| is_element(child, &["String"])) {
+        Some(last) => last + 1,
+        None => element.children.iter()
+            .position(|child| is_element(child, &["Binary", "AutoType", "History"]))
+            .unwrap_or(element.children.len()),
+    };
+    element.children.insert(position, Node::Element(string));
+}
+
+fn new_uuid() -> Result<String> {
+    let mut id = [0u8; 16];
+    OsRng.try_fill_bytes(&mut id).map_err(|e| Error::Crypto(e.to_string()))?;
+    Ok(BASE64.encode(&id))
+}
+
+fn encode_time(time: u64) -> String {
+    BASE64.encode(&(time as i64 + UNIX_EPOCH_OFFSET).to_le_bytes())
+}
+
+/// Reads a time in the KDBX 4 format, or ISO 8601 used by older versions
+fn decode_time(text: &str) -> Option<u64> {
+    let seconds = match BASE64.decode(text.trim().as_bytes()) {
+        Ok(bytes) => i64::from_le_bytes(bytes.try_into().ok()?) - UNIX_EPOCH_OFFSET,
+        Err(_) => DateTime::parse_from_rfc3339(text.trim()).ok()?.timestamp(),
+    };
+    u64::try_from(seconds).ok()
+}
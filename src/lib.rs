@@ -0,0 +1,42 @@
+//! Encrypted password vault used by the passman command line tool.
+//!
+//! [`Vault`] is the whole public interface: it opens or creates the vault file,
+//! holds the master key for as long as it is unlocked and writes every change
+//! atomically, keeping backups of previous versions.
+
+mod atomic;
+mod audit;
+mod auth;
+mod backup;
+mod breach;
+mod container;
+mod error;
+mod export;
+mod folders;
+mod generator;
+mod import;
+mod kdbx;
+mod keepass;
+mod passphrase;
+mod password_storage;
+mod search;
+mod strength;
+mod totp;
+mod vault;
+mod xml;
+
+pub use audit::{audit, Audit, AuditOptions, BreachedEntry, OldEntry, WeakEntry};
+pub use auth::{calibrate_kdf, measure_kdf, KdfParams};
+pub use backup::{Backup, MAX_BACKUPS};
+pub use breach::BreachedPasswords;
+pub use error::{Error, Result};
+pub use export::{export_plaintext, ExportFormat};
+pub use folders::{base_name, join_label, move_target, relative_label, Folder, SEPARATOR};
+pub use generator::{Generator, Recipe, DEFAULT_PASSWORD_LEN};
+pub use import::{read_import, ColumnMapping, Import, ImportFormat, ImportedEntry};
+pub use passphrase::{Capitalization, Passphrase};
+pub use password_storage::{Entry, HistoryItem, TrashedEntry, DEFAULT_HISTORY_DEPTH};
+pub use search::{fuzzy_score, Found, MatchedField, SearchIndex};
+pub use strength::{estimate_strength, Strength, GUESSES_PER_SECOND};
+pub use totp::{Totp, TotpAlgorithm};
+pub use vault::{default_vault_path, Vault};
@@ -1,349 +1,170 @@
-mod auth;
-mod password_storage;
+mod args;
+mod clipboard;
+mod commands;
 mod cli;
-
-use std::io::{self, Write};
-use clipboard::{ClipboardProvider, ClipboardContext};
-use reedline::Signal;
+mod config;
+mod idle;
+mod picker;
+mod repl;
+mod reveal;
+
+use std::io::{self, BufReader, Write};
+use std::process::ExitCode;
+use clap::Parser;
+use std::path::Path;
+use passman::{Error, Result, Vault};
 use rpassword::read_password;
-use zeroize::Zeroize;
-use passwords::PasswordGenerator;
-use crate::auth::*;
-use crate::cli::MyPrompt;
-use crate::password_storage::*;
-
-static DEFAULT_PASSWORD_LEN: usize = 32;
-
-fn main() {
-    // Check if master password is set
-    let mut master_key = if !master_password_exists() {
-        // Set new master password
-        println!("Nie znaleziono profilu.");
-        let password: String;
-        loop{
-            let opt_password = password_input();
+use crate::args::{Args, CliCommand, Command};
+use crate::commands::*;
+use crate::config::Config;
+
+/// Where the master password is read from when unlocking
+enum PasswordSource {
+    Terminal,
+    Fd(i32),
+    Env(String),
+}
 
-            match opt_password{
-                Some(pass) => {
-                    password = pass;
-                    break;
-                }            
-                _ => {println!("Powtórzone hasło musi być identyczne jak pierwsze!");}
-            }
-        }
+fn main() -> ExitCode {
+    let args = Args::parse();
 
-        let salt = generate_and_store_salt();
-        let master_key = derive_master_key(&password, &salt);
-        create_verification_token(&master_key);
-        println!("Hasło ustawione.");
+    if let Some(seconds) = args.clear_clipboard_after {
+        return clipboard::run_helper(seconds);
+    }
 
-        master_key
+    let source = if let Some(fd) = args.password_fd {
+        PasswordSource::Fd(fd)
+    } else if let Some(var) = args.password_env {
+        PasswordSource::Env(var)
     } else {
-        // Load salt
-        let salt = load_salt().expect("Salt file missing!");
-
-        print!("Wprowadź hasło: ");
-        io::stdout().flush().unwrap();
-        let password = read_password().unwrap();
-
-        let candidate_key = derive_master_key(&password, &salt);
-
-        if !verify_master_key(&candidate_key) {
-            println!("Niepoprawne hasło!");
-            return;
-        }
-
-        candidate_key
+        PasswordSource::Terminal
     };
 
-    // Storage with our encrypted passwords
-    let mut vault = load_vault();
-
-    // Prepare for REPL
-    let commands = vec![
-        String::from("new"),
-        String::from("add"),
-        String::from("remove"),
-        String::from("get"),
-        String::from("change-password"),
-        String::from("help"),
-        String::from("exit")
-        ];
-
-    // Set prompt
-    let prompt = MyPrompt;
-
-    let mut line_editor = cli::bulid_line_editor(vault.keys().cloned().collect(), commands.clone());
-
-    // System's clipboard to copy passwords
-    let mut clipboard: ClipboardContext = ClipboardProvider::new().expect("Cannot access system's clipboard");
-
-    // Show all available commands
-    print_help();
-
-    loop {
-        let sig = line_editor.read_line(&prompt);
-
-        match sig {
-            Ok(Signal::Success(input)) => {
-                let parts: Vec<&str> = input.trim().split_whitespace().collect();
-                if parts.is_empty() {
-                    continue;
-                }
-
-                // Match command
-                match parts[0] {
-                    "new" => {
-                        if parts.len() != 2 && parts.len() != 3 {
-                            println!("Użycie: new <nazwa> lub new <nazwa> <długość>");
-                            continue;
-                        }
-                        
-                        // Check if this label isn't already used
-                        let label = parts[1];
-                        if vault.contains_key(label){
-                            println!("Już istnieje hasło z tą etykietą!");
-                            continue;
-                        }
-
-                        let len: usize;
-                        if parts.len() == 3{
-                            if let Ok(input) = parts[2].parse::<usize>(){
-                                len = input;
-                            } else{
-                                println!("Długość hasła musi być dodatnią liczbą całkowitą!");
-                                continue;
-                            };
-                            if len == 0{
-                                println!("Długość hasła musi być dodatnią liczbą całkowitą!");
-                                continue; 
-                            }
-
-                        } else{
-                            len = DEFAULT_PASSWORD_LEN;
-                        }
-
-                        // Password generator
-                        let password_generator = PasswordGenerator::new()
-                            .length(len)
-                            .lowercase_letters(true)
-                            .uppercase_letters(true)
-                            .symbols(true)
-                            .strict(true);
+    let vault = Config::load().and_then(|config| {
+        let (name, path) = config.resolve(args.vault, args.profile.as_deref())?;
+        Ok((config, name, path))
+    });
+    let (config, name, path) = match vault {
+        Ok(vault) => vault,
+        Err(error) => return report(error),
+    };
 
-                        // Generate random password and save it
-                        let password: String;
-                        match password_generator.generate_one() {
-                            Ok(p) => {password = p}
-                            Err(error) => {
-                                println!("{}", error);
-                                continue;
-                            }
-                        }
+    match args.command {
+        // No subcommand - interactive mode
+        None => repl::run(source, config, name, path, args.read_only),
+        Some(CliCommand::Init { allow_weak }) => match init(&source, &config, &path, allow_weak) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => report(error),
+        },
+        Some(CliCommand::Command(command)) => {
+            let read_only = args.read_only || config.is_read_only(&path);
+            run_once(*command, source, &config, name, &path, read_only)
+        }
+    }
+}
 
-                        add_and_save_password(&mut vault, label, &password, &master_key);
-                        println!("Hasło {} pomyślnie zapisane", label);
+/// Creates an empty vault at path, so scripts can set one up without the REPL
+fn init(source: &PasswordSource, config: &Config, path: &Path, allow_weak: bool) -> Result<()> {
+    if Vault::exists(path) {
+        return Err(Error::Io(io::Error::new(io::ErrorKind::AlreadyExists, format!("sejf {} już istnieje", path.display()))));
+    }
 
-                        // Rebuild line editor to update completions (but it clears history)
-                        line_editor = cli::bulid_line_editor(vault.keys().cloned().collect(), commands.clone());
-                        
-                    }
-                    "add" => {
-                        if parts.len() != 2 {
-                            println!("Użycie: add <nazwa>");
-                            continue;
-                        }
-                        
-                        // Check if this label isn't already used
-                        let label = parts[1];
-                        if vault.contains_key(label){
-                            println!("Już istnieje hasło z tą etykietą!");
-                            continue;
-                        }
+    let interactive = matches!(source, PasswordSource::Terminal);
+    let password = if interactive {
+        // Typed twice, as there is nothing to check it against yet
+        password_input()?.ok_or(Error::PasswordMismatch)?
+    } else {
+        read_master_password(source)?
+    };
+    check_strength(&password, &[], &config.strength, allow_weak, interactive)?;
 
-                        let opt_password = password_input();
-                        match opt_password{
-                            Some(password) => {
-                                add_and_save_password(&mut vault, label, &password, &master_key);
-                                println!("Dodano hasło {}", label);
-                            }
-                            
-                            _ => {println!("Powtórzone hasło musi być identyczne jak pierwsze!");}
-                        }
+    let vault = Vault::create(path, &password)?;
+    println!("Utworzono sejf {}", vault.path().display());
+    Ok(())
+}
 
-                    }
-                    "remove" => {
-                        if parts.len() != 2 {
-                            println!("Użycie: remove <nazwa>");
-                            continue;
-                        }
+/// Non-interactive mode - unlock, run a single command and report result with exit code
+fn run_once(command: Command, source: PasswordSource, config: &Config, name: String, path: &Path, read_only: bool) -> ExitCode {
+    // Generating doesn't need the vault, so it isn't unlocked
+    if let Command::Generate { length, generator, passphrase } = command {
+        return match generate(&config.generators, length, generator, passphrase) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => report(error),
+        };
+    }
 
-                        // Check if there is such password to remove
-                        let label = parts[1];
-                        if !vault.contains_key(label){
-                            println!("Nie ma zapisanego hasła z taką etykietą");
-                            continue;
-                        }
+    let mut session = match unlock(&source, path, read_only) {
+        Ok(vault) => Session::new(name, vault),
+        Err(error) => return report(error),
+    };
+    session.configure(config);
 
-                        // Ask for confirmation, 'T' confirms, anthing else cancels
-                        print!("Czy na pewno chcesz usunąć hasło {}? Tej akcji nie można odwrócić. T/[N] ", label);
-                        
-                        io::stdout().flush().unwrap();
-                        let mut input = String::new();
-                        match io::stdin().read_line(&mut input) {
-                            Ok(_) => {
-                                // If confirmed
-                                if input.trim() == "T" {
+    match execute(&mut session, command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => report(error),
+    }
+}
 
-                                    // Here this label must exist, it was checked before
-                                    let removed_password = get_password(label, &master_key).expect("Couldn't decrypt password");
-                                    remove_password_and_save(&mut vault, label);
-                                    println!("Usunięto hasło {}: {}", label, removed_password);
-                                    
-                                    // Rebuild line editor to update completions (but it clears history)
-                                    line_editor = cli::bulid_line_editor(vault.keys().cloned().collect(), commands.clone());
+fn report(error: Error) -> ExitCode {
+    eprintln!("{}", error);
+    ExitCode::from(error.exit_code())
+}
 
-                                } else {
-                                    println!("Nie potwierdzono usunięcia");
-                                }
-                            }
-                            Err(_) => println!("Błąd odczytu"),
-                        }
-                        
-                    }
-                    "get" => {
-                        if parts.len() != 2 {
-                            println!("Użycie: get <nazwa>");
-                            continue;
-                        }
-                        let label = parts[1];
-                        
-                        // Get password or None if there is not such label saved
-                        let password = get_password(label, &master_key);
-                        match password{
-                            Some(password) => {
+/// Reads master password from given source and unlocks the vault at path with it
+fn unlock(source: &PasswordSource, path: &Path, read_only: bool) -> Result<Vault> {
+    if !Vault::exists(path) {
+        return Err(Error::NoProfile);
+    }
 
-                                // Copy password to clipboard
-                                clipboard.set_contents(password).expect("Cannot access system's clipboard");
-                                println!("Hasło skopiowane do schowka!");
-                            }
-                            None => {println!("Nie ma zapisanego hasła z taką etykietą");}
-                        }
+    let password = read_master_password(source)?;
 
-                    }
-                    "change-password" => {
-                        if parts.len() != 1{
-                            println!("Nadmiarowy argument {}", parts[1]);
-                            continue;
-                        }
-                        
-                        // Ask for confirmation
-                        print!("Czy na pewno chcesz zmienić główne hasło? T/[N] ");
-                        
-                        io::stdout().flush().unwrap();
-                        let mut input = String::new();
-                        match io::stdin().read_line(&mut input) {
-                            Ok(_) => {
-                                if input.trim() == "T" {
-                                    // Get master key from new password or None if user didn't input it correctly
-                                    let opt_new_master_key = change_password(&mut vault, &master_key);
-                                    match opt_new_master_key {
-                                        Some(new_master_key) => {
-                                            // Password changed correctly
-                                            master_key = new_master_key;
-                                            println!("Nowe hasło ustawione!");
-                                        }
-                                        // None - user didn't correctly repeat the new password
-                                        _ => {println!("Powtórzone hasło musi być identyczne jak pierwsze! Nie zmieniono hasła.");}
-                                    }
-                                } else {
-                                    println!("Nie potwierdzono zmiany hasła");
-                                }
-                            }
-                            Err(_) => println!("Błąd odczytu"),
-                        }
-                        
-                    }
-                    "help" => {
-                        if parts.len() != 1{
-                            println!("Nadmiarowy argument {}", parts[1]);
-                        } else{
-                            print_help()
-                        }
-                    }
-                    "exit" => {
-                        if parts.len() != 1{
-                            println!("Nadmiarowy argument {}", parts[1]);
-                        } else{
-                            on_exit(&mut master_key, clipboard);
-                            break;
-                        }
-                    }
-                    _ => println!("Nieznana komenda: '{}'. Wpisz 'help'.", parts[0]),
-                }
-            }
-            Ok(Signal::CtrlD) | Ok(Signal::CtrlC) => {
-                on_exit(&mut master_key, clipboard);
-                break;
-            }
-            _ => {}
-        }
+    if Vault::needs_migration(path) && !read_only {
+        let vault = Vault::migrate_legacy(path, &password)?;
+        println!("Dane przeniesiono do nowego formatu pliku {}", vault.path().display());
+        return Ok(vault);
     }
-}
 
-fn print_help(){
-    println!(
-"Dostępne komendy:
-    new <nazwa> - Wygeneruj losowe hasło z podaną etykietą. Opcjonalnie można też podać długość hasła.
-    add <nazwa> - Dodaj nowe hasło z podaną etykietą.
-    remove <nazwa> - Usuń hasło z podaną etykietą.
-    get <nazwa> - Skopiuj do schowka hasło z podaną etykietą.
-    change-password - Zmień główne hasło.
-    help - Treść oczywista.
-    exit - Wyjdź."
-    );
+    let mut vault = Vault::open(path, &password)?;
+    vault.set_read_only(read_only);
+    Ok(vault)
 }
 
-/// Clear master_key and potential password in clipboard
-fn on_exit(master_key: &mut [u8], mut clipboard: ClipboardContext){
-    master_key.zeroize();
-    clipboard.set_contents(String::from("")).expect("Cannot modify system's clipboard");
-    println!("Zakończono");
+/// Reads the master password once, without asking to repeat it
+fn read_master_password(source: &PasswordSource) -> Result<String> {
+    match source {
+        PasswordSource::Terminal => {
+            print!("Wprowadź hasło: ");
+            io::stdout().flush()?;
+            Ok(read_password()?)
+        }
+        PasswordSource::Fd(fd) => read_line(BufReader::new(open_fd(*fd)?)),
+        PasswordSource::Env(var) => std::env::var(var).map_err(|_| {
+            Error::Io(io::Error::new(io::ErrorKind::NotFound, format!("brak zmiennej środowiskowej {}", var)))
+        }),
+    }
 }
 
-/// Returns None is user didn't repeat the password correctly
-fn password_input() -> Option<String>{
-    print!("Podaj nowe hasło: ");
-    io::stdout().flush().unwrap();
-    let password = read_password().unwrap();
-
-    print!("Powtórz hasło: ");
-    io::stdout().flush().unwrap();
-    let password_repeat = read_password().unwrap();
+/// Copy of the descriptor given by the caller, which is left open
+#[cfg(unix)]
+fn open_fd(fd: i32) -> io::Result<std::fs::File> {
+    use std::os::fd::BorrowedFd;
 
-    if password == password_repeat{
-        return Some(password);
-    } else{
-        return None;
+    // Reading the password would swallow the input or write to the output
+    if (0..=2).contains(&fd) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "--password-fd nie może być standardowym wejściem ani wyjściem (0-2), użyj np. 3 albo --password-env"));
+    }
+    // Safety: F_GETFD only reads the flags of the descriptor, it fails for one which isn't open
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("deskryptor {} nie jest otwarty", fd)));
     }
-}
-
-/// If user correctly inputs new password it changes encrytpion of all
-/// passwords, verification token and returns new master_key (from new password).
-/// If not, it doesn't change anything and returns None
-fn change_password(vault: &mut Vault, master_key: & [u8]) -> Option<[u8; 32]> {
-    if let Some(new_password) = password_input(){
-        let salt = load_salt().expect("Error reading salt file");
-        let new_master_key = derive_master_key(&new_password, &salt);
-        
-        // Decrypt all passwords and encrypt them with new password
-        change_encryption_to_new_master_password(vault, master_key, &new_master_key);
 
-        // need to also change it in auth
-        create_verification_token(&new_master_key);
+    // Safety: the descriptor is open (checked above) and stays open while it's duplicated
+    let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+    Ok(std::fs::File::from(borrowed.try_clone_to_owned()?))
+}
 
-        Some(new_master_key)
-    } else{
-        None
-    }
-}
\ No newline at end of file
+#[cfg(not(unix))]
+fn open_fd(_fd: i32) -> io::Result<std::fs::File> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "--password-fd jest dostępne tylko w systemach uniksowych"))
+}
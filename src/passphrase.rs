@@ -0,0 +1,94 @@
+use std::sync::OnceLock;
+use rand::seq::IndexedRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+
+// English wordlist of BIP-0039 (github.com/bitcoin/bips), 2048 words, so 11 bits of entropy each.
+// Lines of the EFF diceware lists ("11111<tab>abacus") are read too, the list can be swapped for one.
+// Also the dictionary of words in strength.rs, so guesses there match the entropy here.
+const WORDLIST: &str = include_str!("wordlist.txt");
+
+/// Capitalization of passphrase words
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Capitalization {
+    #[default]
+    Lower,
+    /// First letter of every word
+    Title,
+    /// First letter of randomly chosen words, adds a bit per word
+    Random,
+}
+
+/// Rules for generating diceware-style passphrases - random words from an embedded list
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Passphrase {
+    pub words: usize,
+    pub separator: String,
+    pub capitalization: Capitalization,
+    /// Number of random digits, each appended to a random word
+    pub digits: usize,
+}
+
+impl Passphrase {
+    /// Passphrase of given number of lowercase words separated with dashes
+    pub fn new(words: usize) -> Self {
+        Passphrase { words, separator: String::from("-"), capitalization: Capitalization::Lower, digits: 0 }
+    }
+
+    pub fn generate(&self) -> Result<String> {
+        if self.words == 0 {
+            return Err(Error::Generator(String::from("Liczba słów musi być dodatnią liczbą całkowitą!")));
+        }
+
+        let list = wordlist();
+        let mut rng = rand::rng();
+        let mut words: Vec<String> = Vec::with_capacity(self.words);
+        for _ in 0..self.words {
+            let word = *list.choose(&mut rng).ok_or_else(|| Error::Generator(String::from("Pusta lista słów")))?;
+            let capitalize = match self.capitalization {
+                Capitalization::Lower => false,
+                Capitalization::Title => true,
+                Capitalization::Random => rng.random_bool(0.5),
+            };
+            words.push(if capitalize { capitalized(word) } else { word.to_string() });
+        }
+
+        for _ in 0..self.digits {
+            let i = rng.random_range(0..words.len());
+            let digit = rng.random_range(0..10u8);
+            words[i].push(char::from(b'0' + digit));
+        }
+
+        Ok(words.join(&self.separator))
+    }
+
+    /// Bits of entropy of generated passphrases. Doesn't count the separator,
+    /// as it is assumed to be known to an attacker.
+    pub fn entropy(&self) -> f64 {
+        let words = self.words as f64;
+        let mut bits = words * (wordlist().len() as f64).log2();
+        if self.capitalization == Capitalization::Random {
+            bits += words;
+        }
+        // Only the digits themselves - words they end up at can give the same passphrase
+        // in different orders, so their positions aren't counted
+        bits += self.digits as f64 * 10f64.log2();
+        bits
+    }
+}
+
+/// Words of the embedded list, the last field of every line
+pub(crate) fn wordlist() -> &'static [&'static str] {
+    static WORDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    WORDS.get_or_init(|| WORDLIST.lines().filter_map(|line| line.split_whitespace().next_back()).collect())
+}
+
+fn capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
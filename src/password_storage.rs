@@ -1,120 +1,320 @@
 use rand::{rngs::OsRng, TryRngCore};
 use serde::{Serialize, Deserialize};
-use std::{collections::HashMap, fs::{self, File}, io::{Read, Write}, path::PathBuf};
+use std::{collections::HashMap, fs, path::Path, time::{SystemTime, UNIX_EPOCH}};
 use aes_gcm::{Aes256Gcm, Key, Nonce,
 aead::{Aead, KeyInit}};
+use crate::container::{self, Header};
+use crate::error::{Error, Result};
+use crate::generator::Recipe;
 
-const VAULT_FILE: &str = "passman_data/vault.json";
+// Plaintext JSON vault used before the vault file was encrypted as a whole
+const LEGACY_VAULT_FILE: &str = "vault.json";
 
-pub type Vault = HashMap<String, EncryptedPassword>;
+/// Entries by their labels
+pub type Entries = HashMap<String, Entry>;
 
-#[derive(Serialize, Deserialize)]
-pub struct EncryptedPassword{
+/// Removed entries, oldest first
+pub type Trash = Vec<TrashedEntry>;
+
+/// Number of previous passwords kept with each entry when the config doesn't say otherwise
+pub const DEFAULT_HISTORY_DEPTH: usize = 10;
+
+/// Value encrypted with master key
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EncryptedField{
     nonce: Vec<u8>,
     cipher: Vec<u8>
 }
 
-/// Load Vault HashMap from the vault file
-pub fn load_vault() -> Vault{
-    if !PathBuf::from(vault_path()).exists(){
-        return HashMap::new();
+/// Single entry of the vault - password with its metadata.
+/// Password and notes are encrypted with master key, the rest is
+/// protected by encryption of the whole vault file.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry{
+    // Flattened, so entries saved before metadata was added still load
+    #[serde(flatten)]
+    password: EncryptedField,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub urls: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notes: Option<EncryptedField>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    // otpauth URI or base32 secret, as it was given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    totp: Option<EncryptedField>,
+    // Unix timestamps in seconds, 0 if unknown
+    #[serde(default)]
+    pub created: u64,
+    #[serde(default)]
+    pub modified: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<u64>,
+    // Previous passwords, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    history: Vec<HistoryItem>,
+    // Unix timestamp, None if the password is the one the entry was created with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    password_changed: Option<u64>,
+    /// Rules the password was generated with, None if it was typed or imported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipe: Option<Recipe>,
+}
+
+/// Password the entry had before, encrypted like the current one
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistoryItem {
+    password: EncryptedField,
+    /// Unix timestamp of when it was replaced
+    pub replaced: u64,
+}
+
+/// Entry removed from the vault, kept until the trash is purged
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrashedEntry {
+    /// Label the entry had when it was removed
+    pub label: String,
+    /// Unix timestamp of when it was removed
+    pub deleted: u64,
+    entry: Entry,
+    // UUID of the entry in a KeePass database, whose trash is its recycle bin
+    #[serde(skip)]
+    pub(crate) id: Option<String>,
+}
+
+impl TrashedEntry {
+    pub(crate) fn new(label: &str, deleted: u64, entry: Entry, id: Option<String>) -> TrashedEntry {
+        TrashedEntry { label: label.to_string(), deleted, entry, id }
     }
 
-    let mut file = File::open(vault_path()).expect("File opening error");
-    let mut data = String::new();
-    file.read_to_string(&mut data).expect("File reading error");
+    pub fn entry(&self) -> &Entry {
+        &self.entry
+    }
+
+    pub(crate) fn into_entry(self) -> Entry {
+        self.entry
+    }
 
-    return serde_json::from_str::<Vault>(&data).expect("JSON parsing error"); 
+    pub(crate) fn reencrypt(&mut self, old_master_key: &[u8], new_master_key: &[u8]) -> Result<()> {
+        self.entry.reencrypt(old_master_key, new_master_key)
+    }
 }
 
-/// Save vault HashMap to the vault file
-pub fn save_vault(vault: &Vault){
-    let json = serde_json::to_string_pretty(vault).expect("Error serializing passwords");
-    
-    let path = PathBuf::from(vault_path());
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).expect("Unable to create vault directory");
+impl HistoryItem {
+    pub(crate) fn new(password: &str, replaced: u64, master_key: &[u8]) -> Result<HistoryItem> {
+        Ok(HistoryItem { password: encrypt_field(password, master_key)?, replaced })
     }
 
-    let mut file = File::create(vault_path()).expect("Unable to create vault file");
-    file.write_all(json.as_bytes()).expect("Unable to write vault file");
+    pub(crate) fn password(&self, master_key: &[u8]) -> Result<String> {
+        decrypt_field(&self.password, master_key)
+    }
 }
 
-/// Encrypt a password with master_key and add it to the vault
-fn add_password(vault: &mut Vault, label: &str, password: &str, master_key: &[u8]){
-    let key = Key::<Aes256Gcm>::from_slice(master_key);
+impl Entry{
+    /// Creates entry with given password and no metadata
+    pub(crate) fn new(password: &str, master_key: &[u8]) -> Result<Entry>{
+        let now = now();
+        Ok(Entry {
+            password: encrypt_field(password, master_key)?,
+            username: None,
+            urls: Vec::new(),
+            notes: None,
+            tags: Vec::new(),
+            totp: None,
+            created: now,
+            modified: now,
+            last_used: None,
+            history: Vec::new(),
+            password_changed: None,
+            recipe: None,
+        })
+    }
 
-    let cipher = Aes256Gcm::new(key);
+    /// Replaces the password, the previous one goes to the history.
+    /// Only the newest history_depth passwords are kept. Fails with PasswordUnchanged
+    /// if it is the current one, so the history doesn't get a copy of it.
+    pub(crate) fn set_password(&mut self, password: &str, history_depth: usize, master_key: &[u8]) -> Result<()>{
+        let previous = self.password(master_key)?;
+        if previous == password {
+            return Err(Error::PasswordUnchanged);
+        }
+        self.history.push(HistoryItem::new(&previous, now(), master_key)?);
+        if self.history.len() > history_depth {
+            self.history.drain(..self.history.len() - history_depth);
+        }
 
-    let mut nonce = [0u8; 12];
-    OsRng.try_fill_bytes(&mut nonce).expect("Nonce generation fail");
+        self.password = encrypt_field(password, master_key)?;
+        self.password_changed = Some(now());
+        self.touch();
+        Ok(())
+    }
 
-    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), password.as_bytes()).expect("Encryption fail");
+    /// Previous passwords, oldest first
+    pub fn history(&self) -> &[HistoryItem]{
+        &self.history
+    }
 
-    vault.insert(label.to_string(), EncryptedPassword { nonce: nonce.to_vec(), cipher: ciphertext.to_vec() });
-}
+    /// Makes the password at index of the history current again. It is taken
+    /// out of the history and the current one goes there instead. Fails with
+    /// PasswordUnchanged if it is the current one, leaving the history as it was.
+    pub(crate) fn restore_password(&mut self, index: usize, history_depth: usize, master_key: &[u8]) -> Result<()>{
+        let previous = self.history.get(index).ok_or(Error::HistoryNotFound)?.password(master_key)?;
+        if previous == self.password(master_key)? {
+            return Err(Error::PasswordUnchanged);
+        }
+        self.history.remove(index);
+        self.set_password(&previous, history_depth, master_key)
+    }
 
-/// Encrypt a password with master_key, add it to the vault and save vault in the vault file
-pub fn add_and_save_password(vault: &mut Vault, label: &str, password: &str, master_key: &[u8]){
-    add_password(vault, label, password, master_key);
-    save_vault(vault);
-}
+    /// Sets the history, the newest item tells when the password was changed
+    pub(crate) fn set_history(&mut self, history: Vec<HistoryItem>){
+        self.password_changed = history.last().map(|item| item.replaced);
+        self.history = history;
+    }
+
+    /// Unix timestamp of when the current password was set, 0 if unknown
+    pub fn password_changed(&self) -> u64{
+        self.password_changed.unwrap_or(self.created)
+    }
 
-/// Decrypts password with specified label and returns it or
-/// none if there is no such label or it couldn't be decrypted.
-pub fn get_password(label: &str, master_key: &[u8]) -> Option<String>{
-    let vault = load_vault();
+    /// Decrypts the password
+    pub(crate) fn password(&self, master_key: &[u8]) -> Result<String>{
+        decrypt_field(&self.password, master_key)
+    }
 
-    let entry = vault.get(label)?;
+    /// Decrypts the notes, returns None if there are none
+    pub(crate) fn notes(&self, master_key: &[u8]) -> Result<Option<String>>{
+        self.notes.as_ref().map(|notes| decrypt_field(notes, master_key)).transpose()
+    }
 
-    let key = Key::<Aes256Gcm>::from_slice(master_key);
+    pub(crate) fn set_notes(&mut self, notes: Option<&str>, master_key: &[u8]) -> Result<()>{
+        self.notes = notes.map(|notes| encrypt_field(notes, master_key)).transpose()?;
+        Ok(())
+    }
 
-    let cipher = Aes256Gcm::new(key);
+    /// Decrypts the TOTP secret, returns None if there is none
+    pub(crate) fn totp(&self, master_key: &[u8]) -> Result<Option<String>>{
+        self.totp.as_ref().map(|totp| decrypt_field(totp, master_key)).transpose()
+    }
+
+    pub(crate) fn set_totp(&mut self, totp: Option<&str>, master_key: &[u8]) -> Result<()>{
+        self.totp = totp.map(|totp| encrypt_field(totp, master_key)).transpose()?;
+        Ok(())
+    }
+
+    pub fn has_notes(&self) -> bool{
+        self.notes.is_some()
+    }
+
+    pub fn has_totp(&self) -> bool{
+        self.totp.is_some()
+    }
+
+    /// Marks entry as modified now
+    pub fn touch(&mut self){
+        self.modified = now();
+    }
+
+    /// Marks entry as used now, e.g. when its password was copied
+    pub fn mark_used(&mut self){
+        self.last_used = Some(now());
+    }
 
-    let password = cipher.decrypt(
-        Nonce::from_slice(&entry.nonce),
-    entry.cipher.as_ref()).ok()?;
+    /// Encrypts all encrypted fields again with new master key
+    pub(crate) fn reencrypt(&mut self, old_master_key: &[u8], new_master_key: &[u8]) -> Result<()>{
+        let password = self.password(old_master_key)?;
+        let notes = self.notes(old_master_key)?;
+        let totp = self.totp(old_master_key)?;
 
-    Some(String::from_utf8(password).expect("Decrypted password is not a valid UTF-8 string"))
+        self.password = encrypt_field(&password, new_master_key)?;
+        for item in &mut self.history {
+            let previous = item.password(old_master_key)?;
+            item.password = encrypt_field(&previous, new_master_key)?;
+        }
+        self.set_notes(notes.as_deref(), new_master_key)?;
+        self.set_totp(totp.as_deref(), new_master_key)
+    }
+}
+
+/// Current time as unix timestamp in seconds
+pub fn now() -> u64{
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
 }
 
-/** 
- * Removes a password with specified label from the vault.
- * Panics if label doesn't exist.
-*/ 
-fn remove_password(vault: &mut Vault, label: &str){
-    vault.remove(label).expect("Error removing password");
+/// Everything that is stored encrypted in the vault file
+#[derive(Serialize, Deserialize)]
+struct Body<V, T> {
+    entries: V,
+    // Missing in files written before the trash was added
+    #[serde(default)]
+    trash: T,
 }
 
-/**
- * Removes a password with specified label from the vault and
-   saves it to the vault file.
- * Panics if label doesn't exist.
- */
-pub fn remove_password_and_save(vault: &mut Vault, label: &str){
-    remove_password(vault, label);
-    save_vault(vault);
+/// Decrypts entries and the trash from contents of a vault file
+pub fn decrypt_entries(data: &[u8], master_key: &[u8]) -> Result<(Entries, Trash)> {
+    let body = container::open(data, master_key)?;
+
+    let body = serde_json::from_slice::<Body<Entries, Trash>>(&body)?;
+    Ok((body.entries, body.trash))
 }
 
-/// For switching master password, decrypts all passwords and encrypts them it new master password
-pub fn change_encryption_to_new_master_password(vault: &mut Vault, old_master_key: &[u8], new_master_key: &[u8]){
-    let keys: Vec<String> = vault.keys().cloned().collect();
+/// Encrypts entries and the trash with master_key and returns complete contents of a vault file with given header
+pub fn encrypt_entries(entries: &Entries, trash: &Trash, master_key: &[u8], header: &Header) -> Result<Vec<u8>> {
+    let body = serde_json::to_vec(&Body { entries, trash })?;
+    container::seal(header, &body, master_key)
+}
 
-    for label in keys{
-        let password = get_password(&label, old_master_key).expect("Decrytion failed");
-        
-        // Modifying only HashMap, without saving to file
-        remove_password(vault, &label);
-        add_password(vault, &label, &password, new_master_key);
+/// Load entries from the plaintext vault file of the old format in dir
+pub fn load_legacy_entries(dir: &Path) -> Result<Entries>{
+    let path = dir.join(LEGACY_VAULT_FILE);
+    if !path.exists(){
+        return Ok(HashMap::new());
     }
 
-    // Save new vault
-    save_vault(vault);
+    let data = fs::read_to_string(path)?;
+    Ok(serde_json::from_str::<Entries>(&data)?)
 }
 
-fn vault_path() -> PathBuf{
-    let mut path = dirs::data_dir().expect("Couldn't find data dir");
-    path.push(VAULT_FILE);
+/// Deletes the plaintext vault file of the old format in dir, if there is one
+pub fn remove_legacy_entries(dir: &Path) -> Result<()>{
+    let path = dir.join(LEGACY_VAULT_FILE);
+    if path.exists(){
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Encrypt a value with master_key
+pub(crate) fn encrypt_field(value: &str, master_key: &[u8]) -> Result<EncryptedField>{
+    let key = Key::<Aes256Gcm>::from_slice(master_key);
+
+    let cipher = Aes256Gcm::new(key);
+
+    let mut nonce = [0u8; 12];
+    OsRng.try_fill_bytes(&mut nonce).map_err(|e| Error::Crypto(e.to_string()))?;
+
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), value.as_bytes())
+        .map_err(|e| Error::Crypto(e.to_string()))?;
+
+    Ok(EncryptedField { nonce: nonce.to_vec(), cipher: ciphertext.to_vec() })
+}
+
+/// Decrypts a value. The key was already checked when the vault was opened,
+/// so failure means the value was modified.
+pub(crate) fn decrypt_field(field: &EncryptedField, master_key: &[u8]) -> Result<String>{
+    if field.nonce.len() != 12 {
+        return Err(Error::Tampered);
+    }
+
+    let key = Key::<Aes256Gcm>::from_slice(master_key);
+
+    let cipher = Aes256Gcm::new(key);
+
+    let value = cipher.decrypt(
+        Nonce::from_slice(&field.nonce),
+    field.cipher.as_ref()).map_err(|_| Error::Tampered)?;
 
-    return path;
+    String::from_utf8(value).map_err(|_| Error::CorruptVault(String::from("odszyfrowana wartość nie jest poprawnym UTF-8")))
 }
@@ -0,0 +1,72 @@
+use std::io::{self, Write};
+use crossterm::cursor::MoveTo;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::style::{Attribute, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType};
+use passman::Result;
+use crate::reveal::on_alternate_screen;
+
+// Lines of the screen taken by the title and the help line
+const RESERVED_LINES: usize = 3;
+
+/// Lets the user choose one of the items with the arrow keys and Enter, on the alternate
+/// screen. Returns the index of the chosen item, None if the choice was cancelled.
+pub fn pick(title: &str, items: &[String]) -> Result<Option<usize>> {
+    on_alternate_screen(&mut io::stdout(), |stdout| {
+        let mut selected = 0;
+        let mut first_shown = 0;
+        loop {
+            // Some terminals don't tell their size, everything is shown then
+            let height = match terminal::size()?.1 as usize {
+                0 => items.len().max(1),
+                rows => rows.saturating_sub(RESERVED_LINES).max(1),
+            };
+            // Scrolls so the selected item is always visible
+            if selected < first_shown {
+                first_shown = selected;
+            } else if selected >= first_shown + height {
+                first_shown = selected + 1 - height;
+            }
+            draw(stdout, title, items, selected, first_shown, height)?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Enter => return Ok(Some(selected)),
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+                KeyCode::Up => selected = selected.saturating_sub(1),
+                KeyCode::Down => selected = (selected + 1).min(items.len().saturating_sub(1)),
+                KeyCode::PageUp => selected = selected.saturating_sub(height),
+                KeyCode::PageDown => selected = (selected + height).min(items.len().saturating_sub(1)),
+                KeyCode::Home => selected = 0,
+                KeyCode::End => selected = items.len().saturating_sub(1),
+                _ => {}
+            }
+        }
+    })
+}
+
+fn draw(stdout: &mut io::Stdout, title: &str, items: &[String], selected: usize, first_shown: usize,
+        height: usize) -> io::Result<()> {
+    execute!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+    // Raw mode doesn't move back to the start of a line by itself
+    write!(stdout, "{}\r\n", title)?;
+    for (i, item) in items.iter().enumerate().skip(first_shown).take(height) {
+        if i == selected {
+            execute!(stdout, SetAttribute(Attribute::Reverse))?;
+            write!(stdout, "> {}", item)?;
+            execute!(stdout, SetAttribute(Attribute::Reset))?;
+            write!(stdout, "\r\n")?;
+        } else {
+            write!(stdout, "  {}\r\n", item)?;
+        }
+    }
+    write!(stdout, "\r\n↑/↓ - wybierz, Enter - skopiuj hasło, Esc - anuluj")?;
+    stdout.flush()
+}
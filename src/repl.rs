@@ -0,0 +1,268 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use clap::Parser;
+use passman::{Error, Result, SearchIndex, Vault};
+use reedline::Signal;
+use crate::args::{ReplCommand, ReplLine};
+use crate::cli::{self, MyPrompt};
+use crate::commands::*;
+use crate::config::{Config, StrengthPolicy, DEFAULT_PROFILE};
+use crate::idle::{self, State};
+use crate::{report, unlock, PasswordSource};
+
+/// Interactive mode - unlocks the vault (or creates it) and reads commands until exit.
+/// With read_only set, every vault is opened read-only, not only those marked so in the config.
+pub fn run(source: PasswordSource, config: Config, name: String, path: PathBuf, read_only: bool) -> ExitCode {
+    // Check if master password is set
+    let unlocked = match unlock(&source, &path, read_only || config.is_read_only(&path)) {
+        Err(Error::NoProfile) => {
+            println!("Nie znaleziono profilu.");
+            create_profile(&path, &config.strength)
+        }
+        result => result,
+    };
+    let vault = match unlocked {
+        Ok(vault) => vault,
+        Err(error) => return report(error),
+    };
+
+    // Storage with our encrypted passwords
+    let mut session = Session::new(name, vault);
+    session.configure(&config);
+
+    // Prepare for REPL
+    let commands = vec![
+        String::from("new"),
+        String::from("add"),
+        String::from("generate"),
+        String::from("remove"),
+        String::from("ls"),
+        String::from("mv"),
+        String::from("rename"),
+        String::from("edit"),
+        String::from("rotate"),
+        String::from("history"),
+        String::from("restore"),
+        String::from("trash"),
+        String::from("find"),
+        String::from("get"),
+        String::from("totp"),
+        String::from("show"),
+        String::from("info"),
+        String::from("set"),
+        String::from("unset"),
+        String::from("change-password"),
+        String::from("kdf"),
+        String::from("restore-backup"),
+        String::from("audit"),
+        String::from("import"),
+        String::from("export"),
+        String::from("use"),
+        String::from("help"),
+        String::from("exit")
+        ];
+
+    let index = Arc::new(Mutex::new(SearchIndex::new(&session.vault)));
+    let mut line_editor = cli::bulid_line_editor(index.clone(), commands);
+
+    let shared = idle::new_shared(session, index.clone());
+    if !config.lock_timeout().is_zero() {
+        idle::start_watchdog(&shared, config.lock_timeout());
+    }
+
+    // Show all available commands
+    print_help();
+
+    loop {
+        let name = idle::lock(&shared).name().to_string();
+        // The default vault isn't shown in the prompt
+        let prompt = MyPrompt { vault: Some(name).filter(|name| name != DEFAULT_PROFILE) };
+        let sig = line_editor.read_line(&prompt);
+
+        // Held while the command runs, so the session isn't locked in the middle of it
+        let mut guarded = idle::lock(&shared);
+
+        match sig {
+            Ok(Signal::Success(input)) => {
+                let session = match &mut guarded.state {
+                    State::Unlocked(session) => session,
+                    State::Locked { .. } => {
+                        if input.trim() == "exit" {
+                            break;
+                        }
+
+                        println!("Sesja została zablokowana z powodu bezczynności, komenda nie została wykonana.");
+                        let open = |path: &Path, read_only| unlock(&PasswordSource::Terminal, path, read_only);
+                        if let Err(error) = guarded.unlock(open, &config) {
+                            println!("{}", error);
+                        }
+                        continue;
+                    }
+                };
+
+                // Split like a shell does, so values with spaces can be quoted
+                let parts = match shlex::split(&input) {
+                    Some(parts) => parts,
+                    None => {
+                        println!("Niezamknięty cudzysłów");
+                        continue;
+                    }
+                };
+                if parts.is_empty() {
+                    continue;
+                }
+
+                // Match command
+                let command = match ReplLine::try_parse_from(&parts) {
+                    Ok(line) => line.command,
+                    Err(error) => {
+                        if error.kind() == clap::error::ErrorKind::InvalidSubcommand {
+                            println!("Nieznana komenda: '{}'. Wpisz 'help'.", parts[0]);
+                        } else {
+                            let _ = error.print();
+                        }
+                        continue;
+                    }
+                };
+
+                match command {
+                    ReplCommand::Command(command) => {
+                        let index_before = SearchIndex::new(&session.vault);
+
+                        if let Err(error) = execute(session, *command) {
+                            println!("{}", error);
+                        }
+
+                        if SearchIndex::new(&session.vault) != index_before {
+                            // Update completions
+                            cli::set_index(&index, SearchIndex::new(&session.vault));
+                        }
+                    }
+                    ReplCommand::Use { name } => {
+                        let Some(name) = name else {
+                            list_vaults(session, &config);
+                            continue;
+                        };
+
+                        if let Err(error) = use_vault(session, &config, name, read_only) {
+                            println!("{}", error);
+                        }
+                        // Labels are from another vault now
+                        cli::set_index(&index, SearchIndex::new(&session.vault));
+                    }
+                    ReplCommand::Help => print_help(),
+                    ReplCommand::Exit => break,
+                }
+
+                // Time spent in the command (e.g. typing a password) doesn't count as inactivity
+                guarded.touch();
+            }
+            Ok(Signal::CtrlD) | Ok(Signal::CtrlC) => break,
+            _ => {}
+        }
+    }
+
+    on_exit(&shared);
+    ExitCode::SUCCESS
+}
+
+/// Switches to the vault of a profile or at a path. Unlocks it if it isn't unlocked
+/// in this session yet, or creates it if there is no vault there.
+fn use_vault(session: &mut Session, config: &Config, name: String, read_only: bool) -> Result<()> {
+    let path = config.resolve_name(&name)?;
+    if session.switch_to(&path) {
+        println!("Obecny sejf: {}", session.name);
+        return Ok(());
+    }
+
+    let vault = if Vault::exists(&path) {
+        unlock(&PasswordSource::Terminal, &path, read_only || config.is_read_only(&path))?
+    } else {
+        if !confirm(&format!("Nie ma sejfu {}. Czy chcesz go utworzyć?", path.display()), false)? {
+            return Err(Error::NotConfirmed);
+        }
+        create_profile(&path, &config.strength)?
+    };
+
+    session.add(name, vault);
+    println!("Obecny sejf: {}", session.name);
+    Ok(())
+}
+
+/// Prints unlocked vaults and profiles from the config file
+fn list_vaults(session: &Session, config: &Config) {
+    for (i, (name, path)) in session.unlocked().into_iter().enumerate() {
+        let current = if i == 0 { "*" } else { " " };
+        println!("{} {} ({})", current, name, path.display());
+    }
+
+    let locked: Vec<&String> = config.profiles.keys()
+        .filter(|name| !session.unlocked().iter().any(|(unlocked, _)| unlocked == name))
+        .collect();
+    if !locked.is_empty() {
+        println!("Zablokowane profile: {}", locked.iter().map(|name| name.as_str()).collect::<Vec<_>>().join(", "));
+    }
+}
+
+/// Asks for a new master password and creates an empty vault encrypted with it at path.
+/// Asks again until the password is repeated correctly and strong enough (or confirmed).
+fn create_profile(path: &Path, policy: &StrengthPolicy) -> Result<Vault> {
+    let password = loop {
+        let Some(password) = password_input()? else {
+            println!("{}", Error::PasswordMismatch);
+            continue;
+        };
+        match check_strength(&password, &[], policy, false, true) {
+            Ok(()) => break password,
+            Err(error @ (Error::WeakPassword | Error::NotConfirmed)) => println!("{}", error),
+            Err(error) => return Err(error),
+        }
+    };
+
+    let vault = Vault::create(path, &password)?;
+    println!("Hasło ustawione.");
+
+    Ok(vault)
+}
+
+fn print_help(){
+    println!(
+"Dostępne komendy:
+    new <nazwa> - Wygeneruj losowe hasło z podaną etykietą. Opcjonalnie można też podać długość hasła i zestaw reguł generatora (--preset) albo wygenerować frazę (--words <n>).
+    add <nazwa> - Dodaj nowe hasło z podaną etykietą.
+    generate - Wypisz losowe hasło albo frazę (--words <n>) bez zapisywania.
+    remove <nazwa> - Przenieś hasło z podaną etykietą do kosza.
+    ls [folder] - Wypisz drzewo etykiet sejfu albo folderu (części etykiet oddziela /).
+    mv <nazwa | folder> <cel> - Przenieś hasło albo cały folder, cel zakończony / to folder docelowy.
+    rename <nazwa> <nowa nazwa> - Zmień etykietę hasła.
+    edit <nazwa> - Wpisz nowe hasło dla podanej etykiety, poprzednie zostaje w historii.
+    rotate <nazwa> - Wygeneruj nowe hasło według tych samych reguł co poprzednie, poprzednie zostaje w historii.
+    history <nazwa> - Wypisz daty zmian poprzednich haseł (bez samych haseł).
+    restore <nazwa> <numer> - Przywróć poprzednie hasło z historii.
+    trash [list|restore <nazwa>|purge] - Wypisz usunięte hasła, przywróć hasło z kosza albo opróżnij kosz.
+    find <szukane...> [--list] - Znajdź hasła po etykiecie, użytkowniku, adresie albo tagu i wybierz jedno do skopiowania.
+    get <nazwa> - Skopiuj do schowka hasło z podaną etykietą.
+    totp <nazwa> - Skopiuj do schowka obecny kod TOTP (2FA) hasła z podaną etykietą.
+    show <nazwa> [--field password | notes | totp] [--mask] - Pokaż hasło, notatkę albo sekret TOTP na chwilę, na osobnym ekranie terminala.
+    info <nazwa> - Pokaż nazwę użytkownika, adresy, tagi i daty hasła.
+    set <nazwa> <username | url | notes | tags | totp> [wartość...] - Ustaw pole hasła (notatka i sekret TOTP są czytane osobno).
+    unset <nazwa> <username | url | notes | tags | totp> - Wyczyść pole hasła.
+    change-password - Zmień główne hasło.
+    restore-backup [numer] - Wypisz kopie zapasowe sejfu albo przywróć jedną z nich.
+    use [profil | ścieżka] - Przełącz na inny sejf albo wypisz odblokowane sejfy i profile.
+    audit [--json] [--breached <ścieżka>] - Sprawdź wszystkie hasła: powtórzone, słabe, stare, z wycieków i wpisy bez danych.
+    import <plik> [--dry-run] - Zaimportuj hasła z Bitwardena, KeePassa, 1Password albo pliku CSV (np. z przeglądarki).
+    export <plik> [--format encrypted | csv | json] - Wyeksportuj wszystkie hasła do pliku zaszyfrowanego osobnym hasłem albo do CSV lub JSON.
+    kdf [show | set | calibrate] - Pokaż albo zmień parametry tworzenia klucza z głównego hasła.
+    help - Treść oczywista.
+    exit - Wyjdź.
+Każdą komendę (poza use, help i exit) można też wywołać bezpośrednio, np. passman get <nazwa>."
+    );
+}
+
+/// Clear potential password in clipboard and master keys of all vaults
+fn on_exit(shared: &idle::Shared){
+    idle::lock(shared).lock_session();
+    println!("Zakończono");
+}
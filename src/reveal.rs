@@ -0,0 +1,109 @@
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+use crossterm::cursor::MoveTo;
+use crossterm::event::{self, Event, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use zeroize::Zeroizing;
+use passman::{Error, Result};
+
+// Characters of a masked secret which stay visible at each end
+const MASK_VISIBLE: usize = 2;
+// Shorter secrets are masked completely
+const MASK_MIN_LEN: usize = 8;
+
+/// Shows a secret on the alternate screen of the terminal until a key is pressed
+/// or the timeout passes (never if it is zero), then switches back to the normal one.
+/// The secret doesn't stay in the scrollback, so it doesn't end up in terminal logs
+/// or recordings. Fails if stdout isn't a terminal.
+pub fn reveal(title: &str, secret: &str, timeout: Duration) -> Result<()> {
+    let mut stdout = io::stdout();
+    if !stdout.is_terminal() {
+        return Err(Error::Io(io::Error::other("sekret można pokazać tylko w terminalu, hasło wypisze get --stdout")));
+    }
+
+    on_alternate_screen(&mut stdout, |stdout| show(stdout, title, secret, timeout))
+}
+
+/// Runs run with the terminal switched to the alternate screen in raw mode,
+/// then clears it and switches back to the normal one
+pub fn on_alternate_screen<T>(stdout: &mut io::Stdout, run: impl FnOnce(&mut io::Stdout) -> io::Result<T>) -> Result<T> {
+    execute!(stdout, EnterAlternateScreen)?;
+    let result = terminal::enable_raw_mode().and_then(|_| run(stdout));
+    // Screen is restored even if showing failed, nothing should be left on it
+    let _ = terminal::disable_raw_mode();
+    execute!(stdout, Clear(ClearType::All), LeaveAlternateScreen)?;
+    Ok(result?)
+}
+
+fn show(stdout: &mut io::Stdout, title: &str, secret: &str, timeout: Duration) -> io::Result<()> {
+    let start = Instant::now();
+    loop {
+        let left = timeout.saturating_sub(start.elapsed());
+        if !timeout.is_zero() && left.is_zero() {
+            return Ok(());
+        }
+
+        execute!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+        // Raw mode doesn't move back to the start of a line by itself
+        write!(stdout, "{}\r\n\r\n", title)?;
+        for line in secret.lines() {
+            write!(stdout, "  {}\r\n", line)?;
+        }
+        write!(stdout, "\r\n")?;
+        if timeout.is_zero() {
+            write!(stdout, "Naciśnij dowolny klawisz, żeby ukryć.")?;
+        } else {
+            write!(stdout, "Zniknie za {} s. Naciśnij dowolny klawisz, żeby ukryć wcześniej.", left.as_secs_f32().ceil())?;
+        }
+        stdout.flush()?;
+
+        // Redrawn every second for the countdown
+        let wait = if timeout.is_zero() { Duration::from_secs(1) } else { left.min(Duration::from_secs(1)) };
+        if event::poll(wait)? && matches!(event::read()?, Event::Key(key) if key.kind == KeyEventKind::Press) {
+            return Ok(());
+        }
+    }
+}
+
+/// Secret with all but a few characters at each end replaced, enough to tell
+/// which one it is without showing it. Short secrets are masked completely.
+pub fn mask(secret: &str) -> Zeroizing<String> {
+    let len = secret.chars().count();
+    let visible = if len < MASK_MIN_LEN { 0 } else { MASK_VISIBLE };
+    Zeroizing::new(secret.chars().enumerate()
+        .map(|(i, c)| if i < visible || i >= len - visible { c } else { '*' })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_secrets_are_masked_completely() {
+        assert_eq!(*mask(""), "");
+        assert_eq!(*mask("a"), "*");
+        let below = "x".repeat(MASK_MIN_LEN - 1);
+        assert_eq!(*mask(&below), "*".repeat(MASK_MIN_LEN - 1));
+    }
+
+    #[test]
+    fn ends_stay_visible() {
+        // Exactly the minimum length
+        let at = "abcdefgh";
+        assert_eq!(at.len(), MASK_MIN_LEN);
+        assert_eq!(MASK_VISIBLE, 2);
+        assert_eq!(*mask(at), "ab****gh");
+
+        assert_eq!(*mask("abcdefghi"), "ab*****hi");
+        assert_eq!(*mask("correct-horse-battery"), "co*****************ry");
+    }
+
+    #[test]
+    fn counts_characters_not_bytes() {
+        // 8 characters, 12 bytes
+        assert_eq!(*mask("żółwiątk"), "żó****tk");
+        assert_eq!(*mask("żółw"), "****");
+    }
+}
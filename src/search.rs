@@ -0,0 +1,155 @@
+use std::cmp::Reverse;
+use crate::vault::Vault;
+
+// Scores of fuzzy matching, the same as fzf uses. Each matched character is worth
+// SCORE_MATCH, more at the start of a word, and gaps between matched characters cost.
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CAMEL: i64 = 7;
+const BONUS_CONSECUTIVE: i64 = 4;
+// First character of the query counts this many times more at the start of a word
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+// Matches in usernames, URLs and tags rank below the same matches in labels
+const PENALTY_METADATA: i64 = 8;
+
+/// Field of an entry a query matched
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MatchedField {
+    Label,
+    Username,
+    Url,
+    Tag,
+}
+
+/// Entry found by `SearchIndex::search`
+#[derive(Debug)]
+pub struct Found {
+    pub label: String,
+    pub score: i64,
+    /// Fields other than the label that matched words of the query, with their values
+    pub matched: Vec<(MatchedField, String)>,
+}
+
+/// Metadata of the entries which can be searched: labels, usernames, URLs and tags.
+/// Passwords and notes never are. Compared to notice when it needs to be built again.
+#[derive(Clone, PartialEq, Default)]
+pub struct SearchIndex {
+    entries: Vec<Indexed>,
+}
+
+#[derive(Clone, PartialEq)]
+struct Indexed {
+    label: String,
+    // Values of the other fields, label isn't repeated here
+    fields: Vec<(MatchedField, String)>,
+}
+
+impl SearchIndex {
+    pub fn new(vault: &Vault) -> SearchIndex {
+        let mut entries: Vec<Indexed> = vault.iter_labels()
+            .filter_map(|label| {
+                let entry = vault.entry(label).ok()?;
+                let fields = entry.username.iter().map(|username| (MatchedField::Username, username.clone()))
+                    .chain(entry.urls.iter().map(|url| (MatchedField::Url, url.clone())))
+                    .chain(entry.tags.iter().map(|tag| (MatchedField::Tag, tag.clone())))
+                    .collect();
+                Some(Indexed { label: label.to_string(), fields })
+            })
+            .collect();
+        entries.sort_by(|a, b| a.label.cmp(&b.label));
+        SearchIndex { entries }
+    }
+
+    /// Labels of all entries, sorted
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.label.as_str())
+    }
+
+    /// Entries matching the query, best first. Every word of the query has to match
+    /// one of the fields as a fuzzy subsequence, like in fzf: `gthb` matches `github`.
+    /// Words with an upper case letter are matched case sensitively.
+    pub fn search(&self, query: &str) -> Vec<Found> {
+        let words: Vec<&str> = query.split_whitespace().collect();
+        if words.is_empty() {
+            return Vec::new();
+        }
+
+        let mut found: Vec<Found> = self.entries.iter().filter_map(|entry| entry.search(&words)).collect();
+        // Shorter labels first among equal scores, they match more of their text
+        found.sort_by_key(|found| (Reverse(found.score), found.label.len(), found.label.clone()));
+        found
+    }
+}
+
+impl Indexed {
+    fn search(&self, words: &[&str]) -> Option<Found> {
+        let mut score = 0;
+        let mut matched = Vec::new();
+        for word in words {
+            let label_score = fuzzy_score(word, &self.label);
+            let field = self.fields.iter()
+                .filter_map(|field| Some((fuzzy_score(word, &field.1)? - PENALTY_METADATA, field)))
+                .max_by_key(|(score, _)| *score);
+
+            match (label_score, field) {
+                (Some(label_score), Some((field_score, _))) if label_score >= field_score => score += label_score,
+                (Some(label_score), None) => score += label_score,
+                (_, Some((field_score, field))) => {
+                    score += field_score;
+                    if !matched.contains(field) {
+                        matched.push(field.clone());
+                    }
+                }
+                (None, None) => return None,
+            }
+        }
+        Some(Found { label: self.label.clone(), score, matched })
+    }
+}
+
+/// Score of the best way to match pattern as a subsequence of text, None if it doesn't match.
+/// Case is ignored, unless the pattern has upper case letters.
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<i64> {
+    let case_sensitive = pattern.chars().any(char::is_uppercase);
+    let fold = |c: char| if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) };
+    let pattern: Vec<char> = pattern.chars().map(fold).collect();
+    let text: Vec<char> = text.chars().collect();
+    if pattern.is_empty() {
+        return Some(0);
+    }
+
+    let bonus: Vec<i64> = (0..text.len()).map(|j| match j.checked_sub(1).map(|i| text[i]) {
+        None => BONUS_BOUNDARY,
+        Some(previous) if !previous.is_alphanumeric() => BONUS_BOUNDARY,
+        Some(previous) if previous.is_lowercase() && text[j].is_uppercase() => BONUS_CAMEL,
+        Some(_) => 0,
+    }).collect();
+
+    // Best score of matching the pattern up to i with its character i at j of the text
+    let mut previous: Vec<Option<i64>> = vec![None; text.len()];
+    for (i, &p) in pattern.iter().enumerate() {
+        let mut current = vec![None; text.len()];
+        for j in 0..text.len() {
+            if fold(text[j]) != p {
+                continue;
+            }
+            let score = if i == 0 {
+                Some(SCORE_MATCH + bonus[j] * BONUS_FIRST_CHAR_MULTIPLIER)
+            } else {
+                (0..j).filter_map(|k| {
+                    let gap = (j - k - 1) as i64;
+                    let step = match gap {
+                        0 => BONUS_CONSECUTIVE,
+                        gap => SCORE_GAP_START + SCORE_GAP_EXTENSION * (gap - 1),
+                    };
+                    Some(previous[k]? + step)
+                }).max().map(|best| best + SCORE_MATCH + bonus[j])
+            };
+            current[j] = score;
+        }
+        previous = current;
+    }
+    previous.into_iter().flatten().max()
+}
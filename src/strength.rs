@@ -0,0 +1,568 @@
+//! Password strength estimation in the style of zxcvbn: the password is split into
+//! guessable patterns (common passwords, words, sequences, repeats, keyboard runs,
+//! dates) and the split needing the least guesses decides its strength.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+use chrono::Datelike;
+use crate::passphrase::wordlist;
+
+// Most common passwords, most popular first
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+const KEYBOARD_ROWS: [&str; 4] = ["`1234567890-=", "qwertyuiop[]\\", "asdfghjkl;'", "zxcvbnm,./"];
+const KEYBOARD_KEYS: f64 = 47.0;
+// Only neighbours in the same row are matched
+const KEYBOARD_DEGREE: f64 = 2.0;
+
+const LEET: [(char, char); 11] = [
+    ('4', 'a'), ('@', 'a'), ('3', 'e'), ('1', 'i'), ('!', 'i'), ('|', 'i'),
+    ('0', 'o'), ('5', 's'), ('$', 's'), ('7', 't'), ('+', 't'),
+];
+
+// Longer passwords are only estimated by their beginning, which is enough to call them strong
+const MAX_LENGTH: usize = 100;
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_GUESSES_BEFORE_GROWING_SEQUENCE: f64 = 10_000.0;
+const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI_CHAR: f64 = 50.0;
+const MIN_YEAR_SPACE: f64 = 20.0;
+
+/// Guesses per second of an offline attack on a slow hash (like the vault's Argon2)
+pub const GUESSES_PER_SECOND: f64 = 10_000.0;
+
+/// Result of `estimate_strength`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Strength {
+    /// From 0 (guessed almost immediately) to 4 (very hard to guess)
+    pub score: u8,
+    /// Estimated number of guesses needed to find the password
+    pub guesses: f64,
+    /// What makes the password weak, if it is weak
+    pub warning: Option<String>,
+    /// How to make it stronger
+    pub suggestions: Vec<String>,
+}
+
+impl Strength {
+    /// Time needed to find the password at `GUESSES_PER_SECOND`
+    pub fn crack_time(&self) -> Duration {
+        Duration::try_from_secs_f64(self.guesses / GUESSES_PER_SECOND).unwrap_or(Duration::MAX)
+    }
+
+    /// Crack time in words, e.g. "3 godziny"
+    pub fn crack_time_display(&self) -> String {
+        display_time(self.guesses / GUESSES_PER_SECOND)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Pattern {
+    CommonPassword { rank: usize },
+    Word,
+    UserInput,
+    Sequence,
+    Repeat,
+    Keyboard,
+    Date,
+    Bruteforce,
+}
+
+#[derive(Clone, Debug)]
+struct Match {
+    // First and last character covered
+    i: usize,
+    j: usize,
+    guesses: f64,
+    pattern: Pattern,
+    uppercase: bool,
+    leet: bool,
+}
+
+/// Estimates strength of password. User inputs are other values known to an attacker,
+/// like the label or user name, which shouldn't be part of the password.
+pub fn estimate_strength(password: &str, user_inputs: &[&str]) -> Strength {
+    if password.is_empty() {
+        return Strength {
+            score: 0,
+            guesses: 1.0,
+            warning: Some(String::from("Hasło jest puste.")),
+            suggestions: vec![String::from("Użyj kilku słów, najlepiej nietypowych.")],
+        };
+    }
+
+    let chars: Vec<char> = password.chars().take(MAX_LENGTH).collect();
+    let (guesses, sequence) = most_guessable(&chars, user_inputs);
+    let score = score(guesses);
+    let (warning, suggestions) = feedback(score, &sequence, chars.len());
+    Strength { score, guesses, warning, suggestions }
+}
+
+fn score(guesses: f64) -> u8 {
+    match guesses {
+        g if g < 1e3 + 5.0 => 0,
+        g if g < 1e6 + 5.0 => 1,
+        g if g < 1e8 + 5.0 => 2,
+        g if g < 1e10 + 5.0 => 3,
+        _ => 4,
+    }
+}
+
+/// Finds the split of password into patterns which needs the fewest guesses.
+/// Each step of the optimal sequence can be any match, or brute force over any substring.
+fn most_guessable(chars: &[char], user_inputs: &[&str]) -> (f64, Vec<Match>) {
+    let n = chars.len();
+    let mut matches = find_matches(chars, user_inputs);
+    for i in 0..n {
+        for j in i..n {
+            let guesses = BRUTEFORCE_CARDINALITY.powi((j - i + 1) as i32);
+            matches.push(Match { i, j, guesses, pattern: Pattern::Bruteforce, uppercase: false, leet: false });
+        }
+    }
+    for m in &mut matches {
+        // Short parts of a longer password need some guesses anyway
+        if m.j - m.i + 1 < n {
+            let min = if m.i == m.j { MIN_SUBMATCH_GUESSES_SINGLE_CHAR } else { MIN_SUBMATCH_GUESSES_MULTI_CHAR };
+            m.guesses = m.guesses.max(min);
+        }
+    }
+
+    // best[k][l] - the best sequence of l matches covering chars up to k:
+    // product of guesses of its matches, the last match
+    let mut best: Vec<HashMap<usize, (f64, usize)>> = vec![HashMap::new(); n];
+    let mut by_end: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for (index, m) in matches.iter().enumerate() {
+        by_end[m.j].push(index);
+    }
+
+    for k in 0..n {
+        for &index in &by_end[k] {
+            let m = &matches[index];
+            let candidates: Vec<(usize, f64)> = if m.i == 0 {
+                vec![(1, m.guesses)]
+            } else {
+                best[m.i - 1].iter().map(|(&l, &(product, _))| (l + 1, product * m.guesses)).collect()
+            };
+
+            for (l, product) in candidates {
+                let better = match best[k].get(&l) {
+                    Some(&(current, _)) => product < current,
+                    None => true,
+                };
+                if better {
+                    best[k].insert(l, (product, index));
+                }
+            }
+        }
+    }
+
+    // Longer sequences are penalized, as the attacker has to try more combinations of patterns
+    let Some((guesses, mut l)) = best[n - 1].iter()
+        .map(|(&l, &(product, _))| (factorial(l) * product + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(l as i32 - 1), l))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+    else {
+        return (BRUTEFORCE_CARDINALITY.powi(n as i32), Vec::new());
+    };
+
+    let mut sequence = Vec::new();
+    let mut k = n;
+    while k > 0 {
+        let (_, index) = best[k - 1][&l];
+        let m = matches[index].clone();
+        k = m.i;
+        l -= 1;
+        sequence.push(m);
+    }
+    sequence.reverse();
+
+    (guesses, sequence)
+}
+
+fn find_matches(chars: &[char], user_inputs: &[&str]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    dictionary_matches(chars, user_inputs, &mut matches);
+    sequence_matches(chars, &mut matches);
+    repeat_matches(chars, user_inputs, &mut matches);
+    keyboard_matches(chars, &mut matches);
+    date_matches(chars, &mut matches);
+    matches
+}
+
+/// Ranked dictionaries: common passwords and words
+fn dictionaries() -> &'static (HashMap<String, usize>, HashMap<String, usize>) {
+    static DICTIONARIES: OnceLock<(HashMap<String, usize>, HashMap<String, usize>)> = OnceLock::new();
+    // Words are the same as in passphrases. Position in their list says nothing about
+    // popularity, so every word counts as guessed after trying the whole list.
+    DICTIONARIES.get_or_init(|| (ranked(COMMON_PASSWORDS.lines()), ranked(wordlist().iter().copied())))
+}
+
+fn ranked<'a>(words: impl Iterator<Item = &'a str>) -> HashMap<String, usize> {
+    words.filter(|word| !word.is_empty()).enumerate().map(|(i, word)| (word.to_lowercase(), i + 1)).collect()
+}
+
+fn dictionary_matches(chars: &[char], user_inputs: &[&str], matches: &mut Vec<Match>) {
+    let (common, words) = dictionaries();
+    let inputs = ranked(user_inputs.iter().copied().filter(|input| input.chars().count() >= 3));
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+
+    for i in 0..chars.len() {
+        for j in i..chars.len() {
+            let token: String = lower[i..=j].iter().collect();
+            let reversed: String = lower[i..=j].iter().rev().collect();
+            // Variants of the token with how many guesses each of them adds
+            let mut variants = vec![(token.clone(), 1.0, false), (reversed, 2.0, false)];
+            for unleeted in unleet(&lower[i..=j]) {
+                let variations = leet_variations(&lower[i..=j], &unleeted);
+                variants.push((unleeted, variations, true));
+            }
+
+            for (variant, variations, leet) in variants {
+                let found = if let Some(&rank) = inputs.get(&variant) {
+                    Some((rank as f64, Pattern::UserInput))
+                } else if let Some(&rank) = common.get(&variant) {
+                    Some((rank as f64, Pattern::CommonPassword { rank }))
+                } else if words.contains_key(&variant) {
+                    Some((words.len() as f64, Pattern::Word))
+                } else {
+                    None
+                };
+
+                if let Some((rank, pattern)) = found {
+                    let uppercase = uppercase_variations(&chars[i..=j]);
+                    matches.push(Match {
+                        i, j, pattern,
+                        guesses: rank * uppercase * variations,
+                        uppercase: uppercase > 1.0,
+                        leet,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Token with leet characters replaced by letters, none if it has no leet characters.
+/// 1 can stand for both i and l.
+fn unleet(token: &[char]) -> Vec<String> {
+    if !token.iter().any(|c| LEET.iter().any(|(leet, _)| leet == c)) {
+        return Vec::new();
+    }
+
+    let replace = |one: char| -> String {
+        token.iter().map(|&c| match c {
+            '1' => one,
+            _ => LEET.iter().find(|(leet, _)| *leet == c).map(|(_, letter)| *letter).unwrap_or(c),
+        }).collect()
+    };
+    let mut variants = vec![replace('i')];
+    if token.contains(&'1') {
+        variants.push(replace('l'));
+    }
+    variants
+}
+
+/// Number of ways the leet characters could be placed among the letters they stand for
+fn leet_variations(token: &[char], unleeted: &str) -> f64 {
+    let substituted = token.iter().zip(unleeted.chars()).filter(|(a, b)| *a != b).count();
+    let replaced: Vec<char> = token.iter().zip(unleeted.chars()).filter(|(a, b)| *a != b).map(|(_, b)| b).collect();
+    let plain = token.iter().filter(|c| replaced.contains(c)).count();
+    if plain == 0 {
+        return 2.0;
+    }
+    (1..=substituted.min(plain)).map(|i| binomial(substituted + plain, i)).sum()
+}
+
+/// Number of ways the uppercase letters could be placed in the token
+fn uppercase_variations(token: &[char]) -> f64 {
+    let upper = token.iter().filter(|c| c.is_uppercase()).count();
+    let lower = token.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    // Most common places for uppercase letters
+    let first_only = token[0].is_uppercase() && upper == 1;
+    let last_only = token[token.len() - 1].is_uppercase() && upper == 1;
+    if lower == 0 || first_only || last_only {
+        return 2.0;
+    }
+    (1..=upper.min(lower)).map(|i| binomial(upper + lower, i)).sum()
+}
+
+/// Runs like abc, 6543 or XYZ
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let mut start = 0;
+    while start + 2 < chars.len() {
+        let delta = chars[start + 1] as i64 - chars[start] as i64;
+        let same_class = |a: char, b: char| {
+            (a.is_ascii_lowercase() && b.is_ascii_lowercase())
+                || (a.is_ascii_uppercase() && b.is_ascii_uppercase())
+                || (a.is_ascii_digit() && b.is_ascii_digit())
+        };
+
+        let mut end = start + 1;
+        if delta.abs() == 1 && same_class(chars[start], chars[end]) {
+            while end + 1 < chars.len()
+                && chars[end + 1] as i64 - chars[end] as i64 == delta
+                && same_class(chars[end], chars[end + 1]) {
+                end += 1;
+            }
+        }
+
+        if end - start + 1 >= 3 {
+            let first = chars[start];
+            let base = if "aAzZ019".contains(first) {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            let direction = if delta < 0 { 2.0 } else { 1.0 };
+            let guesses = base * direction * (end - start + 1) as f64;
+            matches.push(Match { i: start, j: end, guesses, pattern: Pattern::Sequence, uppercase: false, leet: false });
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+/// Repeated characters or blocks, like aaa or abcabc. A repeat needs
+/// as many guesses as its block, times the number of repetitions.
+/// Only the shortest repeated block starting at each position is matched.
+fn repeat_matches(chars: &[char], user_inputs: &[&str], matches: &mut Vec<Match>) {
+    let mut i = 0;
+    while i < chars.len() {
+        let repeat = (1..=(chars.len() - i) / 2).find_map(|len| {
+            let block = &chars[i..i + len];
+            let mut count = 1;
+            while i + (count + 1) * len <= chars.len() && &chars[i + count * len..i + (count + 1) * len] == block {
+                count += 1;
+            }
+            (count >= 3 || (count == 2 && len > 1)).then_some((len, count))
+        });
+        let Some((len, count)) = repeat else {
+            i += 1;
+            continue;
+        };
+
+        let (block_guesses, _) = most_guessable(&chars[i..i + len], user_inputs);
+        matches.push(Match {
+            i,
+            j: i + count * len - 1,
+            guesses: block_guesses * count as f64,
+            pattern: Pattern::Repeat,
+            uppercase: false,
+            leet: false,
+        });
+        // Later parts of the same repeat would only be weaker matches
+        i += len * (count - 1);
+    }
+}
+
+/// Runs of neighbouring keys on a QWERTY keyboard, like qwer or lkjh
+fn keyboard_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let position = |c: char| {
+        let c = c.to_ascii_lowercase();
+        KEYBOARD_ROWS.iter().enumerate().find_map(|(row, keys)| keys.chars().position(|key| key == c).map(|col| (row, col)))
+    };
+
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = start;
+        let mut turns = 0;
+        let mut direction = 0i64;
+        while end + 1 < chars.len() {
+            let (Some((row, col)), Some((next_row, next_col))) = (position(chars[end]), position(chars[end + 1])) else {
+                break;
+            };
+            let step = next_col as i64 - col as i64;
+            if row != next_row || step.abs() != 1 {
+                break;
+            }
+            if step != direction {
+                turns += 1;
+                direction = step;
+            }
+            end += 1;
+        }
+
+        let len = end - start + 1;
+        if len >= 3 {
+            // Possible starting keys, turns and their placement along the run
+            let mut guesses = 0.0;
+            for i in 2..=len {
+                for j in 1..=turns.min(i - 1) {
+                    guesses += binomial(i - 1, j - 1) * KEYBOARD_KEYS * KEYBOARD_DEGREE.powi(j as i32);
+                }
+            }
+            let uppercase = uppercase_variations(&chars[start..=end]);
+            matches.push(Match {
+                i: start, j: end,
+                guesses: guesses * uppercase,
+                pattern: Pattern::Keyboard,
+                uppercase: uppercase > 1.0,
+                leet: false,
+            });
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+/// Years (1900-2050) and dates of day, month and year in any order,
+/// written without separators or with . - / or space
+fn date_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let reference = chrono::Local::now().year() as f64;
+    for i in 0..chars.len() {
+        for j in (i + 3)..chars.len().min(i + 10) {
+            let token: String = chars[i..=j].iter().collect();
+            let Some((year, separator)) = parse_date(&token) else {
+                continue;
+            };
+
+            let year_space = (year as f64 - reference).abs().max(MIN_YEAR_SPACE);
+            let mut guesses = if token.len() == 4 { year_space } else { year_space * 365.0 };
+            if separator {
+                guesses *= 4.0;
+            }
+            matches.push(Match { i, j, guesses, pattern: Pattern::Date, uppercase: false, leet: false });
+        }
+    }
+}
+
+/// Year of the date, and whether it has separators
+fn parse_date(token: &str) -> Option<(i32, bool)> {
+    let is_year = |year: i32| (1900..=2050).contains(&year);
+    if token.len() == 4 && token.chars().all(|c| c.is_ascii_digit()) {
+        let year = token.parse().ok()?;
+        return is_year(year).then_some((year, false));
+    }
+
+    let separators: Vec<char> = token.chars().filter(|c| !c.is_ascii_digit()).collect();
+    let parts: Vec<&str> = match separators.as_slice() {
+        [] => Vec::new(),
+        [a, b] if a == b && ".-/ ".contains(*a) => token.split(*a).collect(),
+        _ => return None,
+    };
+
+    // Without separators try every split into day, month and year
+    let splits: Vec<[&str; 3]> = if parts.is_empty() {
+        let n = token.len();
+        if !(6..=8).contains(&n) {
+            return None;
+        }
+        let mut splits = Vec::new();
+        for a in 1..n - 1 {
+            for b in a + 1..n {
+                splits.push([&token[..a], &token[a..b], &token[b..]]);
+            }
+        }
+        splits
+    } else {
+        vec![[parts[0], parts[1], parts[2]]]
+    };
+
+    for [a, b, c] in splits {
+        let numbers: Option<Vec<i32>> = [a, b, c].iter().map(|part| {
+            if part.is_empty() || part.len() > 4 { None } else { part.parse().ok() }
+        }).collect();
+        let Some(numbers) = numbers else { continue };
+
+        // Year first or last, day and month in either order
+        for (year_part, year, day, month) in [(c, numbers[2], numbers[0], numbers[1]), (c, numbers[2], numbers[1], numbers[0]), (a, numbers[0], numbers[2], numbers[1])] {
+            let year = match year_part.len() {
+                2 if year < 50 => 2000 + year,
+                2 => 1900 + year,
+                4 => year,
+                _ => continue,
+            };
+            if is_year(year) && (1..=31).contains(&day) && (1..=12).contains(&month) {
+                return Some((year, !parts.is_empty()));
+            }
+        }
+    }
+    None
+}
+
+fn feedback(score: u8, sequence: &[Match], length: usize) -> (Option<String>, Vec<String>) {
+    if score > 2 {
+        return (None, Vec::new());
+    }
+
+    let mut suggestions = vec![String::from("Dodaj jedno albo dwa słowa, najlepiej nietypowe.")];
+    let Some(longest) = sequence.iter().filter(|m| m.pattern != Pattern::Bruteforce).max_by_key(|m| m.j - m.i) else {
+        if length < 12 {
+            suggestions.push(String::from("Użyj dłuższego hasła, np. frazy z generate --words 6."));
+        }
+        return (None, suggestions);
+    };
+
+    let whole = sequence.len() == 1;
+    let warning = match longest.pattern {
+        Pattern::CommonPassword { rank } if whole && rank <= 10 => "To jedno z 10 najpopularniejszych haseł.",
+        Pattern::CommonPassword { .. } if whole => "To bardzo popularne hasło.",
+        Pattern::CommonPassword { .. } => "Hasło zawiera bardzo popularne hasło.",
+        Pattern::Word if whole => "Pojedyncze słowo łatwo odgadnąć.",
+        Pattern::Word => "Hasło opiera się na popularnym słowie.",
+        Pattern::UserInput => "Hasło zawiera etykietę albo nazwę użytkownika.",
+        Pattern::Sequence => "Ciągi takie jak abc albo 6543 łatwo odgadnąć.",
+        Pattern::Repeat => "Powtórzenia takie jak aaa albo abcabc łatwo odgadnąć.",
+        Pattern::Keyboard => "Sąsiednie klawisze na klawiaturze łatwo odgadnąć.",
+        Pattern::Date => "Daty i lata łatwo odgadnąć.",
+        Pattern::Bruteforce => return (None, suggestions),
+    };
+
+    match longest.pattern {
+        Pattern::Date => suggestions.push(String::from("Unikaj dat i lat, które są z tobą związane.")),
+        Pattern::Sequence | Pattern::Repeat | Pattern::Keyboard => suggestions.push(String::from("Unikaj powtórzeń, ciągów i układów klawiszy.")),
+        _ => {}
+    }
+    if longest.uppercase {
+        suggestions.push(String::from("Wielkie litery niewiele pomagają."));
+    }
+    if longest.leet {
+        suggestions.push(String::from("Zamiany takie jak @ zamiast a niewiele pomagają."));
+    }
+
+    (Some(String::from(warning)), suggestions)
+}
+
+fn display_time(seconds: f64) -> String {
+    const MINUTE: f64 = 60.0;
+    const HOUR: f64 = MINUTE * 60.0;
+    const DAY: f64 = HOUR * 24.0;
+    const MONTH: f64 = DAY * 30.44;
+    const YEAR: f64 = DAY * 365.25;
+
+    let (value, forms) = match seconds {
+        s if s < 1.0 => return String::from("mniej niż sekunda"),
+        s if s < MINUTE => (s, ["sekunda", "sekundy", "sekund"]),
+        s if s < HOUR => (s / MINUTE, ["minuta", "minuty", "minut"]),
+        s if s < DAY => (s / HOUR, ["godzina", "godziny", "godzin"]),
+        s if s < MONTH => (s / DAY, ["dzień", "dni", "dni"]),
+        s if s < YEAR => (s / MONTH, ["miesiąc", "miesiące", "miesięcy"]),
+        s if s < YEAR * 100.0 => (s / YEAR, ["rok", "lata", "lat"]),
+        _ => return String::from("wieki"),
+    };
+
+    let n = value.round() as u64;
+    let form = if n == 1 {
+        forms[0]
+    } else if (2..=4).contains(&(n % 10)) && !(12..=14).contains(&(n % 100)) {
+        forms[1]
+    } else {
+        forms[2]
+    };
+    format!("{} {}", n, form)
+}
+
+fn factorial(n: usize) -> f64 {
+    (1..=n).map(|i| i as f64).product()
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (0..k).map(|i| (n - i) as f64 / (i + 1) as f64).product()
+}
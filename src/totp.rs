@@ -0,0 +1,165 @@
+use std::fmt;
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use crate::error::{Error, Result};
+
+const URI_PREFIX: &str = "otpauth://totp/";
+
+/// Hash function used by HMAC
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TotpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl fmt::Display for TotpAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TotpAlgorithm::Sha1 => write!(f, "SHA1"),
+            TotpAlgorithm::Sha256 => write!(f, "SHA256"),
+            TotpAlgorithm::Sha512 => write!(f, "SHA512"),
+        }
+    }
+}
+
+/// Time-based one-time password generator (RFC 6238)
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Totp {
+    secret: Vec<u8>,
+    pub algorithm: TotpAlgorithm,
+    pub digits: u32,
+    /// Seconds each code is valid for
+    pub period: u64,
+    pub issuer: Option<String>,
+}
+
+impl Totp {
+    /// Creates generator from a raw secret. Digits must be 6 to 10, period can't be 0.
+    pub fn new(secret: Vec<u8>, algorithm: TotpAlgorithm, digits: u32, period: u64) -> Result<Totp> {
+        if secret.is_empty() {
+            return Err(Error::Totp(String::from("pusty sekret")));
+        }
+        if !(6..=10).contains(&digits) {
+            return Err(Error::Totp(format!("nieobsługiwana liczba cyfr: {}", digits)));
+        }
+        if period == 0 {
+            return Err(Error::Totp(String::from("okres musi być dodatni")));
+        }
+
+        Ok(Totp { secret, algorithm, digits, period, issuer: None })
+    }
+
+    /// Parses an `otpauth://totp/` URI or a bare base32 secret (with default SHA1, 6 digits and 30 s)
+    pub fn parse(input: &str) -> Result<Totp> {
+        let input = input.trim();
+        if input.starts_with("otpauth://") {
+            Totp::from_uri(input)
+        } else {
+            Totp::new(decode_secret(input)?, TotpAlgorithm::Sha1, 6, 30)
+        }
+    }
+
+    /// Parses a key URI in the format used by authenticator apps:
+    /// `otpauth://totp/Issuer:account?secret=BASE32&issuer=Issuer&algorithm=SHA1&digits=6&period=30`
+    pub fn from_uri(uri: &str) -> Result<Totp> {
+        let rest = uri.strip_prefix(URI_PREFIX)
+            .ok_or_else(|| Error::Totp(String::from("obsługiwane są tylko adresy otpauth://totp/")))?;
+        let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+        let mut secret = None;
+        let mut algorithm = TotpAlgorithm::Sha1;
+        let mut digits = 6;
+        let mut period = 30;
+        // Issuer from the label is used if there is no issuer parameter
+        let mut issuer = percent_decode(label)?.split_once(':').map(|(issuer, _)| issuer.trim().to_string());
+
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value)?;
+            match key.to_ascii_lowercase().as_str() {
+                "secret" => secret = Some(decode_secret(&value)?),
+                "algorithm" => algorithm = match value.to_ascii_uppercase().as_str() {
+                    "SHA1" => TotpAlgorithm::Sha1,
+                    "SHA256" => TotpAlgorithm::Sha256,
+                    "SHA512" => TotpAlgorithm::Sha512,
+                    _ => return Err(Error::Totp(format!("nieobsługiwany algorytm {}", value))),
+                },
+                "digits" => digits = value.parse().map_err(|_| Error::Totp(format!("niepoprawna liczba cyfr {}", value)))?,
+                "period" => period = value.parse().map_err(|_| Error::Totp(format!("niepoprawny okres {}", value)))?,
+                "issuer" => issuer = Some(value),
+                // Other parameters (e.g. image) don't affect the codes
+                _ => {}
+            }
+        }
+
+        let secret = secret.ok_or_else(|| Error::Totp(String::from("brak parametru secret")))?;
+        let mut totp = Totp::new(secret, algorithm, digits, period)?;
+        totp.issuer = issuer.filter(|issuer| !issuer.is_empty());
+        Ok(totp)
+    }
+
+    /// Code valid at given unix time
+    pub fn code_at(&self, unix_time: u64) -> String {
+        let counter = unix_time / self.period;
+        let hash = self.hmac(&counter.to_be_bytes());
+
+        // Dynamic truncation from RFC 4226
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([hash[offset], hash[offset + 1], hash[offset + 2], hash[offset + 3]]) & 0x7fff_ffff;
+        let code = binary as u64 % 10u64.pow(self.digits);
+
+        format!("{:0width$}", code, width = self.digits as usize)
+    }
+
+    /// Seconds until the code valid at given unix time changes
+    pub fn remaining(&self, unix_time: u64) -> u64 {
+        self.period - unix_time % self.period
+    }
+
+    fn hmac(&self, message: &[u8]) -> Vec<u8> {
+        match self.algorithm {
+            TotpAlgorithm::Sha1 => mac::<Hmac<Sha1>>(&self.secret, message),
+            TotpAlgorithm::Sha256 => mac::<Hmac<Sha256>>(&self.secret, message),
+            TotpAlgorithm::Sha512 => mac::<Hmac<Sha512>>(&self.secret, message),
+        }
+    }
+}
+
+fn mac<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Decodes base32 secret, ignoring case, spaces and padding
+fn decode_secret(secret: &str) -> Result<Vec<u8>> {
+    let normalized: String = secret.chars()
+        .filter(|c| !c.is_whitespace() && *c != '=' && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    BASE32_NOPAD.decode(normalized.as_bytes()).map_err(|e| Error::Totp(format!("sekret nie jest w base32: {}", e)))
+}
+
+/// Decodes %XX escapes in a URI component
+fn percent_decode(input: &str) -> Result<String> {
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut rest = input.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail.get(..2).and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| Error::Totp(String::from("niepoprawne kodowanie adresu")))?;
+            bytes.push(hex);
+            rest = &tail[2..];
+        } else {
+            bytes.push(if byte == b'+' { b' ' } else { byte });
+            rest = tail;
+        }
+    }
+
+    String::from_utf8(bytes).map_err(|_| Error::Totp(String::from("niepoprawne kodowanie adresu")))
+}
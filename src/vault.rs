@@ -0,0 +1,520 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+use crate::atomic::{write_atomic, write_private};
+use crate::auth::*;
+use crate::backup::{backup_file, list_backups, Backup};
+use crate::container::{self, Header};
+use crate::error::{Error, Result};
+use crate::folders::{join_label, relative_label, SEPARATOR};
+use crate::kdbx::{composite_key, is_kdbx};
+use crate::keepass::KeePass;
+use crate::password_storage::*;
+use crate::totp::Totp;
+
+// Relative to the system's data directory
+const DEFAULT_VAULT_FILE: &str = "passman_data/vault.bin";
+
+/// Vault file in the system's data directory, used when no other path is given
+pub fn default_vault_path() -> Result<PathBuf> {
+    let dir = dirs::data_dir()
+        .ok_or_else(|| Error::Io(io::Error::new(io::ErrorKind::NotFound, "nie znaleziono systemowego folderu danych")))?;
+    Ok(dir.join(DEFAULT_VAULT_FILE))
+}
+
+/// Unlocked vault. Keeps the master key for as long as it lives,
+/// the key is zeroed when the vault is dropped.
+///
+/// Changes made through the methods stay in memory until `save` is called,
+/// except for `change_password`, `change_kdf` and `restore_backup`,
+/// which write the file themselves.
+///
+/// The file can also be a KeePass (KDBX 4) database. Its entries are then kept
+/// encrypted with a random key and written back into the database on save.
+pub struct Vault {
+    path: PathBuf,
+    storage: Storage,
+    master_key: Zeroizing<[u8; 32]>,
+    entries: Entries,
+    trash: Trash,
+    // Set after the first backup, so a backup holds the state from before this session
+    backed_up: bool,
+    read_only: bool,
+}
+
+/// Format of the vault file
+enum Storage {
+    Passman(Header),
+    KeePass(Box<KeePass>),
+}
+
+impl Vault {
+    /// Checks if there is a vault at path, in the current or the old format
+    pub fn exists(path: &Path) -> bool {
+        path.exists() || legacy_dir(path).is_some_and(legacy_profile_exists)
+    }
+
+    /// True if there are only files of the old format at path, see `migrate_legacy`
+    pub fn needs_migration(path: &Path) -> bool {
+        !path.exists() && legacy_dir(path).is_some_and(legacy_profile_exists)
+    }
+
+    /// Creates an empty vault file at path, encrypted with password
+    pub fn create(path: impl Into<PathBuf>, password: &str) -> Result<Vault> {
+        Vault::create_with_kdf(path, password, KdfParams::default())
+    }
+
+    /// Creates an empty vault file at path, with the key derived from password
+    /// using given KDF parameters instead of the default ones
+    pub fn create_with_kdf(path: impl Into<PathBuf>, password: &str, kdf: KdfParams) -> Result<Vault> {
+        let path = path.into();
+        if Vault::exists(&path) {
+            return Err(Error::Io(io::Error::new(io::ErrorKind::AlreadyExists, format!("sejf {} już istnieje", path.display()))));
+        }
+        kdf.argon2()?;
+
+        let header = Header { kdf, salt: generate_salt()?.to_vec() };
+        let master_key = derive_master_key(password, &header.salt, &header.kdf)?;
+        let mut vault = Vault::new(path, Storage::Passman(header), master_key, Entries::new(), Trash::new());
+        vault.save()?;
+
+        Ok(vault)
+    }
+
+    /// Unlocks the vault file at path with password. KeePass databases are recognized by their contents.
+    pub fn open(path: impl Into<PathBuf>, password: &str) -> Result<Vault> {
+        let path = path.into();
+        let data = fs::read(&path)?;
+        if is_kdbx(&data) {
+            let master_key = generate_key()?;
+            let (keepass, entries, trash) = KeePass::open(&data, composite_key(password), master_key.as_ref())?;
+            return Ok(Vault::new(path, Storage::KeePass(Box::new(keepass)), master_key, entries, trash));
+        }
+
+        let header = container::read_header(&data)?;
+        let master_key = derive_master_key(password, &header.salt, &header.kdf)?;
+        let (entries, trash) = decrypt_entries(&data, master_key.as_ref())?;
+
+        Ok(Vault::new(path, Storage::Passman(header), master_key, entries, trash))
+    }
+
+    fn new(path: PathBuf, storage: Storage, master_key: Zeroizing<[u8; 32]>, entries: Entries, trash: Trash) -> Vault {
+        Vault { path, storage, master_key, entries, trash, backed_up: false, read_only: false }
+    }
+
+    /// Moves salt, verification token and plaintext vault of the old format
+    /// into a single encrypted vault file at path. The old format had no other
+    /// location, so path has to be the default one.
+    /// Needs master password to encrypt it.
+    pub fn migrate_legacy(path: impl Into<PathBuf>, password: &str) -> Result<Vault> {
+        let path = path.into();
+        let dir = legacy_dir(&path)
+            .ok_or_else(|| Error::Io(io::Error::new(io::ErrorKind::NotFound, "pliki starego formatu są tylko w domyślnej lokalizacji")))?
+            .to_path_buf();
+        let salt = load_legacy_salt(&dir)?;
+
+        // Old files were always created with default parameters
+        let header = Header { kdf: KdfParams::default(), salt: salt.to_vec() };
+        let master_key = derive_master_key(password, &header.salt, &header.kdf)?;
+
+        if !verify_legacy_master_key(&dir, master_key.as_ref())? {
+            return Err(Error::WrongPassword);
+        }
+
+        let entries = load_legacy_entries(&dir)?;
+        let mut vault = Vault::new(path, Storage::Passman(header), master_key, entries, Trash::new());
+        vault.save()?;
+
+        // Old files are removed only after the new one is written
+        remove_legacy_entries(&dir)?;
+        remove_legacy_files(&dir)?;
+
+        Ok(vault)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// True if the file is a KeePass database
+    pub fn is_keepass(&self) -> bool {
+        matches!(self.storage, Storage::KeePass(_))
+    }
+
+    /// Makes `save` and all changes written right away fail with `Error::ReadOnly`
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Key derivation parameters the vault is currently encrypted with.
+    /// Fails for KeePass databases using AES-KDF instead of Argon2.
+    pub fn kdf(&self) -> Result<KdfParams> {
+        match &self.storage {
+            Storage::Passman(header) => Ok(header.kdf),
+            Storage::KeePass(keepass) => keepass.file().kdf(),
+        }
+    }
+
+    /// Decrypts password with specified label
+    pub fn get(&self, label: &str) -> Result<String> {
+        self.entry(label)?.password(self.master_key.as_ref())
+    }
+
+    /// Entry with specified label, to read its metadata
+    pub fn entry(&self, label: &str) -> Result<&Entry> {
+        self.entries.get(label).ok_or(Error::LabelNotFound)
+    }
+
+    /// Entry with specified label, to change its metadata
+    pub fn entry_mut(&mut self, label: &str) -> Result<&mut Entry> {
+        self.entries.get_mut(label).ok_or(Error::LabelNotFound)
+    }
+
+    pub fn contains(&self, label: &str) -> bool {
+        self.entries.contains_key(label)
+    }
+
+    /// Decrypts notes of the entry, None if it has none
+    pub fn notes(&self, label: &str) -> Result<Option<String>> {
+        self.entry(label)?.notes(self.master_key.as_ref())
+    }
+
+    pub fn set_notes(&mut self, label: &str, notes: Option<&str>) -> Result<()> {
+        let master_key = self.master_key.clone();
+        let entry = self.entry_mut(label)?;
+        entry.set_notes(notes, master_key.as_ref())?;
+        entry.touch();
+        Ok(())
+    }
+
+    /// TOTP generator of the entry, None if it has no secret
+    pub fn totp(&self, label: &str) -> Result<Option<Totp>> {
+        self.entry(label)?.totp(self.master_key.as_ref())?.map(|totp| Totp::parse(&totp)).transpose()
+    }
+
+    /// Sets TOTP secret of the entry - otpauth URI or base32 secret, None removes it
+    pub fn set_totp(&mut self, label: &str, totp: Option<&str>) -> Result<()> {
+        // Check it before saving, so only valid secrets get to the vault
+        if let Some(totp) = totp {
+            Totp::parse(totp)?;
+        }
+
+        let master_key = self.master_key.clone();
+        let entry = self.entry_mut(label)?;
+        entry.set_totp(totp.map(str::trim), master_key.as_ref())?;
+        entry.touch();
+        Ok(())
+    }
+
+    /// Decrypts TOTP secret of the entry as it was given, None if it has none
+    pub fn totp_secret(&self, label: &str) -> Result<Option<String>> {
+        self.entry(label)?.totp(self.master_key.as_ref())
+    }
+
+    /// Replaces password of the entry. The previous one is kept in its history,
+    /// which holds at most history_depth passwords. Fails with PasswordUnchanged if it is the same.
+    pub fn set_password(&mut self, label: &str, password: &str, history_depth: usize) -> Result<()> {
+        let master_key = self.master_key.clone();
+        self.entry_mut(label)?.set_password(password, history_depth, master_key.as_ref())
+    }
+
+    /// Previous passwords of the entry, oldest first
+    pub fn history(&self, label: &str) -> Result<&[HistoryItem]> {
+        Ok(self.entry(label)?.history())
+    }
+
+    /// Makes a password from the history of the entry current again, index 0 is the oldest.
+    /// The current one goes to the history.
+    pub fn restore_password(&mut self, label: &str, index: usize, history_depth: usize) -> Result<()> {
+        let master_key = self.master_key.clone();
+        self.entry_mut(label)?.restore_password(index, history_depth, master_key.as_ref())
+    }
+
+    /// Encrypts password and adds it as a new entry. Returns the entry,
+    /// so metadata can be filled in before saving.
+    pub fn insert(&mut self, label: &str, password: &str) -> Result<&mut Entry> {
+        if self.contains(label) {
+            return Err(Error::LabelExists);
+        }
+
+        let entry = Entry::new(password, self.master_key.as_ref())?;
+        Ok(self.entries.entry(label.to_string()).insert_entry(entry).into_mut())
+    }
+
+    /// Moves entry with specified label to the trash
+    pub fn remove(&mut self, label: &str) -> Result<()> {
+        let entry = self.entries.remove(label).ok_or(Error::LabelNotFound)?;
+        let id = match &mut self.storage {
+            Storage::Passman(_) => None,
+            Storage::KeePass(keepass) => keepass.removed(label),
+        };
+        self.trash.push(TrashedEntry::new(label, now(), entry, id));
+        Ok(())
+    }
+
+    /// Removed entries, oldest first
+    pub fn trash(&self) -> &[TrashedEntry] {
+        &self.trash
+    }
+
+    /// Takes the most recently removed entry with label out of the trash and puts it
+    /// back under the same label. Fails if the label is used by another entry now.
+    pub fn restore_trashed(&mut self, label: &str) -> Result<()> {
+        if self.contains(label) {
+            return Err(Error::LabelExists);
+        }
+        let index = self.trash.iter().rposition(|trashed| trashed.label == label).ok_or(Error::LabelNotFound)?;
+
+        let trashed = self.trash.remove(index);
+        if let Storage::KeePass(keepass) = &mut self.storage {
+            keepass.restored(label, trashed.id.clone());
+        }
+        self.entries.insert(label.to_string(), trashed.into_entry());
+        Ok(())
+    }
+
+    /// Deletes entries removed before the unix timestamp for good, returns how many there were
+    pub fn purge_trash(&mut self, before: u64) -> usize {
+        let count = self.trash.len();
+        self.trash.retain(|trashed| trashed.deleted >= before);
+        count - self.trash.len()
+    }
+
+    /// Changes label of the entry, fails if the new one is already used
+    pub fn rename(&mut self, label: &str, new_label: &str) -> Result<()> {
+        if !self.contains(label) {
+            return Err(Error::LabelNotFound);
+        }
+        if label == new_label {
+            return Ok(());
+        }
+        if self.contains(new_label) {
+            return Err(Error::LabelExists);
+        }
+
+        let mut entry = self.entries.remove(label).ok_or(Error::LabelNotFound)?;
+        entry.touch();
+        self.entries.insert(new_label.to_string(), entry);
+        if let Storage::KeePass(keepass) = &mut self.storage {
+            keepass.renamed(&[(label.to_string(), new_label.to_string())]);
+        }
+        Ok(())
+    }
+
+    /// Moves all entries of the folder and its subfolders to new_folder, the rest of their
+    /// labels stays the same. Fails without moving anything if one of the new labels is used.
+    /// Returns the number of moved entries.
+    pub fn rename_folder(&mut self, folder: &str, new_folder: &str) -> Result<usize> {
+        if folder.trim_matches(SEPARATOR).is_empty() {
+            return Err(Error::FolderNotFound);
+        }
+        let renames: Vec<(String, String)> = self.entries.keys()
+            .filter_map(|label| Some((label.clone(), join_label(new_folder, relative_label(label, folder)?))))
+            .collect();
+        if renames.is_empty() {
+            return Err(Error::FolderNotFound);
+        }
+        // Labels of moved entries are free, even if another moved entry gets one of them
+        if renames.iter().any(|(_, new_label)| self.contains(new_label) && relative_label(new_label, folder).is_none()) {
+            return Err(Error::LabelExists);
+        }
+
+        let moved: Vec<(String, Entry)> = renames.iter()
+            .filter_map(|(label, new_label)| Some((new_label.clone(), self.entries.remove(label)?)))
+            .collect();
+        for (new_label, mut entry) in moved {
+            entry.touch();
+            self.entries.insert(new_label, entry);
+        }
+        if let Storage::KeePass(keepass) = &mut self.storage {
+            keepass.renamed(&renames);
+        }
+        Ok(renames.len())
+    }
+
+    /// Labels of all entries, in no particular order
+    pub fn iter_labels(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Checks if password is the master password of this vault
+    pub fn check_password(&self, password: &str) -> Result<bool> {
+        match &self.storage {
+            Storage::Passman(header) => Ok(derive_master_key(password, &header.salt, &header.kdf)? == self.master_key),
+            Storage::KeePass(keepass) => keepass.file().check_password(password),
+        }
+    }
+
+    /// Re-encrypts the vault with a new master password and saves it.
+    /// KDF parameters stay the same.
+    pub fn change_password(&mut self, new_password: &str) -> Result<()> {
+        let mut keepass = match &self.storage {
+            Storage::Passman(header) => return self.rekey(new_password, header.kdf),
+            Storage::KeePass(keepass) => keepass.clone(),
+        };
+
+        // Entries stay encrypted with the same random key, only the file changes
+        keepass.file_mut().set_password(new_password)?;
+        let data = keepass.seal(&self.entries, &self.trash, self.master_key.as_ref())?;
+        self.write(&data, true)?;
+        self.storage = Storage::KeePass(keepass);
+        Ok(())
+    }
+
+    /// Re-encrypts the vault with a key derived using new KDF parameters and saves it.
+    /// Needs the master password again, as the new key can't be derived without it.
+    pub fn change_kdf(&mut self, password: &str, kdf: KdfParams) -> Result<()> {
+        if self.is_keepass() {
+            return Err(Error::Kdbx(String::from("parametry KDF bazy KeePass można zmienić w KeePassXC")));
+        }
+        kdf.argon2()?;
+        if !self.check_password(password)? {
+            return Err(Error::WrongPassword);
+        }
+
+        self.rekey(password, kdf)
+    }
+
+    /// Writes a copy of the vault encrypted with another password to a new file,
+    /// readable only by its owner. The copy is a regular vault file with the same
+    /// KDF parameters, so it can be opened like any other vault.
+    pub fn export_encrypted(&self, path: &Path, password: &str) -> Result<()> {
+        // Copy of a KeePass database is a passman vault, with default parameters if it used AES-KDF
+        let (header, master_key, entries, trash) = self.reencrypted(password, self.kdf().unwrap_or_default())?;
+        let data = encrypt_entries(&entries, &trash, master_key.as_ref(), &header)?;
+        write_private(path, &data)?;
+        Ok(())
+    }
+
+    /// Encrypts the vault and writes it to its file
+    pub fn save(&mut self) -> Result<()> {
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+
+        let data = self.sealed()?;
+        self.write(&data, false)
+    }
+
+    /// Saves a change of only the times entries were last used. No backup is taken,
+    /// so such saves don't push the backups of real changes out.
+    pub fn save_usage(&mut self) -> Result<()> {
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+
+        let data = self.sealed()?;
+        write_atomic(&self.path, &data)?;
+        Ok(())
+    }
+
+    /// Contents of the vault file
+    fn sealed(&mut self) -> Result<Vec<u8>> {
+        match &mut self.storage {
+            Storage::Passman(header) => encrypt_entries(&self.entries, &self.trash, self.master_key.as_ref(), header),
+            Storage::KeePass(keepass) => keepass.seal(&self.entries, &self.trash, self.master_key.as_ref()),
+        }
+    }
+
+    /// Backups of the vault file, newest first
+    pub fn backups(&self) -> Vec<Backup> {
+        list_backups(&self.path)
+    }
+
+    /// Replaces the vault with a backup. The backup is decrypted with the current key,
+    /// or with password if given (for backups from before master password or KDF changed).
+    /// Fails with WrongPassword if the key doesn't match. Current file is backed up first.
+    pub fn restore_backup(&mut self, backup: &Backup, password: Option<&str>) -> Result<()> {
+        let data = fs::read(&backup.path)?;
+        if let Storage::KeePass(keepass) = &self.storage {
+            let composite_key = match password {
+                Some(password) => composite_key(password),
+                None => keepass.file().composite_key(),
+            };
+            let master_key = generate_key()?;
+            let (keepass, entries, trash) = KeePass::open(&data, composite_key, master_key.as_ref())?;
+
+            self.write(&data, true)?;
+            self.storage = Storage::KeePass(Box::new(keepass));
+            self.master_key = master_key;
+            self.entries = entries;
+            self.trash = trash;
+            return Ok(());
+        }
+
+        let header = container::read_header(&data)?;
+        let master_key = match password {
+            Some(password) => derive_master_key(password, &header.salt, &header.kdf)?,
+            None => self.master_key.clone(),
+        };
+        let (entries, trash) = decrypt_entries(&data, master_key.as_ref())?;
+
+        self.write(&data, true)?;
+        self.storage = Storage::Passman(header);
+        self.master_key = master_key;
+        self.entries = entries;
+        self.trash = trash;
+        Ok(())
+    }
+
+    /// Changes encryption of all passwords and of the vault file to the key
+    /// derived from password with given KDF parameters
+    fn rekey(&mut self, password: &str, kdf: KdfParams) -> Result<()> {
+        let (header, master_key, entries, trash) = self.reencrypted(password, kdf)?;
+
+        // Previous file can only be opened with the old key, so always keep a copy of it
+        let data = encrypt_entries(&entries, &trash, master_key.as_ref(), &header)?;
+        self.write(&data, true)?;
+
+        self.storage = Storage::Passman(header);
+        self.master_key = master_key;
+        self.entries = entries;
+        self.trash = trash;
+        Ok(())
+    }
+
+    /// Copy of the entries and the trash encrypted with a key derived from password, with
+    /// the new header and key. Working on a copy means a failure in the middle doesn't leave
+    /// entries encrypted with different keys.
+    fn reencrypted(&self, password: &str, kdf: KdfParams) -> Result<(Header, Zeroizing<[u8; 32]>, Entries, Trash)> {
+        // New key always gets new salt
+        let header = Header { kdf, salt: generate_salt()?.to_vec() };
+        let master_key = derive_master_key(password, &header.salt, &header.kdf)?;
+
+        let mut entries = self.entries.clone();
+        for entry in entries.values_mut() {
+            entry.reencrypt(self.master_key.as_ref(), master_key.as_ref())?;
+        }
+        let mut trash = self.trash.clone();
+        for trashed in &mut trash {
+            trashed.reencrypt(self.master_key.as_ref(), master_key.as_ref())?;
+        }
+        Ok((header, master_key, entries, trash))
+    }
+
+    /// Writes contents of the vault file. Previous version is backed up once per
+    /// opened vault, unless force_backup is set (for changes which re-encrypt whole vault).
+    fn write(&mut self, data: &[u8], force_backup: bool) -> Result<()> {
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+        if force_backup || !self.backed_up {
+            backup_file(&self.path)?;
+            self.backed_up = true;
+        }
+
+        write_atomic(&self.path, data)?;
+        Ok(())
+    }
+}
+
+/// Directory with files of the old format, which were only ever written next to
+/// the default vault. Files with these names elsewhere belong to something else.
+fn legacy_dir(path: &Path) -> Option<&Path> {
+    default_vault_path().ok().filter(|default| default == path).and_then(|_| path.parent())
+}
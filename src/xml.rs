@@ -0,0 +1,179 @@
+use crate::error::{Error, Result};
+
+/// Editable XML element. roxmltree only reads documents, KeePass databases
+/// have to be changed and written back with everything passman doesn't know about.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    pub fn new(name: &str) -> Element {
+        Element { name: name.to_string(), attributes: Vec::new(), children: Vec::new() }
+    }
+
+    /// Element with a single text child
+    pub fn with_text(name: &str, text: &str) -> Element {
+        let mut element = Element::new(name);
+        element.set_text(text);
+        element
+    }
+
+    /// Parses a document and returns its root element. Comments and
+    /// processing instructions are dropped.
+    pub fn parse(text: &str) -> Result<Element> {
+        let document = roxmltree::Document::parse(text).map_err(|e| Error::Kdbx(e.to_string()))?;
+        Ok(Element::from_node(document.root_element()))
+    }
+
+    fn from_node(node: roxmltree::Node) -> Element {
+        let mut element = Element::new(node.tag_name().name());
+        element.attributes = node.attributes().map(|a| (a.name().to_string(), a.value().to_string())).collect();
+        for child in node.children() {
+            if child.is_element() {
+                element.children.push(Node::Element(Element::from_node(child)));
+            } else if child.is_text() {
+                element.children.push(Node::Text(child.text().unwrap_or_default().to_string()));
+            }
+        }
+        element
+    }
+
+    /// Whole document with the XML declaration
+    pub fn to_document(&self) -> String {
+        let mut output = String::from("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n");
+        self.write(&mut output);
+        output
+    }
+
+    fn write(&self, output: &mut String) {
+        output.push('<');
+        output.push_str(&self.name);
+        for (name, value) in &self.attributes {
+            output.push_str(&format!(" {}=\"{}\"", name, escape(value, true)));
+        }
+        if self.children.is_empty() {
+            output.push_str("/>");
+            return;
+        }
+
+        output.push('>');
+        for child in &self.children {
+            match child {
+                Node::Element(element) => element.write(output),
+                Node::Text(text) => output.push_str(&escape(text, false)),
+            }
+        }
+        output.push_str("</");
+        output.push_str(&self.name);
+        output.push('>');
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_attribute(&mut self, name: &str, value: &str) {
+        match self.attributes.iter_mut().find(|(key, _)| key == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.attributes.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    pub fn elements_mut(&mut self) -> impl Iterator<Item = &mut Element> {
+        self.children.iter_mut().filter_map(|child| match child {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    /// First child element with given name
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    pub fn child_mut(&mut self, name: &str) -> Option<&mut Element> {
+        self.elements_mut().find(|element| element.name == name)
+    }
+
+    /// First child element with given name, added at the end if there is none
+    pub fn child_or_insert(&mut self, name: &str) -> &mut Element {
+        if self.child(name).is_none() {
+            self.push(Element::new(name));
+        }
+        self.child_mut(name).expect("child was just added")
+    }
+
+    pub fn push(&mut self, element: Element) {
+        self.children.push(Node::Element(element));
+    }
+
+    /// Removes child elements for which keep returns false
+    pub fn retain(&mut self, mut keep: impl FnMut(&Element) -> bool) {
+        self.children.retain(|child| match child {
+            Node::Element(element) => keep(element),
+            Node::Text(_) => true,
+        });
+    }
+
+    /// Text of the element, empty if it has none
+    pub fn text(&self) -> String {
+        self.children.iter().filter_map(|child| match child {
+            Node::Text(text) => Some(text.as_str()),
+            Node::Element(_) => None,
+        }).collect()
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.children.retain(|child| matches!(child, Node::Element(_)));
+        if !text.is_empty() {
+            self.children.push(Node::Text(text.to_string()));
+        }
+    }
+
+    /// Text of the first child element with given name
+    pub fn child_text(&self, name: &str) -> Option<String> {
+        self.child(name).map(Element::text)
+    }
+
+    /// Calls f for this element and all elements inside it, in document order
+    pub fn visit_mut(&mut self, f: &mut impl FnMut(&mut Element) -> Result<()>) -> Result<()> {
+        f(self)?;
+        for child in self.elements_mut() {
+            child.visit_mut(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn escape(text: &str, attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            // Line breaks in attributes would be normalized to spaces when read back
+            '\n' if attribute => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use std::fs;
+use data_encoding::HEXUPPER;
+use passman::{audit, AuditOptions, Vault};
+use sha1::{Digest, Sha1};
+use common::{create_vault, temp_dir};
+
+const STRONG: &str = "wkv8-Qz3r-Lp0x-Ty7m-Hs2c";
+
+fn options() -> AuditOptions {
+    AuditOptions { min_score: 3, max_age_days: 365, breached: None }
+}
+
+/// Adds an entry with a user name and URL, which has no problems of its own
+fn insert(vault: &mut Vault, label: &str, password: &str) {
+    let entry = vault.insert(label, password).unwrap();
+    entry.username = Some(String::from("bob"));
+    entry.urls = vec![format!("https://{}.example.com", label)];
+}
+
+#[test]
+fn clean_vault() {
+    let dir = temp_dir("clean");
+    let mut vault = create_vault(&dir);
+    let report = audit(&vault, &options()).unwrap();
+    assert!(report.is_clean() && report.entries == 0);
+
+    insert(&mut vault, "github", STRONG);
+    insert(&mut vault, "bank", "Hj7#qL2!vN9$xR4@mT6%");
+    let report = audit(&vault, &options()).unwrap();
+    assert_eq!(report.entries, 2);
+    assert!(report.is_clean(), "{:?}", report);
+    assert!(report.breached.is_none());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn finds_every_category() {
+    let dir = temp_dir("categories");
+    let mut vault = create_vault(&dir);
+    insert(&mut vault, "github", STRONG);
+    insert(&mut vault, "gitlab", STRONG);
+    insert(&mut vault, "Bank", "Hj7#qL2!vN9$xR4@mT6%");
+    insert(&mut vault, "bank", "Zp5&wE8*cK1^yU3(fB0)");
+    insert(&mut vault, "forum", "password1");
+    vault.insert("mail", "Qm4)dS7_gV2+jA9=nW5[").unwrap().username = Some(String::new());
+    insert(&mut vault, "old", "Xr6]hF1{pC8}sD3;kG7:");
+    vault.entry_mut("old").unwrap().created = 1;
+
+    let report = audit(&vault, &options()).unwrap();
+    assert_eq!(report.entries, 7);
+    assert!(!report.is_clean());
+    assert_eq!(report.reused, [["github", "gitlab"]]);
+    assert_eq!(report.case_duplicates, [["Bank", "bank"]]);
+    let weak: Vec<&str> = report.weak.iter().map(|weak| weak.label.as_str()).collect();
+    assert_eq!(weak, ["forum"]);
+    assert!(report.weak[0].score < 3);
+    // Empty user name counts as missing
+    assert_eq!(report.missing_username, ["mail"]);
+    assert_eq!(report.missing_url, ["mail"]);
+    assert_eq!(report.old.len(), 1);
+    assert_eq!(report.old[0].label, "old");
+    assert!(report.old[0].age_days > 365);
+
+    // Zero turns the check of age off, lower minimum score accepts the weak password
+    let report = audit(&vault, &AuditOptions { min_score: 0, max_age_days: 0, breached: None }).unwrap();
+    assert!(report.weak.is_empty() && report.old.is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn changed_password_isnt_old() {
+    let dir = temp_dir("changed");
+    let mut vault = create_vault(&dir);
+    insert(&mut vault, "github", STRONG);
+    vault.entry_mut("github").unwrap().created = 1;
+    assert_eq!(audit(&vault, &options()).unwrap().old.len(), 1);
+
+    vault.set_password("github", "Hj7#qL2!vN9$xR4@mT6%", 5).unwrap();
+    assert!(audit(&vault, &options()).unwrap().old.is_empty());
+
+    // Entries with unknown time of creation have no age
+    insert(&mut vault, "bank", "Zp5&wE8*cK1^yU3(fB0)");
+    vault.entry_mut("bank").unwrap().created = 0;
+    let options = AuditOptions { max_age_days: 1, ..options() };
+    assert!(audit(&vault, &options).unwrap().old.is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn finds_breached_passwords() {
+    let dir = temp_dir("breached");
+    let mut vault = create_vault(&dir);
+    insert(&mut vault, "github", STRONG);
+    insert(&mut vault, "forum", "Hj7#qL2!vN9$xR4@mT6%");
+
+    let hash = HEXUPPER.encode(&Sha1::digest(b"Hj7#qL2!vN9$xR4@mT6%"));
+    let list = dir.join("pwned.txt");
+    fs::write(&list, format!("{}:42\n", hash)).unwrap();
+
+    let report = audit(&vault, &AuditOptions { breached: Some(list), ..options() }).unwrap();
+    let breached = report.breached.as_ref().unwrap();
+    assert_eq!(breached.len(), 1);
+    assert_eq!((breached[0].label.as_str(), breached[0].count), ("forum", 42));
+    assert!(!report.is_clean());
+
+    assert!(audit(&vault, &AuditOptions { breached: Some(dir.join("nothing.txt")), ..options() }).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
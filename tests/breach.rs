@@ -0,0 +1,117 @@
+mod common;
+
+use std::fs;
+use data_encoding::HEXUPPER;
+use passman::BreachedPasswords;
+use sha1::{Digest, Sha1};
+use common::temp_dir;
+
+const PASSWORDS: usize = 500;
+
+fn hash(password: &str) -> String {
+    HEXUPPER.encode(&Sha1::digest(password.as_bytes()))
+}
+
+/// Passwords in the list, with their counts and hashes, sorted by hash
+fn breached() -> Vec<(String, u64, String)> {
+    let mut breached: Vec<(String, u64, String)> = (0..PASSWORDS)
+        .map(|i| {
+            let password = format!("password-{}", i);
+            let hash = hash(&password);
+            (password, i as u64 + 1, hash)
+        })
+        .collect();
+    breached.sort_by(|a, b| a.2.cmp(&b.2));
+    breached
+}
+
+/// Writes an ordered file of `HASH:COUNT` lines and checks every password and a missing one
+fn check_ordered_file(test: &str, line_end: &str, trailing: bool, lowercase: bool) {
+    let breached = breached();
+    let lines: Vec<String> = breached.iter()
+        .map(|(_, count, hash)| format!("{}:{}", if lowercase { hash.to_lowercase() } else { hash.clone() }, count))
+        .collect();
+    let mut data = lines.join(line_end);
+    if trailing {
+        data.push_str(line_end);
+    }
+    let path = temp_dir(test).join("pwned-passwords-sha1-ordered-by-hash.txt");
+    fs::write(&path, data).unwrap();
+
+    let list = BreachedPasswords::new(&path).unwrap();
+    // First and last lines are the edge cases of the search
+    let (first, last) = (&breached[0], &breached[PASSWORDS - 1]);
+    assert_eq!(list.count(&first.0).unwrap(), first.1);
+    assert_eq!(list.count(&last.0).unwrap(), last.1);
+    for (password, count, _) in &breached {
+        assert_eq!(list.count(password).unwrap(), *count, "{}", password);
+    }
+    assert_eq!(list.count("not-breached").unwrap(), 0);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn finds_in_ordered_file() {
+    check_ordered_file("ordered", "\n", true, false);
+}
+
+#[test]
+fn finds_without_trailing_newline() {
+    check_ordered_file("no-newline", "\n", false, false);
+}
+
+#[test]
+fn finds_with_crlf() {
+    check_ordered_file("crlf", "\r\n", true, false);
+    check_ordered_file("crlf-no-newline", "\r\n", false, false);
+}
+
+#[test]
+fn finds_lowercase_hashes() {
+    check_ordered_file("lowercase", "\n", true, true);
+}
+
+#[test]
+fn single_line_file() {
+    let path = temp_dir("single").join("pwned.txt");
+    // Lines without a count still mean the password was breached
+    fs::write(&path, hash("password")).unwrap();
+    let list = BreachedPasswords::new(&path).unwrap();
+    assert_eq!(list.count("password").unwrap(), 1);
+    assert_eq!(list.count("not-breached").unwrap(), 0);
+
+    fs::write(&path, "").unwrap();
+    assert_eq!(list.count("password").unwrap(), 0);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn finds_in_range_directory() {
+    let dir = temp_dir("ranges");
+    let password_hash = hash("password");
+    let (prefix, suffix) = password_hash.split_at(5);
+    // Range without the password, then with it between its neighbours, in both cases
+    // and with CRLF as downloaded
+    let neighbours = [format!("{}:3", "0".repeat(35)), format!("{}:7", "F".repeat(35))];
+    fs::write(dir.join(format!("{}.txt", prefix)), neighbours.join("\r\n")).unwrap();
+    let list = BreachedPasswords::new(&dir).unwrap();
+    assert_eq!(list.count("password").unwrap(), 0);
+
+    let range = format!("{}\r\n{}:9545824\r\n{}\r\n", neighbours[0], suffix.to_lowercase(), neighbours[1]);
+    fs::write(dir.join(format!("{}.txt", prefix)), range).unwrap();
+    assert_eq!(list.count("password").unwrap(), 9_545_824);
+
+    // Range of another password isn't downloaded
+    assert!(list.count("not-breached").is_err());
+
+    // Range files can also be named without the extension
+    fs::remove_file(dir.join(format!("{}.txt", prefix))).unwrap();
+    fs::write(dir.join(prefix), format!("{}:2", suffix)).unwrap();
+    assert_eq!(list.count("password").unwrap(), 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_list_is_an_error() {
+    assert!(BreachedPasswords::new(temp_dir("missing").join("nothing.txt")).is_err());
+}
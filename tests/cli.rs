@@ -0,0 +1,131 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use passman::{Error, Vault};
+use common::{passman, PASSWORD};
+
+const STRONG: &str = "tylko-skrypt-zna-to-hasło-2024";
+
+fn vault_with_github(test: &str) -> PathBuf {
+    let mut vault = common::create_vault(&common::temp_dir(test));
+    vault.insert("github", "gh-secret").unwrap();
+    vault.save().unwrap();
+    vault.path().to_path_buf()
+}
+
+/// Runs passman through the shell, with the file opened as descriptor 3
+fn with_fd3(vault: &Path, password_file: &Path, args: &str) -> Output {
+    let dir = vault.parent().unwrap();
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!("\"$0\" --vault \"$1\" {} 3< \"$2\"", args))
+        .args([env!("CARGO_BIN_EXE_passman"), vault.to_str().unwrap(), password_file.to_str().unwrap()])
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .env_remove("PASSMAN_VAULT")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn invalid_usage() {
+    let path = vault_with_github("usage");
+    for args in [
+        &["unknown-command"][..],
+        &["get"],
+        &["new", "x", "0"],
+        &["--password-fd", "3", "get", "github"],
+        &["--profile", "praca", "get", "github"],
+    ] {
+        let output = passman(&path, args, "");
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(!output.stderr.is_empty());
+    }
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn password_from_environment() {
+    let path = vault_with_github("env");
+    let output = passman(&path, &["get", "github", "--stdout"], "");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "gh-secret");
+
+    let mut command = common::command(&path);
+    command.args(["get", "github", "--stdout"]).env_remove("PASSMAN_TEST_PASSWORD");
+    let output = common::run(command, "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn password_from_descriptor() {
+    let path = vault_with_github("fd");
+    let dir = path.parent().unwrap();
+    let password_file = dir.join("password.txt");
+    fs::write(&password_file, format!("{}\n", PASSWORD)).unwrap();
+
+    let output = with_fd3(&path, &password_file, "--password-fd 3 get github --stdout");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "gh-secret");
+
+    fs::write(&password_file, "wrong\n").unwrap();
+    let output = with_fd3(&path, &password_file, "--password-fd 3 get github --stdout");
+    assert_eq!(output.status.code(), common::exit_code(Error::WrongPassword));
+
+    // Standard streams and descriptors which aren't open are refused
+    for fd in ["0", "1", "2", "9"] {
+        let output = with_fd3(&path, &password_file, &format!("--password-fd {} get github --stdout", fd));
+        assert_eq!(output.status.code(), Some(1), "{}", fd);
+        assert!(output.stdout.is_empty());
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn init_creates_vault() {
+    let dir = common::temp_dir("init");
+    let path = dir.join("vault.bin");
+
+    // Without a vault there's nothing to unlock
+    let output = passman(&path, &["get", "github"], "");
+    assert_eq!(output.status.code(), common::exit_code(Error::NoProfile));
+
+    let mut command = common::command(&path);
+    command.arg("init").env("PASSMAN_TEST_PASSWORD", "123");
+    assert_eq!(common::run(command, "").status.code(), common::exit_code(Error::WeakPassword));
+    assert!(!path.exists());
+
+    let mut command = common::command(&path);
+    command.arg("init").env("PASSMAN_TEST_PASSWORD", STRONG);
+    let output = common::run(command, "");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let vault = Vault::open(&path, STRONG).unwrap();
+    assert_eq!(vault.iter_labels().count(), 0);
+
+    // Existing vault isn't replaced
+    let before = fs::read(&path).unwrap();
+    let mut command = common::command(&path);
+    command.arg("init").env("PASSMAN_TEST_PASSWORD", "inne-hasło-do-skryptu-2024");
+    assert_eq!(common::run(command, "").status.code(), Some(1));
+    assert_eq!(fs::read(&path).unwrap(), before);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn init_from_descriptor() {
+    let dir = common::temp_dir("init-fd");
+    let path = dir.join("vault.bin");
+    let password_file = dir.join("password.txt");
+    fs::write(&password_file, "słabe\n").unwrap();
+
+    let output = with_fd3(&path, &password_file, "--password-fd 3 init --allow-weak");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(Vault::open(&path, "słabe").is_ok());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,69 @@
+//! Helpers shared by the integration tests, each test crate uses only some of them
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use passman::{KdfParams, Vault};
+
+/// Master password of vaults made by `create_vault`
+pub const PASSWORD: &str = "test-password";
+
+/// Smallest KDF parameters, so vaults are created and opened quickly
+pub fn fast_kdf() -> KdfParams {
+    KdfParams { memory_kib: 64, iterations: 1, parallelism: 1, ..KdfParams::default() }
+}
+
+/// New empty directory for a test. Vaults need one each, as saving writes backups next to them.
+pub fn temp_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("passman-{}-{}-{}", env!("CARGO_CRATE_NAME"), std::process::id(), test));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Empty vault.bin in dir with fast KDF parameters, unlocked with `PASSWORD`
+pub fn create_vault(dir: &Path) -> Vault {
+    Vault::create_with_kdf(dir.join("vault.bin"), PASSWORD, fast_kdf()).unwrap()
+}
+
+/// Writes the config file read by `passman` run for the vault in dir
+pub fn write_config(dir: &Path, config: &str) {
+    fs::create_dir_all(dir.join("passman")).unwrap();
+    fs::write(dir.join("passman/config.toml"), config).unwrap();
+}
+
+/// The binary set up to unlock the vault with `PASSWORD` (can be replaced by setting
+/// PASSMAN_TEST_PASSWORD again). The user's configuration is kept out, the config file
+/// is looked up in the directory of the vault.
+pub fn command(vault: &Path) -> Command {
+    let dir = vault.parent().unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_passman"));
+    command
+        .args(["--vault", vault.to_str().unwrap(), "--password-env", "PASSMAN_TEST_PASSWORD"])
+        .env("PASSMAN_TEST_PASSWORD", PASSWORD)
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .env_remove("PASSMAN_VAULT");
+    command
+}
+
+/// Runs the command with stdin as its standard input
+pub fn run(mut command: Command, stdin: &str) -> Output {
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Runs passman with args for the vault
+pub fn passman(vault: &Path, args: &[&str], stdin: &str) -> Output {
+    let mut command = command(vault);
+    command.args(args);
+    run(command, stdin)
+}
+
+/// Exit code `error` is reported with
+pub fn exit_code(error: passman::Error) -> Option<i32> {
+    Some(error.exit_code() as i32)
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use passman::{Error, KdfParams, Vault};
+use common::{fast_kdf, temp_dir, PASSWORD};
+
+// Offsets in the vault file, see the layout in container.rs
+const ITERATIONS_OFFSET: usize = 19;
+const KEY_CHECK_OFFSET: usize = 44;
+const NONCE_OFFSET: usize = 60;
+
+/// Vault with one entry
+fn create_vault(test: &str) -> PathBuf {
+    let mut vault = common::create_vault(&temp_dir(test));
+    vault.insert("github", "secret").unwrap().username = Some(String::from("bob"));
+    vault.set_notes("github", Some("notatka")).unwrap();
+    vault.save().unwrap();
+    vault.path().to_path_buf()
+}
+
+/// Fast parameters with more memory, so they differ from the ones vaults are created with
+fn more_memory() -> KdfParams {
+    KdfParams { memory_kib: 128, ..fast_kdf() }
+}
+
+#[test]
+fn round_trip() {
+    let path = create_vault("round-trip");
+    let data = fs::read(&path).unwrap();
+    assert!(data.starts_with(b"PASSMAN\0"));
+    // Nothing of the entry is stored in plaintext
+    assert!(!data.windows(6).any(|window| window == b"github"));
+
+    let vault = Vault::open(&path, PASSWORD).unwrap();
+    assert_eq!(vault.get("github").unwrap(), "secret");
+    assert_eq!(vault.entry("github").unwrap().username.as_deref(), Some("bob"));
+    assert_eq!(vault.notes("github").unwrap().as_deref(), Some("notatka"));
+    assert_eq!(vault.kdf().unwrap(), fast_kdf());
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn wrong_password_and_tampering() {
+    let path = create_vault("tampering");
+    let data = fs::read(&path).unwrap();
+    assert!(matches!(Vault::open(&path, "wrong"), Err(Error::WrongPassword)));
+
+    let tampered = |offset: usize| {
+        let mut data = data.clone();
+        data[offset] ^= 2;
+        fs::write(&path, &data).unwrap();
+        Vault::open(&path, PASSWORD)
+    };
+    // Modified body or nonce is told apart from a wrong password
+    assert!(matches!(tampered(data.len() - 1), Err(Error::Tampered)));
+    assert!(matches!(tampered(NONCE_OFFSET), Err(Error::Tampered)));
+    // Changed KDF parameters give another key, which doesn't match the key check
+    assert!(matches!(tampered(ITERATIONS_OFFSET), Err(Error::WrongPassword)));
+    assert!(matches!(tampered(KEY_CHECK_OFFSET), Err(Error::WrongPassword)));
+    assert!(matches!(tampered(0), Err(Error::CorruptVault(_))));
+
+    fs::write(&path, &data[..NONCE_OFFSET + 4]).unwrap();
+    assert!(matches!(Vault::open(&path, PASSWORD), Err(Error::CorruptVault(_))));
+    fs::write(&path, &data).unwrap();
+    assert!(Vault::open(&path, PASSWORD).is_ok());
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn kdf_change_round_trip() {
+    let path = create_vault("kdf");
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    assert!(matches!(vault.change_kdf("wrong", more_memory()), Err(Error::WrongPassword)));
+    assert!(vault.change_kdf(PASSWORD, KdfParams { memory_kib: 0, ..fast_kdf() }).is_err());
+    assert_eq!(Vault::open(&path, PASSWORD).unwrap().kdf().unwrap(), fast_kdf());
+
+    vault.change_kdf(PASSWORD, more_memory()).unwrap();
+    let vault = Vault::open(&path, PASSWORD).unwrap();
+    assert_eq!(vault.kdf().unwrap(), more_memory());
+    assert_eq!(vault.get("github").unwrap(), "secret");
+    assert_eq!(vault.notes("github").unwrap().as_deref(), Some("notatka"));
+
+    // The previous file is kept, it opens with its own parameters
+    let backup = &vault.backups()[0];
+    assert_eq!(Vault::open(&backup.path, PASSWORD).unwrap().kdf().unwrap(), fast_kdf());
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn password_change_round_trip() {
+    let path = create_vault("password");
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    vault.change_password("new-password").unwrap();
+
+    assert!(matches!(Vault::open(&path, PASSWORD), Err(Error::WrongPassword)));
+    let vault = Vault::open(&path, "new-password").unwrap();
+    assert_eq!(vault.get("github").unwrap(), "secret");
+    assert_eq!(vault.kdf().unwrap(), fast_kdf());
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+/// Encrypts like the old format did, with a key from default Argon2 parameters
+fn legacy_encrypt(key: &[u8], nonce: [u8; 12], value: &[u8]) -> Vec<u8> {
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)).encrypt(Nonce::from_slice(&nonce), value).unwrap()
+}
+
+/// salt.bin, verify.bin with a nonce and encrypted token, and plaintext vault.json
+/// of the old format, with a key from the default parameters
+fn write_legacy_files(dir: &Path) {
+    let salt = [7u8; 16];
+    let mut key = [0u8; 32];
+    Argon2::default().hash_password_into(PASSWORD.as_bytes(), &salt, &mut key).unwrap();
+
+    fs::create_dir_all(dir).unwrap();
+    fs::write(dir.join("salt.bin"), salt).unwrap();
+    let mut verify = vec![1u8; 12];
+    verify.extend(legacy_encrypt(&key, [1; 12], b"verification token"));
+    fs::write(dir.join("verify.bin"), verify).unwrap();
+    let vault_json = serde_json::json!({
+        "github": { "nonce": vec![2u8; 12], "cipher": legacy_encrypt(&key, [2; 12], b"legacy-secret") },
+    });
+    fs::write(dir.join("vault.json"), vault_json.to_string()).unwrap();
+}
+
+#[test]
+fn migrates_legacy_files() {
+    // The old format was only ever in the default data directory
+    let dir = temp_dir("legacy");
+    let data = dir.join("passman_data");
+    write_legacy_files(&data);
+    let path = data.join("vault.bin");
+
+    let run = |password: &str| {
+        // Without --vault, so the default location is used
+        let mut command = Command::new(env!("CARGO_BIN_EXE_passman"));
+        command.args(["--password-env", "PASSMAN_TEST_PASSWORD", "get", "github", "--stdout"])
+            .env("PASSMAN_TEST_PASSWORD", password)
+            .env("HOME", &dir)
+            .env("XDG_CONFIG_HOME", &dir)
+            .env("XDG_DATA_HOME", &dir)
+            .env_remove("PASSMAN_VAULT");
+        common::run(command, "")
+    };
+
+    assert_eq!(run("wrong").status.code(), common::exit_code(Error::WrongPassword));
+    assert!(data.join("vault.json").exists() && !path.exists());
+
+    let output = run(PASSWORD);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().last(), Some("legacy-secret"));
+    for old in ["salt.bin", "verify.bin", "vault.json"] {
+        assert!(!data.join(old).exists(), "{}", old);
+    }
+
+    let vault = Vault::open(&path, PASSWORD).unwrap();
+    assert_eq!(vault.get("github").unwrap(), "legacy-secret");
+    assert_eq!(vault.kdf().unwrap(), KdfParams::default());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn ignores_legacy_names_elsewhere() {
+    let dir = temp_dir("legacy-elsewhere");
+    write_legacy_files(&dir);
+    let path = dir.join("vault.bin");
+
+    assert!(!Vault::exists(&path) && !Vault::needs_migration(&path));
+    assert!(Vault::migrate_legacy(&path, PASSWORD).is_err());
+    // Another program's files stay untouched and a new vault can be created next to them
+    common::create_vault(&dir);
+    for old in ["salt.bin", "verify.bin", "vault.json"] {
+        assert!(dir.join(old).exists(), "{}", old);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
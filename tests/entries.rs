@@ -0,0 +1,72 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use passman::{Error, Vault};
+use common::{passman, PASSWORD};
+
+const TOTP: &str = "JBSWY3DPEHPK3PXP";
+
+fn vault_path(test: &str) -> PathBuf {
+    let mut vault = common::create_vault(&common::temp_dir(test));
+    vault.insert("github", "gh-secret").unwrap();
+    vault.save().unwrap();
+    vault.path().to_path_buf()
+}
+
+#[test]
+fn add_reads_notes_and_totp_after_password() {
+    let path = vault_path("add");
+    let stdin = format!("bank-secret\nnumer klienta 123\n{}\n", TOTP);
+    let output = passman(&path, &["add", "bank", "--stdin", "--allow-weak", "--username", "jan", "--notes", "--totp"], &stdin);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let vault = Vault::open(&path, PASSWORD).unwrap();
+    assert_eq!(vault.get("bank").unwrap(), "bank-secret");
+    assert_eq!(vault.entry("bank").unwrap().username.as_deref(), Some("jan"));
+    assert_eq!(vault.notes("bank").unwrap().as_deref(), Some("numer klienta 123"));
+    assert!(vault.totp("bank").unwrap().is_some());
+
+    // Generated password reads only the notes
+    let output = passman(&path, &["new", "forum", "--notes"], "konto testowe\n");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(Vault::open(&path, PASSWORD).unwrap().notes("forum").unwrap().as_deref(), Some("konto testowe"));
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn invalid_totp_adds_nothing() {
+    let path = vault_path("invalid-totp");
+    let output = passman(&path, &["add", "bank", "--stdin", "--allow-weak", "--totp"], "bank-secret\nnie-base32!\n");
+    assert_eq!(output.status.code(), common::exit_code(Error::Totp(String::new())));
+    assert!(!Vault::open(&path, PASSWORD).unwrap().contains("bank"));
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn set_reads_secrets_from_input() {
+    let path = vault_path("set");
+    assert!(passman(&path, &["set", "github", "notes"], "klucze SSH\n").status.success());
+    assert!(passman(&path, &["set", "github", "totp"], &format!("{}\n", TOTP)).status.success());
+
+    let vault = Vault::open(&path, PASSWORD).unwrap();
+    assert_eq!(vault.notes("github").unwrap().as_deref(), Some("klucze SSH"));
+    assert!(vault.totp("github").unwrap().is_some());
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn secrets_in_arguments_are_refused() {
+    let path = vault_path("arguments");
+    let before = fs::read(&path).unwrap();
+
+    for field in ["notes", "totp"] {
+        let output = passman(&path, &["set", "github", field, TOTP], "");
+        assert_eq!(output.status.code(), common::exit_code(Error::SecretInArguments), "{}", field);
+    }
+    // Flags of add don't take values
+    let output = passman(&path, &["add", "bank", "--stdin", "--totp", TOTP], "bank-secret\n");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(fs::read(&path).unwrap(), before);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
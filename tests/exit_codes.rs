@@ -0,0 +1,65 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use passman::Error;
+use common::passman;
+
+fn vault_with_github(test: &str) -> PathBuf {
+    let mut vault = common::create_vault(&common::temp_dir(test));
+    vault.insert("github", "gh-secret").unwrap();
+    vault.save().unwrap();
+    vault.path().to_path_buf()
+}
+
+#[test]
+fn wrong_password() {
+    let path = vault_with_github("wrong-password");
+    let mut command = common::command(&path);
+    command.args(["get", "github", "--stdout"]).env("PASSMAN_TEST_PASSWORD", "wrong");
+    let output = common::run(command, "");
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(output.status.code(), common::exit_code(Error::WrongPassword));
+    assert!(output.stdout.is_empty());
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn missing_entry() {
+    let path = vault_with_github("missing-entry");
+    for args in [&["get", "gitlab", "--stdout"][..], &["remove", "gitlab", "--yes"], &["set", "gitlab", "username", "jan"]] {
+        let output = passman(&path, args, "");
+        assert_eq!(output.status.code(), Some(5), "{:?}", args);
+        assert_eq!(output.status.code(), common::exit_code(Error::LabelNotFound));
+    }
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn read_only_vault() {
+    let path = vault_with_github("read-only");
+    let before = fs::read(&path).unwrap();
+
+    let output = passman(&path, &["--read-only", "remove", "github", "--yes"], "");
+    assert_eq!(output.status.code(), Some(22));
+    assert_eq!(output.status.code(), common::exit_code(Error::ReadOnly));
+    // Reading still works
+    assert!(passman(&path, &["--read-only", "get", "github", "--stdout"], "").status.success());
+    assert_eq!(fs::read(&path).unwrap(), before);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn tampered_file() {
+    let path = vault_with_github("tampered");
+    let mut data = fs::read(&path).unwrap();
+    // Last byte belongs to the authentication tag of the encrypted entries
+    *data.last_mut().unwrap() ^= 1;
+    fs::write(&path, &data).unwrap();
+
+    let output = passman(&path, &["get", "github", "--stdout"], "");
+    assert_eq!(output.status.code(), Some(13));
+    assert_eq!(output.status.code(), common::exit_code(Error::Tampered));
+    assert!(output.stdout.is_empty());
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use passman::{export_plaintext, read_import, ColumnMapping, Error, ExportFormat, ImportFormat, Vault};
+
+/// Vault with two entries, in its own directory the exports are written to
+fn vault_with_entries(test: &str) -> (PathBuf, Vault) {
+    let dir = common::temp_dir(test);
+    let mut vault = common::create_vault(&dir);
+    let entry = vault.insert("github", "gh-secret").unwrap();
+    entry.username = Some(String::from("bob"));
+    entry.urls = vec![String::from("https://github.com")];
+    entry.tags = vec![String::from("dev"), String::from("praca")];
+    vault.set_notes("github", Some("klucz, z przecinkiem\ni nową linią")).unwrap();
+    vault.set_totp("github", Some("JBSWY3DPEHPK3PXP")).unwrap();
+    vault.insert("bank", "bank-secret").unwrap();
+    (dir, vault)
+}
+
+#[test]
+fn exports_are_private() {
+    let (dir, vault) = vault_with_entries("private");
+    for (file, format) in [("export.csv", ExportFormat::Csv), ("export.json", ExportFormat::Json)] {
+        let path = dir.join(file);
+        assert_eq!(export_plaintext(&vault, &path, format).unwrap(), 2);
+        assert!(fs::read_to_string(&path).unwrap().contains("gh-secret"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600, "{}", file);
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn refuses_to_overwrite() {
+    let (dir, vault) = vault_with_entries("overwrite");
+    let path = dir.join("export.csv");
+    fs::write(&path, "important").unwrap();
+
+    for format in [ExportFormat::Csv, ExportFormat::Json] {
+        match export_plaintext(&vault, &path, format) {
+            Err(Error::Io(error)) => assert_eq!(error.kind(), ErrorKind::AlreadyExists),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+    assert_eq!(fs::read_to_string(&path).unwrap(), "important");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn csv_export_imports_back() {
+    let (dir, vault) = vault_with_entries("round-trip");
+    let path = dir.join("export.csv");
+    export_plaintext(&vault, &path, ExportFormat::Csv).unwrap();
+
+    let import = read_import(&path, ImportFormat::Csv, &ColumnMapping::default()).unwrap();
+    let labels: Vec<&str> = import.entries.iter().map(|entry| entry.label.as_str()).collect();
+    assert_eq!(labels, ["bank", "github"]);
+    let github = &import.entries[1];
+    assert_eq!(github.password, "gh-secret");
+    assert_eq!(github.username.as_deref(), Some("bob"));
+    assert_eq!(github.urls, ["https://github.com"]);
+    assert_eq!(github.tags, ["dev", "praca"]);
+    assert_eq!(github.notes.as_deref(), Some("klucz, z przecinkiem\ni nową linią"));
+    assert_eq!(github.totp.as_deref(), Some("JBSWY3DPEHPK3PXP"));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,340 @@
+#!/usr/bin/env python3
+"""KDBX 4 writer and reader independent of passman, for test fixtures.
+
+    python3 kdbx.py create                  writes the fixtures next to this script
+    python3 kdbx.py dump FILE PASSWORD      prints groups and entries of a database as JSON
+
+Needs the `cryptography` package (42 or newer for Argon2id). Only password keys,
+Argon2id or AES-KDF and AES-256 or ChaCha20 ciphers are supported.
+"""
+import base64
+import gzip
+import hashlib
+import hmac
+import json
+import os
+import struct
+import sys
+import uuid
+import xml.etree.ElementTree as ET
+
+from cryptography.hazmat.primitives import padding
+from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+from cryptography.hazmat.primitives.kdf.argon2 import Argon2id
+
+SIGNATURES = struct.pack('<II', 0x9AA2D903, 0xB54BFB67)
+CIPHER_AES256 = uuid.UUID('31c1f2e6-bf71-4350-be58-05216afc5aff').bytes
+CIPHER_CHACHA20 = uuid.UUID('d6038a2b-8b6f-4cb5-a524-339a31dbb59a').bytes
+KDF_AES = uuid.UUID('c9d9f39a-628a-4460-bf74-0d08c18a4fea').bytes
+KDF_ARGON2ID = uuid.UUID('9e298b19-56db-4773-b23d-fc3ec6f0a1e6').bytes
+STREAM_CHACHA20 = 3
+
+PASSWORD = 'fixture-password'
+# Seconds between 0001-01-01 and the unix epoch
+EPOCH_OFFSET = 62135596800
+
+
+def variant_dict(items):
+    data = struct.pack('<H', 0x0100)
+    for key, kind, value in items:
+        name = key.encode()
+        data += struct.pack('<BI', kind, len(name)) + name + struct.pack('<I', len(value)) + value
+    return data + b'\x00'
+
+
+def parse_variant_dict(data):
+    pos, result = 2, {}
+    while data[pos] != 0:
+        pos += 1
+        (length,) = struct.unpack_from('<I', data, pos)
+        key = data[pos + 4:pos + 4 + length].decode()
+        pos += 4 + length
+        (length,) = struct.unpack_from('<I', data, pos)
+        result[key] = data[pos + 4:pos + 4 + length]
+        pos += 4 + length
+    return result
+
+
+def transform_key(password, kdf):
+    composite = hashlib.sha256(hashlib.sha256(password.encode()).digest()).digest()
+    if kdf['$UUID'] == KDF_ARGON2ID:
+        return Argon2id(salt=kdf['S'], length=32, iterations=struct.unpack('<Q', kdf['I'])[0],
+                        lanes=struct.unpack('<I', kdf['P'])[0],
+                        memory_cost=struct.unpack('<Q', kdf['M'])[0] // 1024).derive(composite)
+    if kdf['$UUID'] == KDF_AES:
+        encryptor = Cipher(algorithms.AES(kdf['S']), modes.ECB()).encryptor()
+        key = composite
+        for _ in range(struct.unpack('<Q', kdf['R'])[0]):
+            key = encryptor.update(key)
+        return hashlib.sha256(key).digest()
+    raise SystemExit('unsupported KDF')
+
+
+def chacha20(key, nonce):
+    # cryptography takes the 32 bit block counter in front of the 96 bit nonce
+    return Cipher(algorithms.ChaCha20(key, b'\x00' * 4 + nonce), mode=None)
+
+
+def inner_stream(key):
+    digest = hashlib.sha512(key).digest()
+    return chacha20(digest[:32], digest[32:44]).encryptor()
+
+
+def block_key(index, hmac_key):
+    return hashlib.sha512(struct.pack('<Q', index) + hmac_key).digest()
+
+
+def keys(password, seed, kdf):
+    transformed = transform_key(password, kdf)
+    return hashlib.sha256(seed + transformed).digest(), hashlib.sha512(seed + transformed + b'\x01').digest()
+
+
+def write_kdbx(path, password, root, cipher, kdf_items, compress, binaries):
+    seed = os.urandom(32)
+    iv = os.urandom(16 if cipher == CIPHER_AES256 else 12)
+    fields = [(2, cipher), (3, struct.pack('<I', 1 if compress else 0)), (4, seed), (7, iv),
+              (11, variant_dict(kdf_items)), (0, b'\r\n\r\n')]
+    header = SIGNATURES + struct.pack('<HH', 0, 4)
+    for field, data in fields:
+        header += struct.pack('<BI', field, len(data)) + data
+
+    key, hmac_key = keys(password, seed, parse_variant_dict(variant_dict(kdf_items)))
+    output = header + hashlib.sha256(header).digest()
+    output += hmac.new(block_key(2 ** 64 - 1, hmac_key), header, hashlib.sha256).digest()
+
+    stream_key = os.urandom(64)
+    inner = struct.pack('<BII', 1, 4, STREAM_CHACHA20) + struct.pack('<BI', 2, 64) + stream_key
+    for binary in binaries:
+        inner += struct.pack('<BI', 3, len(binary) + 1) + b'\x01' + binary
+    inner += struct.pack('<BI', 0, 0)
+
+    stream = inner_stream(stream_key)
+    for value in root.iter('Value'):
+        if value.get('Protected') == 'True':
+            value.text = base64.b64encode(stream.update((value.text or '').encode())).decode()
+    payload = inner + ET.tostring(root, encoding='utf-8', xml_declaration=True)
+    if compress:
+        payload = gzip.compress(payload)
+
+    if cipher == CIPHER_AES256:
+        padder = padding.PKCS7(128).padder()
+        encryptor = Cipher(algorithms.AES(key), modes.CBC(iv)).encryptor()
+        encrypted = encryptor.update(padder.update(payload) + padder.finalize()) + encryptor.finalize()
+    else:
+        encrypted = chacha20(key, iv).encryptor().update(payload)
+
+    for index, block in enumerate([encrypted, b'']):
+        size = struct.pack('<I', len(block))
+        mac = hmac.new(block_key(index, hmac_key), struct.pack('<Q', index) + size + block, hashlib.sha256).digest()
+        output += mac + size + block
+
+    with open(path, 'wb') as file:
+        file.write(output)
+
+
+def read_kdbx(path, password):
+    with open(path, 'rb') as file:
+        data = file.read()
+    if data[:8] != SIGNATURES or struct.unpack_from('<H', data, 10)[0] != 4:
+        raise SystemExit('not a KDBX 4 file')
+
+    pos, fields = 12, {}
+    while True:
+        field, length = struct.unpack_from('<BI', data, pos)
+        fields[field] = data[pos + 5:pos + 5 + length]
+        pos += 5 + length
+        if field == 0:
+            break
+    header = data[:pos]
+    if hashlib.sha256(header).digest() != data[pos:pos + 32]:
+        raise SystemExit('header is corrupted')
+
+    key, hmac_key = keys(password, fields[4], parse_variant_dict(fields[11]))
+    if hmac.new(block_key(2 ** 64 - 1, hmac_key), header, hashlib.sha256).digest() != data[pos + 32:pos + 64]:
+        raise SystemExit('wrong password')
+
+    pos, index, encrypted = pos + 64, 0, b''
+    while True:
+        mac, (length,) = data[pos:pos + 32], struct.unpack_from('<I', data, pos + 32)
+        block = data[pos + 36:pos + 36 + length]
+        expected = hmac.new(block_key(index, hmac_key), struct.pack('<QI', index, length) + block, hashlib.sha256).digest()
+        if mac != expected:
+            raise SystemExit('block %d is corrupted' % index)
+        if length == 0:
+            break
+        encrypted += block
+        pos, index = pos + 36 + length, index + 1
+
+    if fields[2] == CIPHER_AES256:
+        decryptor = Cipher(algorithms.AES(key), modes.CBC(fields[7])).decryptor()
+        unpadder = padding.PKCS7(128).unpadder()
+        payload = unpadder.update(decryptor.update(encrypted) + decryptor.finalize()) + unpadder.finalize()
+    else:
+        payload = chacha20(key, fields[7]).decryptor().update(encrypted)
+    if struct.unpack('<I', fields[3])[0] == 1:
+        payload = gzip.decompress(payload)
+
+    pos, stream_key, binaries = 0, None, []
+    while True:
+        field, length = struct.unpack_from('<BI', payload, pos)
+        value = payload[pos + 5:pos + 5 + length]
+        pos += 5 + length
+        if field == 0:
+            break
+        if field == 1 and struct.unpack('<I', value)[0] != STREAM_CHACHA20:
+            raise SystemExit('unsupported inner stream')
+        if field == 2:
+            stream_key = value
+        if field == 3:
+            binaries.append(value[1:])
+
+    root = ET.fromstring(payload[pos:])
+    stream = inner_stream(stream_key)
+    for value in root.iter('Value'):
+        if value.get('Protected') == 'True':
+            value.text = stream.update(base64.b64decode(value.text or '')).decode()
+    return root, binaries
+
+
+def time(unix):
+    return base64.b64encode(struct.pack('<q', unix + EPOCH_OFFSET)).decode()
+
+
+def child(parent, tag, text=None, **attributes):
+    element = ET.SubElement(parent, tag, attributes)
+    if text is not None:
+        element.text = text
+    return element
+
+
+def new_uuid(name):
+    return base64.b64encode(uuid.uuid5(uuid.NAMESPACE_URL, name).bytes).decode()
+
+
+def entry(group, title, fields, tags='', history=(), binary=None):
+    element = child(group, 'Entry')
+    child(element, 'UUID', new_uuid(group.find('Name').text + '/' + title + str(len(group))))
+    child(element, 'IconID', '0')
+    child(element, 'Tags', tags)
+    times = child(element, 'Times')
+    child(times, 'CreationTime', time(1700000000))
+    child(times, 'LastModificationTime', time(1700086400))
+    child(times, 'LastAccessTime', time(1700172800))
+    child(times, 'Expires', 'False')
+    child(times, 'UsageCount', '3')
+    for key, value in [('Title', title)] + list(fields.items()):
+        string = child(element, 'String')
+        child(string, 'Key', key)
+        if key == 'Password' or key.startswith('Secret'):
+            child(string, 'Value', value, Protected='True')
+        else:
+            child(string, 'Value', value)
+    if binary is not None:
+        reference = child(element, 'Binary')
+        child(reference, 'Key', 'recovery-codes.txt')
+        child(reference, 'Value', Ref=str(binary))
+    old = child(element, 'History')
+    for password in history:
+        previous = child(old, 'Entry')
+        child(previous, 'UUID', element.find('UUID').text)
+        for key, value in [('Title', title), ('Password', password)]:
+            string = child(previous, 'String')
+            child(string, 'Key', key)
+            if key == 'Password':
+                child(string, 'Value', value, Protected='True')
+            else:
+                child(string, 'Value', value)
+    return element
+
+
+def group(parent, name):
+    element = child(parent, 'Group')
+    child(element, 'UUID', new_uuid('group/' + name))
+    child(element, 'Name', name)
+    child(element, 'IsExpanded', 'True')
+    return element
+
+
+def fixture_document():
+    document = ET.Element('KeePassFile')
+    meta = child(document, 'Meta')
+    child(meta, 'Generator', 'passman fixture')
+    child(meta, 'DatabaseName', 'Fixture')
+    child(meta, 'RecycleBinEnabled', 'True')
+    child(meta, 'RecycleBinUUID', new_uuid('group/Recycle Bin'))
+    root = child(document, 'Root')
+
+    top = group(root, 'Fixture')
+    entry(top, 'github', {
+        'UserName': 'bob',
+        'Password': 'gh-Pa55word!',
+        'URL': 'https://github.com',
+        'KP2A_URL': 'https://gist.github.com',
+        'Notes': 'konto\nfirmowe',
+        'otp': 'otpauth://totp/GitHub:bob?secret=JBSWY3DPEHPK3PXP&issuer=GitHub',
+        'SecretAnswer': 'Warszawa',
+    }, tags='dev;work', history=['old-password'], binary=0)
+    entry(top, 'bank', {'UserName': 'jan', 'Password': 's3cret', 'URL': '', 'Notes': ''})
+    entry(top, 'bank', {'UserName': 'anna', 'Password': 'drugie', 'URL': '', 'Notes': ''})
+    email = group(top, 'Email')
+    entry(email, 'gmail', {'UserName': 'jan@gmail.com', 'Password': 'zażółć-gęślą', 'URL': 'https://mail.google.com'})
+    work = group(email, 'Work')
+    entry(work, 'outlook', {'UserName': 'jan@firma.pl', 'Password': 'outlook-pass'})
+    trash = group(top, 'Recycle Bin')
+    entry(trash, 'deleted', {'Password': 'gone'})
+
+    child(root, 'DeletedObjects')
+    return document
+
+
+def argon2id(salt):
+    return [('$UUID', 0x42, KDF_ARGON2ID), ('S', 0x42, salt), ('P', 0x04, struct.pack('<I', 1)),
+            ('M', 0x05, struct.pack('<Q', 1024 * 1024)), ('I', 0x05, struct.pack('<Q', 2)),
+            ('V', 0x04, struct.pack('<I', 0x13))]
+
+
+def aes_kdf(seed):
+    return [('$UUID', 0x42, KDF_AES), ('R', 0x05, struct.pack('<Q', 1000)), ('S', 0x42, seed)]
+
+
+def dump(path, password):
+    document, binaries = read_kdbx(path, password)
+
+    def walk(group, names):
+        for element in group.findall('Entry'):
+            strings = {string.findtext('Key'): string.findtext('Value') or '' for string in element.findall('String')}
+            yield {
+                'group': '/'.join(names),
+                'strings': strings,
+                'tags': element.findtext('Tags') or '',
+                'history': len(element.findall('History/Entry')),
+                'binaries': [reference.findtext('Key') for reference in element.findall('Binary')],
+            }
+        for subgroup in group.findall('Group'):
+            yield from walk(subgroup, names + [subgroup.findtext('Name')])
+
+    top = document.find('Root/Group')
+    print(json.dumps({
+        'entries': list(walk(top, [])),
+        'binaries': [binary.decode() for binary in binaries],
+        'deleted': len(document.findall('Root/DeletedObjects/DeletedObject')),
+    }, ensure_ascii=False, indent=2))
+
+
+def main():
+    here = os.path.dirname(os.path.abspath(__file__))
+    if sys.argv[1:2] == ['create']:
+        binaries = [b'1111-2222\n3333-4444\n']
+        write_kdbx(os.path.join(here, 'argon2id-aes.kdbx'), PASSWORD, fixture_document(),
+                   CIPHER_AES256, argon2id(os.urandom(32)), True, binaries)
+        write_kdbx(os.path.join(here, 'aeskdf-chacha20.kdbx'), PASSWORD, fixture_document(),
+                   CIPHER_CHACHA20, aes_kdf(os.urandom(32)), False, binaries)
+    elif sys.argv[1:2] == ['dump'] and len(sys.argv) == 4:
+        dump(sys.argv[2], sys.argv[3])
+    else:
+        print(__doc__)
+        sys.exit(2)
+
+
+if __name__ == '__main__':
+    main()
@@ -0,0 +1,42 @@
+use passman::{move_target, relative_label, Folder};
+
+const LABELS: [&str; 6] = ["praca/aws/prod", "praca/aws/test", "praca/github", "bank", "praca-stara/vpn", "praca"];
+
+#[test]
+fn tree_of_all_labels() {
+    let tree = Folder::from_labels(LABELS, "").unwrap();
+    assert_eq!(tree.entries, ["bank", "praca"]);
+    assert_eq!(tree.folders.keys().collect::<Vec<_>>(), ["praca", "praca-stara"]);
+    assert_eq!(tree.count(), 6);
+
+    let work = &tree.folders["praca"];
+    assert_eq!(work.entries, ["github"]);
+    assert_eq!(work.folders["aws"].entries, ["prod", "test"]);
+}
+
+#[test]
+fn tree_of_folder() {
+    let tree = Folder::from_labels(LABELS, "praca/").unwrap();
+    assert_eq!(tree.entries, ["github"]);
+    assert_eq!(tree.count(), 3);
+
+    assert!(Folder::from_labels(LABELS, "bank").is_none());
+    assert!(Folder::from_labels(LABELS, "prac").is_none());
+}
+
+#[test]
+fn labels_in_folder() {
+    assert_eq!(relative_label("praca/aws/prod", "praca"), Some("aws/prod"));
+    assert_eq!(relative_label("praca/aws/prod", "/praca/aws/"), Some("prod"));
+    assert_eq!(relative_label("praca-stara/vpn", "praca"), None);
+    assert_eq!(relative_label("praca", "praca"), None);
+    assert_eq!(relative_label("bank", ""), Some("bank"));
+}
+
+#[test]
+fn move_targets() {
+    assert_eq!(move_target("praca/aws", "chmura/aws"), "chmura/aws");
+    assert_eq!(move_target("praca/aws", "chmura/"), "chmura/aws");
+    assert_eq!(move_target("praca/github", "/"), "github");
+    assert_eq!(move_target("bank", "finanse/"), "finanse/bank");
+}
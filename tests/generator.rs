@@ -0,0 +1,109 @@
+mod common;
+
+use std::fs;
+use std::process::Output;
+use passman::{Error, Generator, DEFAULT_PASSWORD_LEN};
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+fn count(password: &str, check: impl Fn(char) -> bool) -> usize {
+    password.chars().filter(|c| check(*c)).count()
+}
+
+#[test]
+fn every_enabled_class_appears() {
+    // Short passwords make a missing class likely if it weren't required
+    let generator = Generator { length: 4, ..Generator::default() };
+    for _ in 0..200 {
+        let password = generator.generate().unwrap();
+        assert_eq!(password.chars().count(), 4);
+        assert_eq!(count(&password, |c| c.is_ascii_lowercase()), 1, "{}", password);
+        assert_eq!(count(&password, |c| c.is_ascii_uppercase()), 1, "{}", password);
+        assert_eq!(count(&password, |c| c.is_ascii_digit()), 1, "{}", password);
+        assert_eq!(count(&password, |c| SYMBOLS.contains(c)), 1, "{}", password);
+    }
+
+    let generator = Generator { length: 10, uppercase: false, symbols: false, min_digits: 6, ..Generator::default() };
+    for _ in 0..100 {
+        let password = generator.generate().unwrap();
+        assert!(count(&password, |c| c.is_ascii_digit()) >= 6, "{}", password);
+        assert!(count(&password, |c| c.is_ascii_lowercase()) >= 1, "{}", password);
+        assert!(password.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()), "{}", password);
+    }
+}
+
+#[test]
+fn length_and_excluded_characters() {
+    assert_eq!(Generator::default().generate().unwrap().chars().count(), DEFAULT_PASSWORD_LEN);
+    for length in [1, 7, 64] {
+        let generator = Generator { length, uppercase: false, digits: false, symbols: false, ..Generator::default() };
+        assert_eq!(generator.generate().unwrap().chars().count(), length);
+    }
+
+    let generator = Generator { length: 200, exclude: String::from("abc#"), exclude_similar: true, ..Generator::default() };
+    let password = generator.generate().unwrap();
+    assert!(!password.contains(['a', 'b', 'c', '#']), "{}", password);
+    assert!(!password.contains(['1', 'l', 'I', '0', 'O']), "{}", password);
+}
+
+#[test]
+fn impossible_rules_fail() {
+    let rules = [
+        Generator { length: 0, ..Generator::default() },
+        Generator { length: 3, ..Generator::default() },
+        Generator { length: 8, min_digits: 5, min_symbols: 4, ..Generator::default() },
+        Generator { digits: false, min_digits: 1, ..Generator::default() },
+        Generator { lowercase: false, uppercase: false, digits: false, symbols: false, ..Generator::default() },
+        Generator { uppercase: false, digits: false, symbols: false, exclude: LOWERCASE.to_string(), ..Generator::default() },
+    ];
+    for generator in rules {
+        assert!(matches!(generator.generate(), Err(Error::Generator(_))), "{:?}", generator);
+        assert!(generator.entropy().is_err());
+    }
+}
+
+#[test]
+fn entropy() {
+    // 94 printable characters
+    let entropy = Generator { length: 20, ..Generator::default() }.entropy().unwrap();
+    assert!((entropy - 20.0 * 94f64.log2()).abs() < 1e-9, "{}", entropy);
+
+    let digits = Generator { length: 10, lowercase: false, uppercase: false, symbols: false, ..Generator::default() };
+    assert!((digits.entropy().unwrap() - 10.0 * 10f64.log2()).abs() < 1e-9);
+    let without_some = Generator { exclude: String::from("0123"), ..digits };
+    assert!((without_some.entropy().unwrap() - 10.0 * 6f64.log2()).abs() < 1e-9);
+}
+
+/// Runs `passman generate` with given config file, there is no vault
+fn generate(test: &str, config: &str, args: &[&str]) -> Output {
+    let dir = common::temp_dir(test);
+    common::write_config(&dir, config);
+    let mut command = common::command(&dir.join("vault.bin"));
+    command.arg("generate").args(args);
+    let output = common::run(command, "");
+    fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+#[test]
+fn resolves_generator_presets() {
+    let config = "[generators.pin]\nlength = 6\nlowercase = false\nuppercase = false\nsymbols = false\n";
+    let output = generate("preset", config, &["--preset", "pin"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let password = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    assert!(password.len() == 6 && password.chars().all(|c| c.is_ascii_digit()), "{}", password);
+
+    // Options given directly override the preset
+    let output = generate("override", config, &["12", "--preset", "pin", "--exclude", "0123456"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let password = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    assert!(password.len() == 12 && password.chars().all(|c| "789".contains(c)), "{}", password);
+
+    let output = generate("missing", config, &["--preset", "strong"]);
+    assert_eq!(output.status.code(), common::exit_code(Error::Config(String::new())));
+
+    // --profile chooses a vault and goes before the command, generate has no such option
+    let output = generate("global-profile", config, &["--profile", "pin"]);
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use passman::{Capitalization, Error, Passphrase, Recipe, Vault, DEFAULT_HISTORY_DEPTH};
+use common::{passman, PASSWORD};
+
+/// Vault with a github entry with all the metadata
+fn vault_with_github(test: &str) -> PathBuf {
+    let mut vault = common::create_vault(&common::temp_dir(test));
+    let entry = vault.insert("github", "first-password").unwrap();
+    entry.username = Some(String::from("bob"));
+    entry.urls = vec![String::from("https://github.com")];
+    entry.tags = vec![String::from("dev")];
+    entry.recipe = Some(Recipe::Passphrase(Passphrase { words: 5, separator: String::from("."), capitalization: Capitalization::Lower, digits: 0 }));
+    vault.set_notes("github", Some("klucze SSH")).unwrap();
+    vault.set_totp("github", Some("JBSWY3DPEHPK3PXP")).unwrap();
+    vault.save().unwrap();
+    vault.path().to_path_buf()
+}
+
+fn check_metadata(vault: &Vault, label: &str) {
+    let entry = vault.entry(label).unwrap();
+    assert_eq!(entry.username.as_deref(), Some("bob"));
+    assert_eq!(entry.urls, ["https://github.com"]);
+    assert_eq!(entry.tags, ["dev"]);
+    assert_eq!(vault.notes(label).unwrap().as_deref(), Some("klucze SSH"));
+    assert!(vault.totp(label).unwrap().is_some());
+}
+
+#[test]
+fn rename_keeps_everything() {
+    let path = vault_with_github("rename");
+    assert!(passman(&path, &["rename", "github", "Praca/github"], "").status.success());
+
+    let vault = Vault::open(&path, PASSWORD).unwrap();
+    assert!(!vault.contains("github"));
+    assert_eq!(vault.get("Praca/github").unwrap(), "first-password");
+    check_metadata(&vault, "Praca/github");
+    assert!(vault.entry("Praca/github").unwrap().recipe.is_some());
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn rotate_reuses_recipe() {
+    let path = vault_with_github("rotate");
+    for rotations in 1..=2 {
+        assert!(passman(&path, &["rotate", "github"], "").status.success());
+
+        let vault = Vault::open(&path, PASSWORD).unwrap();
+        let password = vault.get("github").unwrap();
+        assert_eq!(password.split('.').count(), 5, "{}", password);
+        assert_eq!(vault.history("github").unwrap().len(), rotations);
+        check_metadata(&vault, "github");
+    }
+
+    // Rules given for a rotation replace the stored ones
+    assert!(passman(&path, &["rotate", "github", "32", "--chars", "digits"], "").status.success());
+    let vault = Vault::open(&path, PASSWORD).unwrap();
+    let password = vault.get("github").unwrap();
+    assert!(password.len() == 32 && password.chars().all(|c| c.is_ascii_digit()));
+    assert!(matches!(vault.entry("github").unwrap().recipe, Some(Recipe::Password(_))));
+    assert_eq!(vault.history("github").unwrap().len(), 3);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn edit_keeps_previous_password() {
+    let path = vault_with_github("edit");
+    let output = passman(&path, &["edit", "github", "--stdin", "--allow-weak"], "second-password\n");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    assert_eq!(vault.get("github").unwrap(), "second-password");
+    assert_eq!(vault.history("github").unwrap().len(), 1);
+    check_metadata(&vault, "github");
+    // A typed password can't be generated again
+    assert!(vault.entry("github").unwrap().recipe.is_none());
+
+    vault.restore_password("github", 0, DEFAULT_HISTORY_DEPTH).unwrap();
+    assert_eq!(vault.get("github").unwrap(), "first-password");
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn edit_to_the_same_password_changes_nothing() {
+    let path = vault_with_github("edit-same");
+    let before = fs::read(&path).unwrap();
+
+    let output = passman(&path, &["edit", "github", "--stdin", "--allow-weak"], "first-password\n");
+    assert_eq!(output.status.code(), common::exit_code(Error::PasswordUnchanged));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("historii"));
+    assert_eq!(fs::read(&path).unwrap(), before);
+
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    assert!(matches!(vault.set_password("github", "first-password", DEFAULT_HISTORY_DEPTH), Err(Error::PasswordUnchanged)));
+    assert!(vault.history("github").unwrap().is_empty());
+    assert!(vault.entry("github").unwrap().recipe.is_some());
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn history_keeps_newest_passwords() {
+    let path = vault_with_github("depth");
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    for i in 2..=6 {
+        vault.set_password("github", &format!("password-{}", i), 3).unwrap();
+    }
+    // first-password, password-2 and password-3 were dropped first
+    assert_eq!(vault.history("github").unwrap().len(), 3);
+    let replaced: Vec<u64> = vault.history("github").unwrap().iter().map(|item| item.replaced).collect();
+    assert!(replaced.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    vault.restore_password("github", 0, 3).unwrap();
+    assert_eq!(vault.get("github").unwrap(), "password-3");
+    assert_eq!(vault.history("github").unwrap().len(), 3);
+
+    // Lower depth trims the history on the next change
+    vault.set_password("github", "password-7", 1).unwrap();
+    assert_eq!(vault.history("github").unwrap().len(), 1);
+    vault.restore_password("github", 0, 1).unwrap();
+    assert_eq!(vault.get("github").unwrap(), "password-3");
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn restore_from_history() {
+    let path = vault_with_github("restore");
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    vault.set_password("github", "second-password", DEFAULT_HISTORY_DEPTH).unwrap();
+    vault.set_password("github", "third-password", DEFAULT_HISTORY_DEPTH).unwrap();
+
+    assert!(matches!(vault.restore_password("github", 2, DEFAULT_HISTORY_DEPTH), Err(Error::HistoryNotFound)));
+    assert!(matches!(vault.restore_password("gitlab", 0, DEFAULT_HISTORY_DEPTH), Err(Error::LabelNotFound)));
+
+    // Restored password and the current one swap places
+    vault.restore_password("github", 0, DEFAULT_HISTORY_DEPTH).unwrap();
+    assert_eq!(vault.get("github").unwrap(), "first-password");
+    assert_eq!(vault.history("github").unwrap().len(), 2);
+    vault.restore_password("github", 1, DEFAULT_HISTORY_DEPTH).unwrap();
+    assert_eq!(vault.get("github").unwrap(), "third-password");
+    check_metadata(&vault, "github");
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn restoring_current_password_keeps_history() {
+    let path = vault_with_github("restore-current");
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    vault.set_password("github", "second-password", DEFAULT_HISTORY_DEPTH).unwrap();
+    vault.set_password("github", "first-password", DEFAULT_HISTORY_DEPTH).unwrap();
+
+    // The oldest item is the same as the current password
+    assert!(matches!(vault.restore_password("github", 0, DEFAULT_HISTORY_DEPTH), Err(Error::PasswordUnchanged)));
+    assert_eq!(vault.history("github").unwrap().len(), 2);
+    assert_eq!(vault.get("github").unwrap(), "first-password");
+
+    vault.restore_password("github", 1, DEFAULT_HISTORY_DEPTH).unwrap();
+    assert_eq!(vault.get("github").unwrap(), "second-password");
+    vault.restore_password("github", 0, DEFAULT_HISTORY_DEPTH).unwrap();
+    assert_eq!(vault.get("github").unwrap(), "first-password");
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use passman::{read_import, ColumnMapping, Import, ImportFormat, ImportedEntry, Vault};
+use common::PASSWORD;
+
+// Small exports of other password managers, written by hand after their documented formats
+const FIXTURES: &str = "tests/fixtures/import";
+const TOTP: &str = "JBSWY3DPEHPK3PXP";
+
+fn import(file: &str, format: ImportFormat, mapping: &[&str]) -> Import {
+    let mapping: Vec<String> = mapping.iter().map(|pair| pair.to_string()).collect();
+    let path = Path::new(FIXTURES).join(file);
+    assert_eq!(ImportFormat::detect(&path), format);
+    read_import(&path, format, &ColumnMapping::parse(&mapping).unwrap()).unwrap()
+}
+
+fn find<'a>(import: &'a Import, label: &str) -> &'a ImportedEntry {
+    import.entries.iter().find(|entry| entry.label == label).unwrap_or_else(|| panic!("brak {}", label))
+}
+
+fn labels(import: &Import) -> Vec<&str> {
+    let mut labels: Vec<&str> = import.entries.iter().map(|entry| entry.label.as_str()).collect();
+    labels.sort();
+    labels
+}
+
+#[test]
+fn reads_bitwarden() {
+    let import = import("bitwarden.json", ImportFormat::Bitwarden, &[]);
+    assert_eq!(labels(&import), ["example.com", "github"]);
+    assert_eq!(import.without_password, 1);
+
+    let github = find(&import, "github");
+    assert_eq!(github.password, "bw-github-pass");
+    assert_eq!(github.username.as_deref(), Some("bob"));
+    assert_eq!(github.urls, ["https://github.com/login", "https://gist.github.com"]);
+    assert_eq!(github.notes.as_deref(), Some("klucz SSH w sejfie firmowym"));
+    assert_eq!(github.tags, ["Praca"]);
+    assert_eq!(github.totp.as_deref(), Some(TOTP));
+
+    // Label of an entry without a name comes from its URL
+    let example = find(&import, "example.com");
+    assert_eq!(example.username.as_deref(), Some("anna"));
+    assert!(example.notes.is_none() && example.totp.is_none() && example.tags.is_empty());
+}
+
+#[test]
+fn reads_keepass_xml() {
+    let import = import("keepass.xml", ImportFormat::KeePass, &[]);
+    // Recycle bin and history are left out
+    assert_eq!(labels(&import), ["bank", "gmail"]);
+
+    let bank = find(&import, "bank");
+    assert_eq!(bank.password, "kp-bank-pass");
+    assert_eq!(bank.username.as_deref(), Some("jan"));
+    assert_eq!(bank.urls, ["https://bank.example.pl"]);
+    assert_eq!(bank.notes.as_deref(), Some("numer klienta 123"));
+    assert_eq!(bank.tags, ["finanse", "dom"]);
+    assert_eq!(bank.totp.as_deref(), Some("otpauth://totp/bank?secret=JBSWY3DPEHPK3PXP"));
+
+    // Groups become tags, custom fields go to the notes
+    let gmail = find(&import, "gmail");
+    assert_eq!(gmail.tags, ["Email"]);
+    assert_eq!(gmail.notes.as_deref(), Some("PIN: 1234"));
+}
+
+#[test]
+fn reads_1password() {
+    let import = import("1password.1pux", ImportFormat::OnePassword, &[]);
+    // Archived items are left out
+    assert_eq!(labels(&import), ["github", "router"]);
+    assert_eq!(import.without_password, 1);
+
+    let github = find(&import, "github");
+    assert_eq!(github.password, "op-github-pass");
+    assert_eq!(github.username.as_deref(), Some("bob"));
+    assert_eq!(github.urls, ["https://github.com/login", "https://gist.github.com"]);
+    assert_eq!(github.notes.as_deref(), Some("konto z 2FA"));
+    assert_eq!(github.tags, ["dev", "praca"]);
+    assert_eq!(github.totp.as_deref(), Some(TOTP));
+
+    // Wrapped item of an older export, with the password outside login fields
+    let router = find(&import, "router");
+    assert_eq!(router.password, "op-router-pass");
+    assert_eq!(router.urls, ["http://192.168.1.1"]);
+    assert!(router.username.is_none());
+}
+
+#[test]
+fn reads_browser_csv() {
+    let chrome = import("chrome.csv", ImportFormat::Csv, &[]);
+    assert_eq!(labels(&chrome), ["example.com", "github"]);
+    assert_eq!(chrome.without_password, 1);
+    let github = find(&chrome, "github");
+    assert_eq!(github.password, "chrome-github-pass");
+    assert_eq!(github.username.as_deref(), Some("bob"));
+    assert_eq!(github.urls, ["https://github.com/login"]);
+    assert_eq!(github.notes.as_deref(), Some("konto prywatne"));
+
+    // Firefox has no names, labels are hosts without www. and port
+    let firefox = import("firefox.csv", ImportFormat::Csv, &[]);
+    assert_eq!(labels(&firefox), ["accounts.example.com", "github.com"]);
+    let example = find(&firefox, "accounts.example.com");
+    assert_eq!(example.password, "firefox-example-pass");
+    assert_eq!(example.username.as_deref(), Some("anna@example.com"));
+    assert_eq!(example.urls, ["https://accounts.example.com:8443"]);
+}
+
+#[test]
+fn maps_csv_columns() {
+    let mapping = ["label=Tytuł", "password=Hasło", "url=Adres", "totp=Kod", "tags=Kategoria"];
+    let import = import("custom.csv", ImportFormat::Csv, &mapping);
+    let server = find(&import, "serwer");
+    assert_eq!(server.password, "custom-server-pass");
+    // Known column names still work next to the mapped ones
+    assert_eq!(server.username.as_deref(), Some("root"));
+    assert_eq!(server.urls, ["ssh://serwer.example.pl"]);
+    assert_eq!(server.tags, ["praca", "linux"]);
+    assert_eq!(server.totp.as_deref(), Some(TOTP));
+
+    // Without the mapping there is no password column
+    let path = Path::new(FIXTURES).join("custom.csv");
+    assert!(read_import(&path, ImportFormat::Csv, &ColumnMapping::default()).is_err());
+    assert!(ColumnMapping::parse(&[String::from("haslo=Hasło")]).is_err());
+    assert!(ColumnMapping::parse(&[String::from("password")]).is_err());
+}
+
+/// Vault with a github entry
+fn vault_with_github(test: &str) -> PathBuf {
+    let mut vault = common::create_vault(&common::temp_dir(test));
+    vault.insert("github", "existing-pass").unwrap();
+    vault.save().unwrap();
+    vault.path().to_path_buf()
+}
+
+fn run_import(vault: &Path, args: &[&str]) -> Output {
+    let mut command = common::command(vault);
+    command.arg("import").arg(Path::new(FIXTURES).join("bitwarden.json")).args(args);
+    let output = common::run(command, "");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output
+}
+
+#[test]
+fn renamed_collision_keeps_existing_entry() {
+    let path = vault_with_github("rename");
+    run_import(&path, &["--on-conflict", "rename"]);
+
+    let vault = Vault::open(&path, PASSWORD).unwrap();
+    let mut labels: Vec<&str> = vault.iter_labels().collect();
+    labels.sort();
+    assert_eq!(labels, ["example.com", "github", "github-2"]);
+    assert_eq!(vault.get("github").unwrap(), "existing-pass");
+    assert!(vault.entry("github").unwrap().username.is_none());
+    assert_eq!(vault.get("github-2").unwrap(), "bw-github-pass");
+    assert_eq!(vault.entry("github-2").unwrap().username.as_deref(), Some("bob"));
+    assert!(vault.totp("github-2").unwrap().is_some());
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn dry_run_changes_nothing() {
+    let path = vault_with_github("dry-run");
+    let before = fs::read(&path).unwrap();
+    let backups = Vault::open(&path, PASSWORD).unwrap().backups().len();
+
+    for on_conflict in ["ask", "rename", "skip"] {
+        let output = run_import(&path, &["--dry-run", "--on-conflict", on_conflict]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("+ example.com"));
+    }
+    assert_eq!(fs::read(&path).unwrap(), before);
+    assert_eq!(Vault::open(&path, PASSWORD).unwrap().backups().len(), backups);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,203 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use passman::{Error, Vault, DEFAULT_HISTORY_DEPTH};
+
+// Databases made by tests/fixtures/kdbx.py, independently of passman
+const ARGON2_AES: &str = "tests/fixtures/argon2id-aes.kdbx";
+const AESKDF_CHACHA20: &str = "tests/fixtures/aeskdf-chacha20.kdbx";
+const PASSWORD: &str = "fixture-password";
+
+fn copy_fixture(fixture: &str, test: &str) -> PathBuf {
+    let dir = common::temp_dir(test);
+    let path = dir.join(Path::new(fixture).file_name().unwrap());
+    fs::copy(fixture, &path).unwrap();
+    path
+}
+
+fn sorted_labels(vault: &Vault) -> Vec<&str> {
+    let mut labels: Vec<&str> = vault.iter_labels().collect();
+    labels.sort();
+    labels
+}
+
+fn check_fixture(vault: &Vault) {
+    assert_eq!(sorted_labels(vault), ["Email/Work/outlook", "Email/gmail", "bank", "bank-2", "github"]);
+
+    let github = vault.entry("github").unwrap();
+    assert_eq!(vault.get("github").unwrap(), "gh-Pa55word!");
+    assert_eq!(github.username.as_deref(), Some("bob"));
+    assert_eq!(github.urls, ["https://github.com", "https://gist.github.com"]);
+    assert_eq!(github.tags, ["dev", "work"]);
+    assert_eq!(github.created, 1700000000);
+    assert_eq!(github.modified, 1700086400);
+    assert_eq!(vault.notes("github").unwrap().as_deref(), Some("konto\nfirmowe"));
+    assert!(vault.totp("github").unwrap().is_some());
+    assert_eq!(github.history().len(), 1);
+
+    assert_eq!(vault.get("Email/gmail").unwrap(), "zażółć-gęślą");
+    assert_eq!(vault.entry("Email/gmail").unwrap().urls, ["https://mail.google.com"]);
+    let banks = [vault.get("bank").unwrap(), vault.get("bank-2").unwrap()];
+    assert!(banks.contains(&String::from("s3cret")) && banks.contains(&String::from("drugie")));
+}
+
+#[test]
+fn opens_argon2_aes() {
+    let vault = Vault::open(ARGON2_AES, PASSWORD).unwrap();
+    check_fixture(&vault);
+    assert!(vault.is_keepass());
+    assert_eq!(vault.kdf().unwrap().memory_kib, 1024);
+}
+
+#[test]
+fn opens_aeskdf_chacha20() {
+    let vault = Vault::open(AESKDF_CHACHA20, PASSWORD).unwrap();
+    check_fixture(&vault);
+    assert!(matches!(vault.kdf(), Err(Error::Kdbx(_))));
+}
+
+#[test]
+fn wrong_password() {
+    assert!(matches!(Vault::open(ARGON2_AES, "wrong"), Err(Error::WrongPassword)));
+    assert!(matches!(Vault::open(AESKDF_CHACHA20, "wrong"), Err(Error::WrongPassword)));
+}
+
+#[test]
+fn changes_are_written_back() {
+    for (fixture, test) in [(ARGON2_AES, "changes-argon2"), (AESKDF_CHACHA20, "changes-aeskdf")] {
+        let path = copy_fixture(fixture, test);
+        let mut vault = Vault::open(&path, PASSWORD).unwrap();
+
+        let entry = vault.insert("Email/Work/slack", "nowe-hasło").unwrap();
+        entry.username = Some(String::from("jan"));
+        entry.urls = vec![String::from("https://slack.com"), String::from("https://app.slack.com")];
+        entry.tags = vec![String::from("work")];
+        vault.remove("bank-2").unwrap();
+        vault.rename("Email/gmail", "Private/gmail").unwrap();
+        vault.set_notes("github", Some("nowa notatka")).unwrap();
+        vault.set_password("bank", "nowe-s3cret", DEFAULT_HISTORY_DEPTH).unwrap();
+        vault.save().unwrap();
+
+        let vault = Vault::open(&path, PASSWORD).unwrap();
+        assert_eq!(sorted_labels(&vault), ["Email/Work/outlook", "Email/Work/slack", "Private/gmail", "bank", "github"]);
+        assert_eq!(vault.get("Email/Work/slack").unwrap(), "nowe-hasło");
+        let slack = vault.entry("Email/Work/slack").unwrap();
+        assert_eq!(slack.username.as_deref(), Some("jan"));
+        assert_eq!(slack.urls, ["https://slack.com", "https://app.slack.com"]);
+        assert_eq!(slack.tags, ["work"]);
+        assert_eq!(vault.get("Private/gmail").unwrap(), "zażółć-gęślą");
+        assert_eq!(vault.notes("github").unwrap().as_deref(), Some("nowa notatka"));
+        assert_eq!(vault.get("github").unwrap(), "gh-Pa55word!");
+        assert!(vault.totp("github").unwrap().is_some());
+        assert_eq!(vault.get("bank").unwrap(), "nowe-s3cret");
+        assert_eq!(vault.entry("bank").unwrap().history().len(), 1);
+    }
+}
+
+#[test]
+fn change_password() {
+    let path = copy_fixture(ARGON2_AES, "change-password");
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    vault.change_password("inne-hasło").unwrap();
+    assert!(vault.check_password("inne-hasło").unwrap());
+
+    assert!(matches!(Vault::open(&path, PASSWORD), Err(Error::WrongPassword)));
+    check_fixture(&Vault::open(&path, "inne-hasło").unwrap());
+}
+
+#[test]
+fn read_only() {
+    let path = copy_fixture(ARGON2_AES, "read-only");
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    vault.set_read_only(true);
+    vault.insert("nowe", "hasło").unwrap();
+
+    assert!(matches!(vault.save(), Err(Error::ReadOnly)));
+    assert!(matches!(vault.change_password("inne-hasło"), Err(Error::ReadOnly)));
+    assert_eq!(fs::read(&path).unwrap(), fs::read(ARGON2_AES).unwrap());
+}
+
+#[test]
+fn recycle_bin_is_the_trash() {
+    let path = copy_fixture(ARGON2_AES, "trash");
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    let labels = |vault: &Vault| vault.trash().iter().map(|trashed| trashed.label.clone()).collect::<Vec<_>>();
+    assert_eq!(labels(&vault), ["deleted"]);
+
+    vault.remove("github").unwrap();
+    vault.save().unwrap();
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    assert_eq!(labels(&vault), ["deleted", "github"]);
+    assert!(!vault.contains("github"));
+
+    vault.restore_trashed("github").unwrap();
+    vault.purge_trash(u64::MAX);
+    vault.save().unwrap();
+    let vault = Vault::open(&path, PASSWORD).unwrap();
+    assert!(vault.trash().is_empty());
+    check_fixture(&vault);
+}
+
+#[test]
+fn folders_are_groups() {
+    let path = copy_fixture(ARGON2_AES, "folders");
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    assert!(matches!(vault.rename_folder("Brak", "Inne"), Err(Error::FolderNotFound)));
+    vault.insert("Inne/gmail", "hasło").unwrap();
+    assert!(matches!(vault.rename_folder("Email", "Inne"), Err(Error::LabelExists)));
+    vault.remove("Inne/gmail").unwrap();
+    assert_eq!(vault.rename_folder("Email", "Poczta/Email").unwrap(), 2);
+    vault.save().unwrap();
+
+    let vault = Vault::open(&path, PASSWORD).unwrap();
+    assert_eq!(sorted_labels(&vault), ["Poczta/Email/Work/outlook", "Poczta/Email/gmail", "bank", "bank-2", "github"]);
+    assert_eq!(vault.get("Poczta/Email/gmail").unwrap(), "zażółć-gęślą");
+}
+
+#[test]
+fn get_leaves_database_unchanged() {
+    let path = copy_fixture(AESKDF_CHACHA20, "get");
+    let before = fs::read(&path).unwrap();
+    let dir = path.parent().unwrap();
+
+    let mut command = common::command(&path);
+    command.args(["get", "github", "--stdout"]).env("PASSMAN_TEST_PASSWORD", PASSWORD);
+    let output = common::run(command, "");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "gh-Pa55word!");
+
+    // Time of use alone isn't worth rewriting a database shared with KeePassXC
+    assert_eq!(fs::read(&path).unwrap(), before);
+    assert!(!dir.join("backups").exists());
+}
+
+#[test]
+fn tampered_block() {
+    let path = copy_fixture(ARGON2_AES, "tampered");
+    let mut data = fs::read(&path).unwrap();
+    // Last block is the empty one (HMAC and length), the byte is in the data before it
+    let index = data.len() - 36 - 10;
+    data[index] ^= 1;
+    fs::write(&path, &data).unwrap();
+    assert!(matches!(Vault::open(&path, PASSWORD), Err(Error::Tampered)));
+}
+
+#[test]
+fn excessive_aes_kdf_rounds() {
+    use sha2::{Digest, Sha256};
+
+    let path = copy_fixture(AESKDF_CHACHA20, "rounds");
+    let mut data = fs::read(&path).unwrap();
+    // UInt64 variant "R" of the KDF parameters, the header hash is fixed up after changing it
+    let rounds = b"\x05\x01\x00\x00\x00R\x08\x00\x00\x00";
+    let start = data.windows(rounds.len()).position(|window| window == rounds).unwrap() + rounds.len();
+    data[start..start + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    let end_of_header = b"\x00\x04\x00\x00\x00\r\n\r\n";
+    let end = data.windows(end_of_header.len()).position(|window| window == end_of_header).unwrap() + end_of_header.len();
+    let hash = Sha256::digest(&data[..end]);
+    data[end..end + 32].copy_from_slice(&hash);
+    fs::write(&path, &data).unwrap();
+
+    assert!(matches!(Vault::open(&path, PASSWORD), Err(Error::Kdbx(_))));
+}
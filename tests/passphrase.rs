@@ -0,0 +1,66 @@
+use passman::{Capitalization, Passphrase, Recipe};
+
+fn recipe(words: usize, separator: &str, capitalization: Capitalization, digits: usize) -> Recipe {
+    Recipe::Passphrase(Passphrase { words, separator: separator.to_string(), capitalization, digits })
+}
+
+#[test]
+fn generates_words_with_separator() {
+    let passphrase = recipe(5, ".", Capitalization::Lower, 0).generate().unwrap();
+    let words: Vec<&str> = passphrase.split('.').collect();
+    assert_eq!(words.len(), 5);
+    assert!(words.iter().all(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_lowercase())));
+
+    let passphrase = recipe(3, "", Capitalization::Title, 0).generate().unwrap();
+    assert_eq!(passphrase.chars().filter(char::is_ascii_uppercase).count(), 3);
+    assert!(passphrase.starts_with(|c: char| c.is_ascii_uppercase()));
+}
+
+#[test]
+fn capitalizes_words() {
+    let passphrase = recipe(6, " ", Capitalization::Title, 0).generate().unwrap();
+    for word in passphrase.split(' ') {
+        let mut chars = word.chars();
+        assert!(chars.next().unwrap().is_ascii_uppercase());
+        assert!(chars.all(|c| c.is_ascii_lowercase()));
+    }
+
+    let passphrase = recipe(6, " ", Capitalization::Random, 0).generate().unwrap();
+    for word in passphrase.split(' ') {
+        assert!(word.chars().skip(1).all(|c| c.is_ascii_lowercase()));
+    }
+}
+
+#[test]
+fn appends_digits_to_words() {
+    let passphrase = recipe(4, "-", Capitalization::Lower, 3).generate().unwrap();
+    assert_eq!(passphrase.split('-').count(), 4);
+    assert_eq!(passphrase.chars().filter(char::is_ascii_digit).count(), 3);
+    // Digits only ever end words
+    for word in passphrase.split('-') {
+        let letters = word.trim_end_matches(|c: char| c.is_ascii_digit());
+        assert!(!letters.is_empty() && letters.chars().all(|c| c.is_ascii_lowercase()));
+    }
+}
+
+#[test]
+fn refuses_no_words() {
+    assert!(recipe(0, "-", Capitalization::Lower, 0).generate().is_err());
+}
+
+#[test]
+fn reports_entropy() {
+    let word = recipe(1, "-", Capitalization::Lower, 0).entropy().unwrap();
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+    // Every word is one of a whole number of at least 2048 of them
+    assert!(word >= 11.0);
+    assert!(close(word.exp2().round().log2(), word));
+
+    assert!(close(recipe(4, "-", Capitalization::Lower, 0).entropy().unwrap(), 4.0 * word));
+    // Separator is known and fixed capitalization adds nothing
+    assert!(close(recipe(4, "", Capitalization::Title, 0).entropy().unwrap(), 4.0 * word));
+    // A bit per randomly capitalized word
+    assert!(close(recipe(4, "-", Capitalization::Random, 0).entropy().unwrap(), 4.0 * word + 4.0));
+    // Each digit is one of 10, the words they're appended to don't count
+    assert!(close(recipe(4, "-", Capitalization::Lower, 2).entropy().unwrap(), 4.0 * word + 2.0 * 10f64.log2()));
+}
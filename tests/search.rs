@@ -0,0 +1,41 @@
+use passman::{fuzzy_score, MatchedField, SearchIndex, Vault};
+
+#[test]
+fn subsequences_match() {
+    assert!(fuzzy_score("gthb", "github").is_some());
+    assert!(fuzzy_score("GH", "GitHub").is_some());
+    assert!(fuzzy_score("hg", "github").is_none());
+    assert!(fuzzy_score("GH", "github").is_none());
+    assert_eq!(fuzzy_score("", "github"), Some(0));
+}
+
+#[test]
+fn better_matches_score_higher() {
+    let score = |pattern, text| fuzzy_score(pattern, text).unwrap();
+    // Consecutive characters
+    assert!(score("git", "github") > score("git", "gaming/it"));
+    // Starts of words
+    assert!(score("ap", "aws/prod") > score("ap", "maple"));
+    assert!(score("wp", "work/prod") > score("wp", "wipe"));
+}
+
+#[test]
+fn searches_metadata() {
+    let vault = Vault::open("tests/fixtures/argon2id-aes.kdbx", "fixture-password").unwrap();
+    let index = SearchIndex::new(&vault);
+
+    let found = index.search("gml");
+    assert_eq!(found[0].label, "Email/gmail");
+    assert!(found[0].matched.is_empty());
+
+    // Username of Email/Work/outlook, URL of github
+    let labels = |query| index.search(query).into_iter().map(|found| found.label).collect::<Vec<_>>();
+    assert_eq!(labels("firma.pl"), ["Email/Work/outlook"]);
+    assert_eq!(labels("gist"), ["github"]);
+    assert_eq!(index.search("gist")[0].matched, [(MatchedField::Url, String::from("https://gist.github.com"))]);
+
+    // Every word has to match
+    assert_eq!(labels("email work"), ["Email/Work/outlook"]);
+    assert!(labels("github zzz").is_empty());
+    assert!(labels("gh-Pa55word").is_empty());
+}
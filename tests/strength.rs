@@ -0,0 +1,62 @@
+mod common;
+
+use std::fs;
+use passman::{estimate_strength, Error, Vault};
+use common::PASSWORD;
+
+#[test]
+fn common_passwords_are_weakest() {
+    for password in ["password", "P@ssw0rd", "qwerty123"] {
+        let strength = estimate_strength(password, &[]);
+        assert_eq!(strength.score, 0, "{}", password);
+        assert!(strength.warning.is_some(), "{}", password);
+        assert!(!strength.suggestions.is_empty(), "{}", password);
+    }
+
+    // Substitutions are found, but make the password a bit harder to guess
+    let leet = estimate_strength("P@ssw0rd", &[]);
+    assert!(leet.guesses > estimate_strength("password", &[]).guesses);
+    assert!(leet.suggestions.iter().any(|suggestion| suggestion.contains('@')));
+}
+
+#[test]
+fn patterns_are_weak() {
+    let date = estimate_strength("19900101", &[]);
+    assert!(date.score <= 1);
+    assert!(date.warning.unwrap().contains("Daty"));
+
+    let repeat = estimate_strength("aaaaaaaa", &[]);
+    assert_eq!(repeat.score, 0);
+    assert!(repeat.warning.unwrap().contains("Powtórzenia"));
+}
+
+#[test]
+fn long_passwords_are_strong() {
+    // Four words from the passphrase list
+    let passphrase = estimate_strength("abandon-ability-able-about", &[]);
+    assert_eq!(passphrase.score, 4);
+    assert!(passphrase.warning.is_none());
+
+    let random = estimate_strength("kX9#vQ2$mL7!pR4z", &[]);
+    assert_eq!(random.score, 4);
+    assert!(random.warning.is_none());
+    assert!(random.suggestions.is_empty());
+}
+
+#[test]
+fn user_inputs_are_guessable() {
+    assert!(estimate_strength("kowalski1987", &["kowalski"]).guesses < estimate_strength("kowalski1987", &[]).guesses);
+}
+
+#[test]
+fn empty_master_password_is_refused() {
+    let dir = common::temp_dir("empty-master");
+    let path = common::create_vault(&dir).path().to_path_buf();
+
+    // Allowing weak passwords doesn't let an empty one through
+    let output = common::passman(&path, &["change-password", "--yes", "--stdin", "--allow-weak"], "\n");
+    assert_eq!(output.status.code(), common::exit_code(Error::WeakPassword));
+
+    assert!(Vault::open(&path, PASSWORD).is_ok());
+    fs::remove_dir_all(&dir).unwrap();
+}
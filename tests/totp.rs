@@ -0,0 +1,95 @@
+use passman::{Totp, TotpAlgorithm};
+
+// Test vectors from RFC 6238, appendix B
+const SHA1_SECRET: &[u8] = b"12345678901234567890";
+const SHA256_SECRET: &[u8] = b"12345678901234567890123456789012";
+const SHA512_SECRET: &[u8] = b"1234567890123456789012345678901234567890123456789012345678901234";
+
+const VECTORS: [(u64, &str, &str, &str); 6] = [
+    (59, "94287082", "46119246", "90693936"),
+    (1111111109, "07081804", "68084774", "25091201"),
+    (1111111111, "14050471", "67062674", "99943326"),
+    (1234567890, "89005924", "91819424", "93441116"),
+    (2000000000, "69279037", "90698825", "38618901"),
+    (20000000000, "65353130", "77737706", "47863826"),
+];
+
+fn rfc_totp(secret: &[u8], algorithm: TotpAlgorithm) -> Totp {
+    Totp::new(secret.to_vec(), algorithm, 8, 30).unwrap()
+}
+
+#[test]
+fn rfc6238_sha1() {
+    let totp = rfc_totp(SHA1_SECRET, TotpAlgorithm::Sha1);
+    for (time, code, _, _) in VECTORS {
+        assert_eq!(totp.code_at(time), code, "time {}", time);
+    }
+}
+
+#[test]
+fn rfc6238_sha256() {
+    let totp = rfc_totp(SHA256_SECRET, TotpAlgorithm::Sha256);
+    for (time, _, code, _) in VECTORS {
+        assert_eq!(totp.code_at(time), code, "time {}", time);
+    }
+}
+
+#[test]
+fn rfc6238_sha512() {
+    let totp = rfc_totp(SHA512_SECRET, TotpAlgorithm::Sha512);
+    for (time, _, _, code) in VECTORS {
+        assert_eq!(totp.code_at(time), code, "time {}", time);
+    }
+}
+
+#[test]
+fn six_digits_keep_leading_zeros() {
+    // Last 6 digits of the 8 digit codes
+    let totp = Totp::new(SHA1_SECRET.to_vec(), TotpAlgorithm::Sha1, 6, 30).unwrap();
+    assert_eq!(totp.code_at(59), "287082");
+    assert_eq!(totp.code_at(1111111109), "081804");
+}
+
+#[test]
+fn parses_otpauth_uri() {
+    // "12345678901234567890" in base32
+    let uri = "otpauth://totp/ACME%20Co:john@example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+        &issuer=ACME%20Co&algorithm=SHA1&digits=8&period=30";
+    let totp = Totp::from_uri(uri).unwrap();
+
+    let mut expected = rfc_totp(SHA1_SECRET, TotpAlgorithm::Sha1);
+    expected.issuer = Some(String::from("ACME Co"));
+    assert_eq!(totp, expected);
+    assert_eq!(totp.code_at(1234567890), "89005924");
+}
+
+#[test]
+fn uri_defaults_and_custom_period() {
+    let totp = Totp::parse("otpauth://totp/Example:alice?secret=gezd gnbv gy3t qojq gezd gnbv gy3t qojq&period=60").unwrap();
+    assert_eq!(totp.algorithm, TotpAlgorithm::Sha1);
+    assert_eq!(totp.digits, 6);
+    assert_eq!(totp.period, 60);
+    // Issuer is taken from the label when there is no parameter
+    assert_eq!(totp.issuer.as_deref(), Some("Example"));
+    // Same counter as time 59 with 30 s period
+    assert_eq!(totp.code_at(119), "287082");
+    assert_eq!(totp.remaining(119), 1);
+}
+
+#[test]
+fn bare_secret_uses_defaults() {
+    let totp = Totp::parse("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap();
+    assert_eq!(totp.code_at(59), "287082");
+    assert_eq!(totp.remaining(59), 1);
+    assert_eq!(totp.remaining(60), 30);
+}
+
+#[test]
+fn rejects_invalid_input() {
+    assert!(Totp::parse("otpauth://hotp/x?secret=GEZDGNBV&counter=1").is_err());
+    assert!(Totp::parse("otpauth://totp/x?issuer=y").is_err());
+    assert!(Totp::parse("otpauth://totp/x?secret=GEZDGNBV&algorithm=MD5").is_err());
+    assert!(Totp::parse("otpauth://totp/x?secret=GEZDGNBV&digits=4").is_err());
+    assert!(Totp::parse("otpauth://totp/x?secret=GEZDGNBV&period=0").is_err());
+    assert!(Totp::parse("not base32!").is_err());
+}
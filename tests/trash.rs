@@ -0,0 +1,117 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use passman::{Error, Vault};
+use common::PASSWORD;
+
+fn create_vault(test: &str) -> (PathBuf, Vault) {
+    let vault = common::create_vault(&common::temp_dir(test));
+    (vault.path().to_path_buf(), vault)
+}
+
+#[test]
+fn remove_moves_to_trash() {
+    let (path, mut vault) = create_vault("remove");
+    vault.insert("github", "secret").unwrap().username = Some(String::from("bob"));
+    vault.remove("github").unwrap();
+    assert!(matches!(vault.remove("github"), Err(Error::LabelNotFound)));
+    vault.save().unwrap();
+
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    assert!(!vault.contains("github"));
+    assert_eq!(vault.trash().len(), 1);
+    assert_eq!(vault.trash()[0].label, "github");
+    assert_eq!(vault.trash()[0].entry().username.as_deref(), Some("bob"));
+
+    vault.restore_trashed("github").unwrap();
+    assert!(vault.trash().is_empty());
+    assert_eq!(vault.get("github").unwrap(), "secret");
+    assert!(matches!(vault.restore_trashed("github"), Err(Error::LabelExists)));
+    assert!(matches!(vault.restore_trashed("gitlab"), Err(Error::LabelNotFound)));
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn restore_with_label_collision() {
+    let (path, mut vault) = create_vault("collision");
+    vault.insert("github", "first").unwrap();
+    vault.remove("github").unwrap();
+    vault.insert("github", "second").unwrap();
+    vault.remove("github").unwrap();
+    vault.insert("github", "third").unwrap();
+
+    // Label is used again, nothing leaves the trash
+    assert!(matches!(vault.restore_trashed("github"), Err(Error::LabelExists)));
+    assert_eq!(vault.trash().len(), 2);
+    assert_eq!(vault.get("github").unwrap(), "third");
+
+    // The most recently removed one comes back first
+    vault.rename("github", "github-old").unwrap();
+    vault.restore_trashed("github").unwrap();
+    assert_eq!(vault.get("github").unwrap(), "second");
+    assert_eq!(vault.trash().len(), 1);
+    vault.rename("github", "github-second").unwrap();
+    vault.restore_trashed("github").unwrap();
+    assert_eq!(vault.get("github").unwrap(), "first");
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn purge_deletes_for_good() {
+    let (path, mut vault) = create_vault("purge");
+    vault.insert("github", "secret").unwrap();
+    vault.insert("bank", "secret").unwrap();
+    vault.remove("github").unwrap();
+    vault.remove("bank").unwrap();
+    let deleted = vault.trash()[0].deleted;
+
+    // Only entries removed before the time are deleted
+    assert_eq!(vault.purge_trash(deleted), 0);
+    assert_eq!(vault.purge_trash(u64::MAX), 2);
+    vault.save().unwrap();
+
+    let vault = Vault::open(&path, PASSWORD).unwrap();
+    assert!(vault.trash().is_empty());
+    assert_eq!(vault.iter_labels().count(), 0);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+/// Runs passman on the vault with given config file
+fn passman(vault: &Path, config: &str, args: &[&str]) -> Output {
+    common::write_config(vault.parent().unwrap(), config);
+    let output = common::passman(vault, args, "");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output
+}
+
+#[test]
+fn old_entries_expire() {
+    let (path, _) = create_vault("expire");
+    // Entry moved to the recycle bin of the KeePass fixture has no time of the move,
+    // so it counts as removed long ago in a copy of the database
+    let keepass = Vault::open("tests/fixtures/argon2id-aes.kdbx", "fixture-password").unwrap();
+    fs::remove_file(&path).unwrap();
+    keepass.export_encrypted(&path, PASSWORD).unwrap();
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    assert_eq!(vault.trash()[0].label, "deleted");
+    vault.remove("github").unwrap();
+    vault.save().unwrap();
+
+    // 0 keeps everything until the trash is purged
+    let output = passman(&path, "trash_retention = 0\n", &["trash", "list"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("deleted"));
+
+    let output = passman(&path, "trash_retention = 30\n", &["trash", "list"]);
+    let listed = String::from_utf8_lossy(&output.stdout);
+    assert!(listed.contains("github") && !listed.contains("deleted"));
+    // Nothing is saved only because entries expired
+    assert_eq!(Vault::open(&path, PASSWORD).unwrap().trash().len(), 2);
+
+    passman(&path, "trash_retention = 30\n", &["remove", "bank", "--yes"]);
+    let vault = Vault::open(&path, PASSWORD).unwrap();
+    let labels: Vec<&str> = vault.trash().iter().map(|trashed| trashed.label.as_str()).collect();
+    assert_eq!(labels, ["github", "bank"]);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
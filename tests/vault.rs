@@ -0,0 +1,85 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use passman::{Vault, MAX_BACKUPS};
+use common::PASSWORD;
+
+/// Empty vault in its own directory, its creation leaves no backup
+fn create_vault(test: &str) -> (PathBuf, Vault) {
+    let vault = common::create_vault(&common::temp_dir(test));
+    (vault.path().to_path_buf(), vault)
+}
+
+#[cfg(unix)]
+fn mode(path: &std::path::Path) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+#[cfg(unix)]
+#[test]
+fn vault_file_is_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (path, mut vault) = create_vault("private");
+    assert_eq!(mode(&path), 0o600);
+
+    // Stricter mode set by the user stays, looser one doesn't
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o400)).unwrap();
+    vault.insert("github", "secret").unwrap();
+    vault.save().unwrap();
+    assert_eq!(mode(&path), 0o400);
+
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+    vault.save().unwrap();
+    assert_eq!(mode(&path), 0o600);
+    for backup in vault.backups() {
+        assert_eq!(mode(&backup.path), 0o600);
+    }
+    assert!(!path.with_file_name("vault.bin.tmp").exists());
+}
+
+#[test]
+fn backups_are_rotated() {
+    let (path, _) = create_vault("backups");
+
+    // Every opened vault backs up the file once, before its first save
+    for i in 0..MAX_BACKUPS + 2 {
+        let mut vault = Vault::open(&path, PASSWORD).unwrap();
+        vault.insert(&format!("entry-{}", i), "secret").unwrap();
+        vault.save().unwrap();
+        vault.insert(&format!("second-{}", i), "secret").unwrap();
+        vault.save().unwrap();
+    }
+
+    let vault = Vault::open(&path, PASSWORD).unwrap();
+    let backups = vault.backups();
+    assert_eq!(backups.len(), MAX_BACKUPS);
+    assert!(backups.windows(2).all(|pair| pair[0].time > pair[1].time));
+
+    // Newest one is from before the last opened vault saved anything
+    let newest = Vault::open(&backups[0].path, PASSWORD).unwrap();
+    assert_eq!(newest.iter_labels().count(), 2 * (MAX_BACKUPS + 1));
+    let oldest = Vault::open(&backups[MAX_BACKUPS - 1].path, PASSWORD).unwrap();
+    assert_eq!(oldest.iter_labels().count(), 4);
+}
+
+#[test]
+fn usage_is_saved_without_backup() {
+    let (path, mut vault) = create_vault("usage");
+    vault.insert("github", "secret").unwrap();
+    vault.save().unwrap();
+    let backups = vault.backups().len();
+
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    vault.entry_mut("github").unwrap().mark_used();
+    vault.save_usage().unwrap();
+    assert_eq!(vault.backups().len(), backups);
+
+    let mut vault = Vault::open(&path, PASSWORD).unwrap();
+    assert!(vault.entry("github").unwrap().last_used.is_some());
+    vault.insert("bank", "secret").unwrap();
+    vault.save().unwrap();
+    assert_eq!(vault.backups().len(), backups + 1);
+}